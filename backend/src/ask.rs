@@ -1,7 +1,7 @@
 use axum::{
-    http::StatusCode,
-    extract::{Path, State},
-    response::{Redirect, IntoResponse, Response, Html},
+    http::{header, StatusCode, HeaderMap},
+    extract::{rejection::JsonRejection, ConnectInfo, Path, Query, State},
+    response::{Redirect, IntoResponse, Response, Html, sse::{Event as SseEvent, KeepAlive, Sse}},
     Json
 };
 use axum_extra::TypedHeader; 
@@ -16,9 +16,22 @@ use rand_core::OsRng;
 use uuid::Uuid;
 use std::fs;
 use std::time::{SystemTime, Duration};
-use std::collections::HashMap;
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::Instant;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 
 use crate::state::AppState;
+use crate::cal::{self, CalError, EventContent};
+use crate::owner;
+use crate::receipt;
+use crate::export::{self, ExportFormat};
+use crate::rate_limit::{extract_real_ip, normalize_ip, StreamSlot, STREAM_MAX_DURATION};
+use crate::ranking::{self, Ballot, RankMethod, RankOutcome};
+use crate::template::SurveyTemplate;
 
 /*
  * --- Limits ---
@@ -27,7 +40,14 @@ use crate::state::AppState;
 const MAX_TITLE_LEN: usize = 255;
 const MAX_ITEM_LEN: usize = 255;
 const MAX_ITEMS: usize = 100;
+const MAX_QUESTIONS: usize = 50;
+const MAX_WRITE_IN_LEN: usize = 100;
+const DEFAULT_MAX_WRITE_INS: usize = 20;
 const MAX_VOTER_LEN: usize = 64; 
+const MAX_TEXT_LEN: usize = 4096;
+const DEFAULT_MAX_TEXT_LEN: usize = 1000;
+const MIN_RATING: i32 = -100;
+const MAX_RATING: i32 = 100;
 const MAX_PASSWORD_LENGTH: usize = 256;
 
 /*
@@ -42,28 +62,225 @@ pub struct Survey {
     pub expiry: u32,
     pub created_at: SystemTime,
     pub votes: Vec<StoredVote>,
+    pub manage_token_hash: Option<String>,
+    pub version: u64, // bumped by every content write, update_survey only applies to the version it read
 }
 
 
 #[derive(Deserialize)]
 pub struct NewSurvey {
-    pub survey_type: SurveyType,
-    pub title: String,
-    pub items: Vec<String>,
+    #[serde(default)]
+    pub title: String, // may be left empty when starting from a template
+    #[serde(default)]
+    pub template: Option<SurveyTemplate>,
+    #[serde(default)]
+    pub questions: Vec<NewQuestion>,
+    // Single-question surveys may still send the question fields at the top level
+    #[serde(flatten)]
+    pub question: Option<NewQuestion>,
+    #[serde(default)]
+    pub opens_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub closes_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub results_visibility: ResultsVisibility,
+    #[serde(default)]
+    pub one_vote_per_participant: bool,
     #[serde(default)]
     pub password: String,
     pub expiry: u32,
 }
 
+#[derive(Deserialize)]
+pub struct NewQuestion {
+    #[serde(default)]
+    pub prompt: String,
+    pub survey_type: SurveyType,
+    #[serde(default)]
+    pub items: Vec<String>,
+    #[serde(default = "default_true")]
+    pub required: bool,
+    #[serde(default)]
+    pub slots: Vec<NewSlot>,
+    #[serde(default)]
+    pub timezone: String,
+    #[serde(default)]
+    pub rank_method: Option<RankMethod>,
+    #[serde(default)]
+    pub min_choices: Option<usize>,
+    #[serde(default)]
+    pub max_choices: Option<usize>,
+    #[serde(default)]
+    pub rating_range: Option<RatingRange>,
+    #[serde(default)]
+    pub rating_ranges: Option<Vec<RatingRange>>,
+    #[serde(default)]
+    pub min_text_len: Option<usize>,
+    #[serde(default)]
+    pub max_text_len: Option<usize>,
+    #[serde(default)]
+    pub allow_write_ins: bool,
+    #[serde(default)]
+    pub max_write_ins: Option<usize>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "SurveyContentRepr", into = "SurveyContentRepr")]
 pub struct SurveyContent {
     pub title: String,
+    pub questions: Vec<Question>,
+    pub opens_at: Option<DateTime<Utc>>,
+    pub closes_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>, // set when the owner closed voting early
+    pub results_visibility: ResultsVisibility,
+    pub one_vote_per_participant: bool, // best-effort, voters are told apart by the polly_voter cookie
+}
+
+// Single-question surveys keep the flat layout they have always been stored and served in
+#[derive(Serialize, Deserialize)]
+struct SurveyContentRepr {
+    title: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    questions: Vec<Question>,
+    #[serde(flatten)]
+    question: Option<Question>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    opens_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    closes_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    closed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    results_visibility: ResultsVisibility,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    one_vote_per_participant: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Question {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prompt: String,
     pub survey_type: SurveyType,
     pub items: Vec<String>,
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank_method: Option<RankMethod>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_choices: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_choices: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating_ranges: Option<Vec<RatingRange>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_text_len: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_text_len: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slots: Option<Vec<TimeSlot>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finalized: Option<FinalizedSlot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_ins: Option<WriteIns>,
+}
+
+#[derive(Deserialize)]
+pub struct NewSlot {
+    pub from: String,
+    pub to: String,
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct TimeSlot {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FinalizedSlot {
+    pub slot: usize,
+    pub event_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct FinalizeRequest {
+    #[serde(default)]
+    pub question: Option<usize>,
+    #[serde(default)]
+    pub slot: Option<usize>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub expiry: Option<u32>,
+    pub uses: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct FinalizeResponse {
+    pub status: SurveyStatus,
+    pub id: String,
+    pub question: usize,
+    pub slot: usize,
+    pub event_id: String,
+    pub event_manage_token: String,
+    pub ics: String,
+    pub message: String,
+}
+
+// Voters may append items of their own until `max` of them were added
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct WriteIns {
+    pub max: usize,
+    #[serde(default)]
+    pub added: usize,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct RatingRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateSurvey {
+    pub title: Option<String>,
+    #[serde(default)]
+    pub question: Option<usize>, // whose items are replaced, defaults to the first
+    pub items: Option<Vec<String>>,
+    pub results_visibility: Option<ResultsVisibility>,
+    pub password: Option<String>,
+    pub expiry: Option<u32>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct CloneRequest {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>, // keeps the original password unless set
+    #[serde(default)]
+    pub expiry: Option<u32>, // defaults to the original lifetime
+}
+
+#[derive(Deserialize, Default)]
+pub struct ReopenRequest {
+    #[serde(default)]
+    pub closes_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize)]
 pub struct CreateSurveyResponse {
+    pub status: SurveyStatus,
+    pub id: String,
+    pub manage_token: String,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct ManageSurveyResponse {
     pub status: SurveyStatus,
     pub id: String,
     pub message: String,
@@ -73,15 +290,46 @@ pub struct CreateSurveyResponse {
 pub struct SurveyData {
     pub id: String,
     pub content: Option<SurveyContent>,
+    pub voting: VotingState,
+    pub results_visible: bool,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub votes: Vec<StoredVote>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<SurveyResults>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "StoredVoteRepr", into = "StoredVoteRepr")]
 pub struct StoredVote {
-    pub vote: HashMap<usize, i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    pub answers: BTreeMap<usize, Answer>, // keyed by question index
     pub voter: Option<String>,
+    // Kept by the stores next to the ballot, never serialized
+    pub ballot_id: Option<String>,
+    pub participant: Option<String>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Answer {
+    #[serde(default)]
+    pub vote: HashMap<usize, i32>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub text: HashMap<usize, String>,
+}
+
+// The first question's answer stays in the flat vote/text fields so existing ballots and
+// clients keep working; answers to any further questions live under `answers`
+#[derive(Serialize, Deserialize)]
+struct StoredVoteRepr {
+    #[serde(default)]
+    vote: HashMap<usize, i32>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    text: HashMap<usize, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    answers: BTreeMap<usize, Answer>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    voter: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -100,10 +348,31 @@ pub struct SurveyResponse {
 }
 
 #[derive(Deserialize)]
+pub struct ResultsQuery {
+    #[serde(default = "default_true")]
+    pub ballots: bool,
+    #[serde(default)]
+    pub required: Option<String>, // comma separated voter names for matrix questions
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Clone, Deserialize)]
 pub struct VoteRequest {
+    #[serde(default)]
     pub vote: HashMap<usize, i32>,
     #[serde(default)]
+    pub text: HashMap<usize, String>,
+    #[serde(default)]
+    pub answers: BTreeMap<usize, Answer>,
+    #[serde(default)]
     pub voter: Option<String>,
+    #[serde(default)]
+    pub write_ins: BTreeMap<usize, String>, // new item per question, selected on this ballot
 }
 
 #[derive(Debug)]
@@ -111,8 +380,16 @@ pub enum AskError {
     Internal(String),
     InvalidInput(String), 
     PasswordHashError(String),
+    Forbidden,
     NotFound,
     Expired,
+    VotingNotOpen,
+    VotingClosed,
+    ResultsHidden,
+    InvalidReceipt,
+    AlreadyVoted,
+    Conflict,
+    TooManyStreams,
 }
 /*
  * --- Trait implementations ---
@@ -127,10 +404,26 @@ impl IntoResponse for AskError {
                 (StatusCode::BAD_REQUEST, e),
             AskError::PasswordHashError(e) => 
                 (StatusCode::INTERNAL_SERVER_ERROR, e),
+            AskError::Forbidden => 
+                (StatusCode::FORBIDDEN, "Invalid or missing management token".into()),
             AskError::NotFound => 
                 (StatusCode::NOT_FOUND, "Survey not found".into()),
             AskError::Expired => 
                 (StatusCode::GONE, "Survey expired".into()),
+            AskError::VotingNotOpen => 
+                (StatusCode::CONFLICT, "Voting has not opened yet".into()),
+            AskError::VotingClosed => 
+                (StatusCode::CONFLICT, "Voting is closed".into()),
+            AskError::ResultsHidden => 
+                (StatusCode::FORBIDDEN, "Results are not visible yet".into()),
+            AskError::InvalidReceipt => 
+                (StatusCode::FORBIDDEN, "Invalid or missing vote receipt".into()),
+            AskError::AlreadyVoted => 
+                (StatusCode::CONFLICT, "You have already voted. Use your vote receipt to change your ballot.".into()),
+            AskError::Conflict => 
+                (StatusCode::CONFLICT, "The survey was changed in the meantime. Reload it and try again.".into()),
+            AskError::TooManyStreams => 
+                (StatusCode::TOO_MANY_REQUESTS, "Too many open result streams".into()),
         };
 
         let body = Json(json!({
@@ -148,6 +441,99 @@ impl From<rusqlite::Error> for AskError {
     }
 }
 
+impl From<CalError> for AskError {
+    fn from(err: CalError) -> Self {
+        let (status, message) = err.status_and_message();
+
+        if status.is_server_error() {
+            AskError::Internal(message)
+        } else {
+            AskError::InvalidInput(message)
+        }
+    }
+}
+
+impl From<SurveyContentRepr> for SurveyContent {
+    fn from(repr: SurveyContentRepr) -> Self {
+        let questions = if repr.questions.is_empty() {
+            repr.question.into_iter().collect()
+        } else {
+            repr.questions
+        };
+
+        SurveyContent {
+            title: repr.title,
+            questions,
+            opens_at: repr.opens_at,
+            closes_at: repr.closes_at,
+            closed_at: repr.closed_at,
+            results_visibility: repr.results_visibility,
+            one_vote_per_participant: repr.one_vote_per_participant,
+        }
+    }
+}
+
+impl From<SurveyContent> for SurveyContentRepr {
+    fn from(content: SurveyContent) -> Self {
+        let mut questions = content.questions;
+
+        if questions.len() == 1 {
+            SurveyContentRepr {
+                title: content.title,
+                questions: Vec::new(),
+                question: questions.pop(),
+                opens_at: content.opens_at,
+                closes_at: content.closes_at,
+                closed_at: content.closed_at,
+                results_visibility: content.results_visibility,
+                one_vote_per_participant: content.one_vote_per_participant,
+            }
+        } else {
+            SurveyContentRepr {
+                title: content.title,
+                questions,
+                question: None,
+                opens_at: content.opens_at,
+                closes_at: content.closes_at,
+                closed_at: content.closed_at,
+                results_visibility: content.results_visibility,
+                one_vote_per_participant: content.one_vote_per_participant,
+            }
+        }
+    }
+}
+
+impl From<StoredVoteRepr> for StoredVote {
+    fn from(repr: StoredVoteRepr) -> Self {
+        let mut answers = repr.answers;
+
+        if !repr.vote.is_empty() || !repr.text.is_empty() {
+            answers.insert(0, Answer { vote: repr.vote, text: repr.text });
+        }
+
+        StoredVote {
+            answers,
+            voter: repr.voter,
+            ballot_id: None,
+            participant: None,
+        }
+    }
+}
+
+impl From<StoredVote> for StoredVoteRepr {
+    fn from(vote: StoredVote) -> Self {
+        let mut answers = vote.answers;
+        let first = answers.remove(&0).unwrap_or_default();
+
+        StoredVoteRepr {
+            vote: first.vote,
+            text: first.text,
+            answers,
+            voter: vote.voter,
+        }
+    }
+}
+
 /*
  * --- Helper Functions ---
  */
@@ -161,7 +547,7 @@ fn verify_password(survey: &Survey, auth: &Option<TypedHeader<Authorization<Basi
 
             if parsed_hash
                 .ok()
-                .map_or(false, |ph| argon2.verify_password(provided_pw.as_bytes(), &ph).is_ok())
+                .is_some_and(|ph| argon2.verify_password(provided_pw.as_bytes(), &ph).is_ok())
             {
                 Ok(())
             } else {
@@ -180,86 +566,92 @@ enum PasswordError {
     Incorrect,
 }
 
-fn success_response(survey: &Survey) -> SurveyResponse {
+// Bodies that are all optional fields may be left out entirely, anything sent has to be valid JSON
+fn optional_json<T: Default>(headers: &HeaderMap, body: Result<Json<T>, JsonRejection>) -> Result<T, AskError> {
+    let empty = headers
+        .get(header::CONTENT_LENGTH)
+        .map_or(!headers.contains_key(header::TRANSFER_ENCODING), |length| length == "0");
+
+    match body {
+        Ok(Json(body)) => Ok(body),
+        Err(JsonRejection::MissingJsonContentType(_)) if empty => Ok(T::default()),
+        Err(rejection) => Err(AskError::InvalidInput(rejection.body_text())),
+    }
+}
+
+// Hidden results leave out both the tallies and the raw ballots they could be derived from
+fn success_response(survey: &Survey, show_results: bool) -> SurveyResponse {
        SurveyResponse {
         status: SurveyStatus::Success,
         data: Some(SurveyData {
             id: survey.id.clone(),
             content: Some(survey.content.clone()),
-            votes: survey.votes.clone(),
+            voting: voting_state(&survey.content),
+            results_visible: show_results,
+            votes: if show_results { survey.votes.clone() } else { Vec::new() },
+            results: show_results.then(|| compute_results(survey)),
+            receipt: None,
         }),
         message: "Survey retrieved successfully.".into(),
     }
 }
 
-fn protected_response(message: &str) -> SurveyResponse {
+fn results_response(survey: &Survey, include_ballots: bool) -> SurveyResponse {
     SurveyResponse {
-        status: SurveyStatus::Protected,
-        data: None,
-        message: message.into(),
+        status: SurveyStatus::Success,
+        data: Some(SurveyData {
+            id: survey.id.clone(),
+            content: Some(survey.content.clone()),
+            voting: voting_state(&survey.content),
+            results_visible: true,
+            votes: if include_ballots { survey.votes.clone() } else { Vec::new() },
+            results: Some(compute_results(survey)),
+            receipt: None,
+        }),
+        message: "Survey results retrieved successfully.".into(),
     }
 }
 
-fn is_expired(survey: &Survey) -> bool {
-    if let Ok(elapsed) = survey.created_at.elapsed() {
-        elapsed > Duration::from_secs(survey.expiry as u64 * 3600)
-    } else {
-        true // treat weird timestamps as expired
-    }
+fn default_true() -> bool {
+    true
 }
 
-/*
- * --- Enums / Small types ---
- */
-
-#[derive(Clone, Copy, Deserialize, Serialize, Debug, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-pub enum SurveyType {
-    SingleChoice,
-    MultipleChoice,
-    RankChoice,
-    MatrixChoice,
+fn is_true(value: &bool) -> bool {
+    *value
 }
 
-/*
- * --- Handlers ---
- */
-
-// Redirect /ask to /ask.html
-pub async fn ask_html() -> impl IntoResponse {
-    Redirect::to("/ask.html")
+fn protected_response(message: &str) -> SurveyResponse {
+    SurveyResponse {
+        status: SurveyStatus::Protected,
+        data: None,
+        message: message.into(),
+    }
 }
 
-pub async fn serve_ask_html() -> impl IntoResponse {
-    let html = fs::read_to_string("static/ask.html")
-        .expect("ask.html not found");
-    Html(html)
+fn validate_expiry(expiry: u32) -> Result<u32, AskError> {
+    if expiry == 0 || expiry > 9999 {
+        return Err(AskError::InvalidInput("Invalid expiry value".into()));
+    }
+
+    Ok(expiry)
 }
 
-// Create new survey 
-pub async fn create_survey(
-    State(state): State<AppState>,
-    Json(new_survey): Json<NewSurvey>,
-) -> Result<Json<CreateSurveyResponse>, AskError>{
-    let id = Uuid::new_v4().to_string();
+fn sanitize_title(raw: &str) -> Result<String, AskError> {
+    let title = raw.trim().to_string();
 
-    // Sanitize and validate settings values 
-    if new_survey.expiry == 0 || new_survey.expiry > 9999 {
-        return Err(AskError::InvalidInput("Invalid expiry value".into()));
-    }
-    
-    // Sanitize and validate title
-    let title = new_survey.title.trim().to_string();
     if title.is_empty() {
         return Err(AskError::InvalidInput("Title cannot be empty".into()));
     }
+
     if title.len() > MAX_TITLE_LEN {
         return Err(AskError::InvalidInput(format!("Title too long (max {} chars)", MAX_TITLE_LEN)));
     }
 
-    // Sanitize and validate items
-    let items: Vec<String> = new_survey
-        .items
+    Ok(title)
+}
+
+fn sanitize_items(raw: Vec<String>) -> Result<Vec<String>, AskError> {
+    let items: Vec<String> = raw
         .into_iter()
         .map(|s| s.trim().to_string())
         .filter(|s| !s.is_empty())
@@ -277,92 +669,1729 @@ pub async fn create_survey(
         }
     }
 
-    // Validate password length
-    if new_survey.password.len() > MAX_PASSWORD_LENGTH {
-        return Err(AskError::InvalidInput("Password too long".into()));
+    Ok(items)
+}
+
+fn validate_choice_limits(
+    survey_type: SurveyType,
+    min_choices: Option<usize>,
+    max_choices: Option<usize>,
+    num_items: usize,
+) -> Result<(), AskError> {
+    if min_choices.is_none() && max_choices.is_none() {
+        return Ok(());
     }
 
-    // Create password hash if a password was set
-    let password_hash = if !new_survey.password.is_empty() {
-        let mut rng = OsRng;
-        let salt = SaltString::generate(&mut rng);
-        let argon2 = Argon2::default();
-        let hash = argon2
-            .hash_password(new_survey.password.as_bytes(), &salt)
-            .map_err(|e| AskError::PasswordHashError(e.to_string()))?
-            .to_string();
-        Some(hash)
-    } else {
-        None
-    };
+    if survey_type != SurveyType::MultipleChoice {
+        return Err(AskError::InvalidInput("Selection limits are only allowed for multiple choice surveys".into()));
+    }
 
-    // Build content
-    let survey_content = SurveyContent {
-        survey_type: new_survey.survey_type,
-        title,
-        items,
-    };
-    
-    // Create proper survey
-    let survey = Survey {
-        id: id.clone(),
-        content: survey_content,
-        password_hash,
-        expiry: new_survey.expiry,
-        created_at: SystemTime::now(),
-        votes: Vec::new(),
-    };
+    let min = min_choices.unwrap_or(1);
+    let max = max_choices.unwrap_or(num_items);
 
-    state.ask.create_survey(survey).await?;
+    if min == 0 {
+        return Err(AskError::InvalidInput("Minimum selections must be at least 1".into()));
+    }
+    if max > num_items {
+        return Err(AskError::InvalidInput(format!("Maximum selections cannot exceed the number of items ({})", num_items)));
+    }
+    if min > max {
+        return Err(AskError::InvalidInput("Minimum selections cannot exceed maximum selections".into()));
+    }
 
-    // Return the survey id to the frontend
-    Ok(Json(CreateSurveyResponse {
-        status: SurveyStatus::Success,
-        id,
-        message: "Survey created successfully.".into(),
-    }))
+    Ok(())
 }
 
-// Retrieve existing survey
-pub async fn get_survey(
-    Path(id): Path<String>,
-    State(state): State<AppState>,
-    auth: Option<TypedHeader<Authorization<Basic>>>,
-) -> Result<Json<SurveyResponse>, AskError> {
-    let survey = state.ask.get_survey(&id).await?;
+fn sanitize_prompt(raw: &str, required: bool) -> Result<String, AskError> {
+    let prompt = raw.trim().to_string();
 
-    // Expiry check 
-    if is_expired(&survey) {
-        return Err(AskError::Expired);
+    if prompt.is_empty() && required {
+        return Err(AskError::InvalidInput("Question text cannot be empty".into()));
     }
 
-    // Password check 
-    match verify_password(&survey, &auth) {
-        Ok(()) => {
-            Ok(Json(success_response(&survey)))
-        }
-        Err(PasswordError::Missing) => {
-            Ok(Json(protected_response("This survey is password protected.")))
-        }
-        Err(PasswordError::Incorrect) => {
-            Ok(Json(protected_response("Incorrect password.")))
+    if prompt.len() > MAX_TITLE_LEN {
+        return Err(AskError::InvalidInput(format!("Question text too long (max {} chars)", MAX_TITLE_LEN)));
+    }
+
+    Ok(prompt)
+}
+
+// Errors of multi-question surveys name the question they belong to
+fn question_error(multi: bool, index: usize, err: AskError) -> AskError {
+    match err {
+        AskError::InvalidInput(message) if multi => {
+            AskError::InvalidInput(format!("Question {}: {}", index + 1, message))
         }
+        err => err,
     }
 }
 
-pub async fn vote_survey(
-    Path(id): Path<String>,
-    State(state): State<AppState>,
-    auth: Option<TypedHeader<Authorization<Basic>>>,
-    Json(vote_req): Json<VoteRequest>,
-) -> Result<Json<SurveyResponse>, AskError> {
-    let survey = state.ask.get_survey(&id).await?;
+fn build_question(new_question: NewQuestion, multi: bool) -> Result<Question, AskError> {
+    // Questions of multi-question surveys need their own text; single ones use the title
+    let prompt = sanitize_prompt(&new_question.prompt, multi)?;
+
+    // Sanitize and validate items; scheduling polls derive them from their slots
+    let (items, slots, timezone) = if new_question.survey_type == SurveyType::Schedule {
+        if !new_question.items.is_empty() {
+            return Err(AskError::InvalidInput("Scheduling polls take slots instead of items".into()));
+        }
+
+        let (slots, tz_str) = parse_slots(&new_question.slots, &new_question.timezone)?;
+        (slot_labels(&slots, &tz_str), Some(slots), Some(tz_str))
+    } else {
+        if !new_question.slots.is_empty() {
+            return Err(AskError::InvalidInput("Slots are only allowed for scheduling polls".into()));
+        }
+
+        (sanitize_items(new_question.items)?, None, None)
+    };
+
+    // Selection limits only apply to multiple choice surveys
+    validate_choice_limits(new_question.survey_type, new_question.min_choices, new_question.max_choices, items.len())?;
+
+    // Answer length limits only apply to free text surveys
+    validate_text_limits(new_question.survey_type, new_question.min_text_len, new_question.max_text_len)?;
+
+    // Rating ranges only apply to rating surveys
+    let rating_ranges = rating_ranges(
+        new_question.survey_type,
+        new_question.rating_range,
+        new_question.rating_ranges,
+        items.len(),
+    )?;
+
+    // Counting method only applies to rank surveys
+    let rank_method = match (new_question.survey_type, new_question.rank_method) {
+        (SurveyType::RankChoice, method) => Some(method.unwrap_or_default()),
+        (_, None) => None,
+        (_, Some(_)) => {
+            return Err(AskError::InvalidInput("Counting method is only allowed for rank surveys".into()));
+        }
+    };
+
+    // Write-ins only apply to plain choice surveys
+    let write_ins = write_in_policy(
+        new_question.survey_type,
+        new_question.allow_write_ins,
+        new_question.max_write_ins,
+    )?;
+
+    Ok(Question {
+        prompt,
+        survey_type: new_question.survey_type,
+        items,
+        required: new_question.required,
+        rank_method,
+        min_choices: new_question.min_choices,
+        max_choices: new_question.max_choices,
+        rating_ranges,
+        min_text_len: new_question.min_text_len,
+        max_text_len: new_question.max_text_len,
+        slots,
+        timezone,
+        finalized: None,
+        write_ins,
+    })
+}
+
+fn accepts_write_ins(survey_type: SurveyType) -> bool {
+    matches!(survey_type, SurveyType::SingleChoice | SurveyType::MultipleChoice | SurveyType::Approval)
+}
+
+fn write_in_policy(
+    survey_type: SurveyType,
+    allow: bool,
+    max: Option<usize>,
+) -> Result<Option<WriteIns>, AskError> {
+    if !allow {
+        if max.is_some() {
+            return Err(AskError::InvalidInput("Write-in limit requires write-ins to be allowed".into()));
+        }
+        return Ok(None);
+    }
+
+    if !accepts_write_ins(survey_type) {
+        return Err(AskError::InvalidInput("Write-ins are only allowed for single, multiple choice and approval surveys".into()));
+    }
+
+    let max = max.unwrap_or(DEFAULT_MAX_WRITE_INS);
+    if max == 0 || max > MAX_ITEMS {
+        return Err(AskError::InvalidInput(format!("Write-in limit must be between 1 and {}", MAX_ITEMS)));
+    }
+
+    Ok(Some(WriteIns { max, added: 0 }))
+}
+
+fn sanitize_write_in(raw: &str) -> Result<String, AskError> {
+    let item = raw.trim();
+
+    if item.is_empty() {
+        return Err(AskError::InvalidInput("Write-in cannot be empty".into()));
+    }
+    if item.chars().count() > MAX_WRITE_IN_LEN {
+        return Err(AskError::InvalidInput(format!("Write-in too long (max {} chars)", MAX_WRITE_IN_LEN)));
+    }
+
+    Ok(item.to_string())
+}
+
+// Appends a write-in and returns its index; an item that already exists is reused instead.
+// Stores place write-ins while holding the survey exclusively, so concurrent write-ins cannot clash
+pub fn append_write_in(question: &mut Question, item: &str) -> Result<usize, AskError> {
+    let write_ins = question
+        .write_ins
+        .as_mut()
+        .ok_or_else(|| AskError::InvalidInput("This question does not accept write-ins".into()))?;
+
+    if let Some(index) = question.items.iter().position(|existing| existing.to_lowercase() == item.to_lowercase()) {
+        return Ok(index);
+    }
+
+    if write_ins.added >= write_ins.max || question.items.len() >= MAX_ITEMS {
+        return Err(AskError::InvalidInput("No more write-ins are accepted for this question".into()));
+    }
+
+    question.items.push(item.to_string());
+    write_ins.added += 1;
+
+    Ok(question.items.len() - 1)
+}
+
+// Selects a write-in on the ballot, next to whatever else the voter picked for that question
+fn select_write_in(vote_req: &mut VoteRequest, question: usize, index: usize) {
+    let vote = if question == 0 && !vote_req.answers.contains_key(&0) {
+        &mut vote_req.vote
+    } else {
+        &mut vote_req.answers.entry(question).or_default().vote
+    };
+
+    vote.insert(index, CHOICE_SELECTED);
+}
+
+// A write-in at the position the ballot was validated with; stores place it for real next to the vote
+#[derive(Clone, Debug)]
+pub struct WriteIn {
+    pub question: usize,
+    pub item: String,
+    pub preview: usize,
+}
+
+// Appends the ballot's write-ins and moves its selections to where they actually landed.
+// Stores call this in the same transaction as the vote, so a rejected vote leaves no stray items
+pub fn place_write_ins(content: &mut SurveyContent, vote: &mut StoredVote, write_ins: &[WriteIn]) -> Result<(), AskError> {
+    let multi = content.questions.len() > 1;
+
+    for write_in in write_ins {
+        let question = content
+            .questions
+            .get_mut(write_in.question)
+            .ok_or_else(|| AskError::InvalidInput(format!("Invalid question index: {}", write_in.question)))?;
+        let position = append_write_in(question, &write_in.item)
+            .map_err(|e| question_error(multi, write_in.question, e))?;
+
+        // Another voter may have written in since the ballot was checked
+        if position != write_in.preview {
+            if let Some(answer) = vote.answers.get_mut(&write_in.question) {
+                if let Some(value) = answer.vote.remove(&write_in.preview) {
+                    answer.vote.insert(position, value);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_matrix(survey_type: SurveyType) -> bool {
+    matches!(survey_type, SurveyType::MatrixChoice | SurveyType::Schedule)
+}
+
+// Voters have to name themselves as soon as one question shows answers per person
+pub fn needs_voter(content: &SurveyContent) -> bool {
+    content.questions.iter().any(|q| is_matrix(q.survey_type))
+}
+
+// Skipped optional questions are dropped, every other answer is checked against its question
+fn validate_answers(content: &SurveyContent, answers: &mut BTreeMap<usize, Answer>) -> Result<(), AskError> {
+    let multi = content.questions.len() > 1;
+
+    if let Some(&index) = answers.keys().find(|&&index| index >= content.questions.len()) {
+        return Err(AskError::InvalidInput(format!("Invalid question index: {}", index)));
+    }
+
+    for (index, question) in content.questions.iter().enumerate() {
+        if let Some(answer) = answers.get_mut(&index) {
+            answer.text = std::mem::take(&mut answer.text)
+                .into_iter()
+                .map(|(item, text)| (item, text.trim().to_string()))
+                .filter(|(_, text)| !text.is_empty())
+                .collect();
+
+            if !question.required && answer.vote.is_empty() && answer.text.is_empty() {
+                answers.remove(&index);
+            }
+        }
+
+        match answers.get(&index) {
+            Some(answer) => validate_ballot(question, &answer.vote, &answer.text)
+                .map_err(|e| question_error(multi, index, e))?,
+            None if question.required => {
+                return Err(question_error(
+                    multi,
+                    index,
+                    AskError::InvalidInput("This question requires an answer.".into()),
+                ));
+            }
+            None => {}
+        }
+    }
+
+    if answers.is_empty() {
+        return Err(AskError::InvalidInput("Cannot submit empty vote.".into()));
+    }
+
+    Ok(())
+}
+
+// Choice surveys mark a selected item with 0, matching the frontend
+pub const CHOICE_SELECTED: i32 = 0;
+
+fn validate_ballot(
+    content: &Question,
+    vote: &HashMap<usize, i32>,
+    text: &HashMap<usize, String>,
+) -> Result<(), AskError> {
+    // Make sure vote indices exist
+    let num_items = content.items.len();
+    for &index in vote.keys().chain(text.keys()) {
+        if index >= num_items {
+            return Err(AskError::InvalidInput(format!("Invalid vote index: {}", index)));
+        }
+    }
+
+    // Only free text surveys carry text answers, and only they may omit the vote map
+    if content.survey_type == SurveyType::FreeText {
+        if !vote.is_empty() {
+            return Err(AskError::InvalidInput("Free text surveys only accept text answers.".into()));
+        }
+        return validate_text_answers(content, text);
+    }
+
+    if !text.is_empty() {
+        return Err(AskError::InvalidInput("Text answers are only allowed for free text surveys.".into()));
+    }
+
+    if vote.is_empty() {
+        return Err(AskError::InvalidInput("Cannot submit empty vote.".into()));
+    }
+
+    match content.survey_type {
+        SurveyType::SingleChoice => {
+            if vote.len() != 1 {
+                return Err(AskError::InvalidInput("Single choice surveys require exactly one selection.".into()));
+            }
+            if vote.values().any(|&v| v != CHOICE_SELECTED) {
+                return Err(AskError::InvalidInput("Invalid selection value.".into()));
+            }
+        }
+        SurveyType::MultipleChoice => {
+            if vote.values().any(|&v| v != CHOICE_SELECTED) {
+                return Err(AskError::InvalidInput("Invalid selection value.".into()));
+            }
+
+            let min = content.min_choices.unwrap_or(1);
+            let max = content.max_choices.unwrap_or(num_items);
+
+            if vote.len() < min {
+                return Err(AskError::InvalidInput(format!("Select at least {} items.", min)));
+            }
+            if vote.len() > max {
+                return Err(AskError::InvalidInput(format!("Select at most {} items.", max)));
+            }
+        }
+        SurveyType::RankChoice => {
+            // Every item gets a rank; ranks are stored zero-based, so n items use exactly 0..n (positions 1..n)
+            let mut ranks: Vec<i32> = vote.values().copied().collect();
+            ranks.sort_unstable();
+
+            if ranks.iter().any(|&r| r < 0) {
+                return Err(AskError::InvalidInput("Ranks cannot be negative.".into()));
+            }
+            if ranks.windows(2).any(|w| w[0] == w[1]) {
+                return Err(AskError::InvalidInput("Each rank can only be used once.".into()));
+            }
+            if ranks.len() != num_items {
+                return Err(AskError::InvalidInput(format!("Rank all {} items, not {}.", num_items, ranks.len())));
+            }
+            if ranks.iter().any(|&r| r as usize >= num_items) {
+                return Err(AskError::InvalidInput(format!("Ranks must be positions 1 to {}.", num_items)));
+            }
+        }
+        SurveyType::MatrixChoice | SurveyType::Schedule => {
+            if vote.values().any(|v| !MATRIX_ANSWERS.contains(v)) {
+                return Err(AskError::InvalidInput("Matrix answers must be yes, maybe or no.".into()));
+            }
+        }
+        SurveyType::RatingScale => {
+            for (&index, &rating) in vote {
+                let range = rating_range_for(content, index);
+                if rating < range.min || rating > range.max {
+                    return Err(AskError::InvalidInput(format!(
+                        "Rating for item {} must be between {} and {}.",
+                        index + 1, range.min, range.max
+                    )));
+                }
+            }
+        }
+        SurveyType::Approval => {
+            if vote.values().any(|&v| v != CHOICE_SELECTED) {
+                return Err(AskError::InvalidInput("Invalid approval value.".into()));
+            }
+        }
+        SurveyType::FreeText => unreachable!("handled above"),
+    }
+
+    Ok(())
+}
+
+fn validate_text_answers(content: &Question, text: &HashMap<usize, String>) -> Result<(), AskError> {
+    let min = content.min_text_len.unwrap_or(1).max(1);
+    let max = content.max_text_len.unwrap_or(DEFAULT_MAX_TEXT_LEN);
+
+    let answers: Vec<(usize, usize)> = text
+        .iter()
+        .map(|(&index, answer)| (index, answer.trim().chars().count()))
+        .filter(|&(_, len)| len > 0)
+        .collect();
+
+    if answers.is_empty() {
+        return Err(AskError::InvalidInput("Cannot submit empty answers.".into()));
+    }
+
+    for (index, len) in answers {
+        if len < min {
+            return Err(AskError::InvalidInput(format!("Answer {} is too short (min {} chars).", index + 1, min)));
+        }
+        if len > max {
+            return Err(AskError::InvalidInput(format!("Answer {} is too long (max {} chars).", index + 1, max)));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_text_limits(
+    survey_type: SurveyType,
+    min_text_len: Option<usize>,
+    max_text_len: Option<usize>,
+) -> Result<(), AskError> {
+    if min_text_len.is_none() && max_text_len.is_none() {
+        return Ok(());
+    }
+
+    if survey_type != SurveyType::FreeText {
+        return Err(AskError::InvalidInput("Text length limits are only allowed for free text surveys".into()));
+    }
+
+    let min = min_text_len.unwrap_or(1);
+    let max = max_text_len.unwrap_or(DEFAULT_MAX_TEXT_LEN);
+
+    if max == 0 || max > MAX_TEXT_LEN {
+        return Err(AskError::InvalidInput(format!("Maximum answer length must be between 1 and {}", MAX_TEXT_LEN)));
+    }
+    if min > max {
+        return Err(AskError::InvalidInput("Minimum answer length cannot exceed maximum answer length".into()));
+    }
+
+    Ok(())
+}
+
+// Resolves the per-item ranges for rating surveys; a single range applies to every item
+fn rating_ranges(
+    survey_type: SurveyType,
+    range: Option<RatingRange>,
+    ranges: Option<Vec<RatingRange>>,
+    num_items: usize,
+) -> Result<Option<Vec<RatingRange>>, AskError> {
+    if survey_type != SurveyType::RatingScale {
+        if range.is_some() || ranges.is_some() {
+            return Err(AskError::InvalidInput("Rating ranges are only allowed for rating surveys".into()));
+        }
+        return Ok(None);
+    }
+
+    let ranges = match (range, ranges) {
+        (Some(_), Some(_)) => {
+            return Err(AskError::InvalidInput("Use either rating_range or rating_ranges, not both".into()));
+        }
+        (_, Some(ranges)) => ranges,
+        (range, None) => vec![range.unwrap_or(DEFAULT_RATING_RANGE); num_items],
+    };
+
+    if ranges.len() != num_items {
+        return Err(AskError::InvalidInput("Each item needs exactly one rating range".into()));
+    }
+
+    for range in &ranges {
+        if range.min >= range.max || range.min < MIN_RATING || range.max > MAX_RATING {
+            return Err(AskError::InvalidInput(format!(
+                "Rating ranges must have min < max within {} to {}", MIN_RATING, MAX_RATING
+            )));
+        }
+    }
+
+    Ok(Some(ranges))
+}
+
+const DEFAULT_RATING_RANGE: RatingRange = RatingRange { min: 1, max: 5 };
+
+fn rating_range_for(content: &Question, index: usize) -> RatingRange {
+    content
+        .rating_ranges
+        .as_ref()
+        .and_then(|ranges| ranges.get(index))
+        .copied()
+        .unwrap_or(DEFAULT_RATING_RANGE)
+}
+
+// Slots are parsed exactly like event dates and stored in UTC
+fn parse_slots(raw: &[NewSlot], timezone: &str) -> Result<(Vec<TimeSlot>, String), AskError> {
+    if raw.is_empty() {
+        return Err(AskError::InvalidInput("At least one slot is required".into()));
+    }
+    if raw.len() > MAX_ITEMS {
+        return Err(AskError::InvalidInput(format!("Too many slots (max {})", MAX_ITEMS)));
+    }
+
+    let mut tz_str = String::new();
+    let mut slots = Vec::with_capacity(raw.len());
+
+    for (index, slot) in raw.iter().enumerate() {
+        let (start, end, tz) = cal::parse_event_range(&slot.from, &slot.to, &slot.start, &slot.end, timezone)
+            .map_err(|e| {
+                let (_, message) = e.status_and_message();
+                AskError::InvalidInput(format!("Slot {}: {}", index + 1, message))
+            })?;
+
+        tz_str = tz;
+        slots.push(TimeSlot { start, end });
+    }
+
+    Ok((slots, tz_str))
+}
+
+fn slot_labels(slots: &[TimeSlot], timezone: &str) -> Vec<String> {
+    let tz: Tz = timezone.parse().unwrap_or(chrono_tz::UTC);
+
+    slots
+        .iter()
+        .map(|slot| {
+            let start = slot.start.with_timezone(&tz);
+            let end = slot.end.with_timezone(&tz);
+
+            if start.date_naive() == end.date_naive() {
+                format!("{} - {}", start.format("%Y-%m-%d %H:%M"), end.format("%H:%M"))
+            } else {
+                format!("{} - {}", start.format("%Y-%m-%d %H:%M"), end.format("%Y-%m-%d %H:%M"))
+            }
+        })
+        .collect()
+}
+
+// The best ranked slot somebody can attend at all
+fn best_slot(survey: &Survey, question: usize) -> Option<usize> {
+    let items = &survey.content.questions[question].items;
+
+    match tally_matrix(items, &answers_to(&survey.votes, question)) {
+        Tally::Matrix { items, ranking, .. } => ranking.into_iter().find(|&index| items[index].score > 0),
+        _ => None,
+    }
+}
+
+fn check_voting_open(survey: &Survey) -> Result<(), AskError> {
+    match voting_state(&survey.content) {
+        VotingState::Open => {}
+        VotingState::Scheduled => return Err(AskError::VotingNotOpen),
+        VotingState::Closed => return Err(AskError::VotingClosed),
+    }
+
+    // Finalized scheduling polls no longer accept votes
+    if survey.content.questions.iter().any(|q| q.finalized.is_some()) {
+        return Err(AskError::InvalidInput("This scheduling poll has been finalized.".into()));
+    }
+
+    Ok(())
+}
+
+// Validates a ballot; `replacing` names the ballot it overwrites so its voter name stays usable
+fn build_vote(survey: &Survey, vote_req: VoteRequest, replacing: Option<&str>) -> Result<StoredVote, AskError> {
+    // The flat vote/text fields answer the first question
+    let mut answers = vote_req.answers;
+    let flat_answer = !vote_req.vote.is_empty() || !vote_req.text.is_empty();
+    if answers.contains_key(&0) && flat_answer {
+        return Err(AskError::InvalidInput("Answer the first question either flat or under answers, not both.".into()));
+    }
+    answers.entry(0).or_insert(Answer { vote: vote_req.vote, text: vote_req.text });
+
+    validate_answers(&survey.content, &mut answers)?;
+
+    // Voter name validation for surveys with matrix or scheduling questions
+    if needs_voter(&survey.content) {
+        let name = vote_req.voter.as_ref().ok_or_else(|| {
+            AskError::InvalidInput(
+                "Voter name is required for matrix and scheduling surveys.".into()
+            )
+        })?;
+
+        if name.trim().is_empty() {
+            return Err(AskError::InvalidInput(
+                "Voter name cannot be empty.".into()
+            ));
+        }
+
+        if name.len() > MAX_VOTER_LEN {
+            return Err(AskError::InvalidInput
+                ("Voter name too long.".into()
+            ));
+        }
+
+        if survey.votes.iter().any(|v| {
+            (replacing.is_none() || v.ballot_id.as_deref() != replacing) && v.voter
+                .as_ref()
+                .map(|existing| existing.trim().to_lowercase() == name.to_lowercase().trim())
+                .unwrap_or(false)
+        }) {
+            return Err(AskError::InvalidInput(
+                "Voter name has already been used.".into(),
+            ));
+        }
+    } else if vote_req.voter.is_some() {
+        return Err(AskError::InvalidInput(
+            "Voter name is only allowed for matrix and scheduling surveys.".into(),
+        ));
+    }
+
+    Ok(StoredVote {
+        answers,
+        voter: vote_req.voter,
+        ballot_id: None,
+        participant: None,
+    })
+}
+
+// Adds the ballot's write-ins to a copy of the survey and selects them, so the ballot can be
+// checked before anything is stored. The returned write-ins go to the store with the vote
+fn preview_write_ins(
+    survey: &Survey,
+    mut vote_req: VoteRequest,
+) -> Result<(Survey, VoteRequest, Vec<WriteIn>), AskError> {
+    let mut preview = survey.clone();
+    let multi = survey.content.questions.len() > 1;
+
+    let mut write_ins = Vec::new();
+    for (&index, raw) in &vote_req.write_ins {
+        if index >= survey.content.questions.len() {
+            return Err(AskError::InvalidInput(format!("Invalid question index: {}", index)));
+        }
+
+        let item = sanitize_write_in(raw).map_err(|e| question_error(multi, index, e))?;
+        let position = append_write_in(&mut preview.content.questions[index], &item)
+            .map_err(|e| question_error(multi, index, e))?;
+        write_ins.push(WriteIn { question: index, item, preview: position });
+    }
+
+    for write_in in &write_ins {
+        select_write_in(&mut vote_req, write_in.question, write_in.preview);
+    }
+
+    Ok((preview, vote_req, write_ins))
+}
+
+// Receipts are only issued in one-vote-per-participant mode
+fn receipt_ballot<'a>(state: &AppState, survey: &'a Survey, headers: &HeaderMap) -> Result<&'a StoredVote, AskError> {
+    if !survey.content.one_vote_per_participant {
+        return Err(AskError::InvalidInput("This survey does not issue vote receipts.".into()));
+    }
+
+    let ballot_id = receipt::verify(&state.receipt_secret, &survey.id, headers)
+        .ok_or(AskError::InvalidReceipt)?;
+
+    survey
+        .votes
+        .iter()
+        .find(|v| v.ballot_id.as_deref() == Some(ballot_id.as_str()))
+        .ok_or(AskError::InvalidReceipt)
+}
+
+fn attach_receipt(response: &mut SurveyResponse, receipt: String) {
+    if let Some(data) = response.data.as_mut() {
+        data.receipt = Some(receipt);
+    }
+}
+
+fn hash_password(password: &str) -> Result<Option<String>, AskError> {
+    // Validate password length
+    if password.len() > MAX_PASSWORD_LENGTH {
+        return Err(AskError::InvalidInput("Password too long".into()));
+    }
+
+    // Create password hash if a password was set
+    if password.is_empty() {
+        return Ok(None);
+    }
+
+    let mut rng = OsRng;
+    let salt = SaltString::generate(&mut rng);
+    let argon2 = Argon2::default();
+    let hash = argon2
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| AskError::PasswordHashError(e.to_string()))?
+        .to_string();
+
+    Ok(Some(hash))
+}
+
+// Voting can end before the survey expires; the survey then stays readable with its results
+fn voting_state(content: &SurveyContent) -> VotingState {
+    let now = Utc::now();
+
+    if content.closed_at.is_some() || content.closes_at.is_some_and(|closes_at| closes_at <= now) {
+        VotingState::Closed
+    } else if content.opens_at.is_some_and(|opens_at| opens_at > now) {
+        VotingState::Scheduled
+    } else {
+        VotingState::Open
+    }
+}
+
+// The owner can always see results; voters see them once they cast a ballot in
+// after-vote mode, and everybody once voting has closed
+fn results_visible(state: &AppState, survey: &Survey, headers: &HeaderMap, just_voted: bool) -> bool {
+    let closed = voting_state(&survey.content) == VotingState::Closed;
+
+    let visible = match survey.content.results_visibility {
+        ResultsVisibility::Always => true,
+        ResultsVisibility::AfterVote => just_voted || closed || has_voted(state, survey, headers),
+        ResultsVisibility::AfterClose => closed,
+        ResultsVisibility::Owner => false,
+    };
+
+    visible || owner::verify_token(&survey.manage_token_hash, headers)
+}
+
+// Ballots are only linked to their voter in one-vote-per-participant mode, by the receipt or
+// the voter cookie; other surveys show after-vote results in the response to the vote alone
+fn has_voted(state: &AppState, survey: &Survey, headers: &HeaderMap) -> bool {
+    if !survey.content.one_vote_per_participant {
+        return false;
+    }
+
+    if receipt_ballot(state, survey, headers).is_ok() {
+        return true;
+    }
+
+    receipt::voter_token(headers).is_some_and(|token| {
+        let participant = receipt::participant(&state.receipt_secret, &survey.id, &token);
+        survey.votes.iter().any(|v| v.participant.as_deref() == Some(participant.as_str()))
+    })
+}
+
+fn validate_voting_window(
+    opens_at: Option<DateTime<Utc>>,
+    closes_at: Option<DateTime<Utc>>,
+) -> Result<(), AskError> {
+    if closes_at.is_some_and(|closes_at| closes_at <= Utc::now()) {
+        return Err(AskError::InvalidInput("Voting must close in the future".into()));
+    }
+
+    if let (Some(opens_at), Some(closes_at)) = (opens_at, closes_at) {
+        if opens_at >= closes_at {
+            return Err(AskError::InvalidInput("Voting must open before it closes".into()));
+        }
+    }
+
+    Ok(())
+}
+
+fn is_expired(survey: &Survey) -> bool {
+    if let Ok(elapsed) = survey.created_at.elapsed() {
+        elapsed > Duration::from_secs(survey.expiry as u64 * 3600)
+    } else {
+        true // treat weird timestamps as expired
+    }
+}
+
+// Wake live result streams; nobody listening is not an error
+fn publish_update(state: &AppState, id: &str) {
+    let _ = state.survey_updates.send(id.to_string());
+}
+
+// Per-connection state of a live result stream
+struct LiveResults {
+    state: AppState,
+    id: String,
+    headers: HeaderMap,
+    updates: broadcast::Receiver<String>,
+    deadline: Instant,
+    initial: Option<Survey>,
+    finished: bool,
+    _slot: StreamSlot,
+}
+
+impl LiveResults {
+    async fn next_event(&mut self) -> Option<SseEvent> {
+        if self.finished {
+            return None;
+        }
+
+        if let Some(survey) = self.initial.take() {
+            return Some(results_event(&survey));
+        }
+
+        loop {
+            // Streams end after a while; clients reconnect through the rate limiter
+            match tokio::time::timeout_at(self.deadline, self.updates.recv()).await {
+                Err(_) | Ok(Err(RecvError::Closed)) => return None,
+                Ok(Err(RecvError::Lagged(_))) => {} // missed some updates, reload to be safe
+                Ok(Ok(updated)) if updated != self.id => continue,
+                Ok(Ok(_)) => {}
+            }
+
+            let survey = match self.state.ask.get_survey(&self.id).await {
+                Ok(survey) if !is_expired(&survey) => survey,
+                _ => {
+                    self.finished = true;
+                    return Some(SseEvent::default().event("expired").data("Survey expired"));
+                }
+            };
+
+            // Visibility can be withdrawn again, e.g. by reopening an after-close survey
+            if results_visible(&self.state, &survey, &self.headers, false) {
+                return Some(results_event(&survey));
+            }
+        }
+    }
+}
+
+fn results_event(survey: &Survey) -> SseEvent {
+    let data = serde_json::to_string(&results_response(survey, false)).unwrap_or_default();
+    SseEvent::default().event("results").data(data)
+}
+
+/*
+ * --- Enums / Small types ---
+ */
+
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SurveyType {
+    SingleChoice,
+    MultipleChoice,
+    RankChoice,
+    MatrixChoice,
+    FreeText,
+    RatingScale,
+    Approval,
+    Schedule,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultsVisibility {
+    #[default]
+    Always,
+    AfterVote,
+    AfterClose,
+    Owner,
+}
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VotingState {
+    Scheduled,
+    Open,
+    Closed,
+}
+
+/*
+ * --- Results ---
+ */
+
+// Matrix answers as sent by the frontend
+pub const MATRIX_YES: i32 = 0;
+pub const MATRIX_MAYBE: i32 = 1;
+pub const MATRIX_NO: i32 = 2;
+const MATRIX_ANSWERS: [i32; 3] = [MATRIX_YES, MATRIX_MAYBE, MATRIX_NO];
+
+#[derive(Serialize)]
+pub struct SurveyResults {
+    pub ballots: usize,
+    pub questions: Vec<QuestionResults>,
+}
+
+#[derive(Serialize)]
+pub struct QuestionResults {
+    pub index: usize,
+    pub answered: usize,
+    pub tally: Tally,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Tally {
+    Choice {
+        items: Vec<ChoiceTally>,
+    },
+    Rank {
+        items: Vec<RankTally>,
+        ranking: Vec<usize>, // best first by the survey's counting method
+        outcome: RankOutcome,
+    },
+    Matrix {
+        items: Vec<MatrixTally>,
+        grid: Vec<MatrixRow>,
+        ranking: Vec<usize>, // best first
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        required: Vec<String>, // ranking only keeps items all of them can attend
+    },
+    Text {
+        items: Vec<TextTally>,
+    },
+    Rating {
+        items: Vec<RatingTally>,
+    },
+    Approval {
+        items: Vec<ChoiceTally>,
+        winners: Vec<usize>,
+    },
+}
+
+#[derive(Serialize)]
+pub struct ChoiceTally {
+    pub index: usize,
+    pub label: String,
+    pub count: usize,
+    pub percentage: f64,
+}
+
+#[derive(Serialize)]
+pub struct RankTally {
+    pub index: usize,
+    pub label: String,
+    pub ranked: usize,
+    pub points: usize,
+    pub average_position: Option<f64>, // 1 = first place
+}
+
+#[derive(Serialize)]
+pub struct MatrixTally {
+    pub index: usize,
+    pub label: String,
+    pub yes: usize,
+    pub maybe: usize,
+    pub no: usize,
+    pub score: usize,
+    pub heat: f64, // score relative to everybody answering yes, 0.0 to 1.0
+    pub unavailable: Vec<String>, // voters who answered no
+}
+
+#[derive(Serialize)]
+pub struct TextTally {
+    pub index: usize,
+    pub label: String,
+    pub count: usize,
+    pub answers: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct RatingTally {
+    pub index: usize,
+    pub label: String,
+    pub range: RatingRange,
+    pub count: usize,
+    pub average: Option<f64>,
+    pub median: Option<f64>,
+    pub distribution: Vec<usize>, // one bucket per value from range.min to range.max
+}
+
+#[derive(Serialize)]
+pub struct MatrixRow {
+    pub voter: String,
+    pub answers: Vec<Option<i32>>,
+}
+
+fn percentage(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
+
+// Every ballot that answered the given question, in the order the ballots were cast
+fn answers_to(votes: &[StoredVote], question: usize) -> Vec<(&StoredVote, &Answer)> {
+    votes
+        .iter()
+        .filter_map(|vote| vote.answers.get(&question).map(|answer| (vote, answer)))
+        .collect()
+}
+
+pub fn compute_results(survey: &Survey) -> SurveyResults {
+    let questions = survey
+        .content
+        .questions
+        .iter()
+        .enumerate()
+        .map(|(index, question)| {
+            let items = &question.items;
+            let votes = answers_to(&survey.votes, index);
+
+            let tally = match question.survey_type {
+                SurveyType::SingleChoice | SurveyType::MultipleChoice => tally_choice(items, &votes),
+                SurveyType::RankChoice => tally_rank(items, &votes, question.rank_method.unwrap_or_default()),
+                SurveyType::MatrixChoice | SurveyType::Schedule => tally_matrix(items, &votes),
+                SurveyType::FreeText => tally_text(items, &votes),
+                SurveyType::RatingScale => tally_rating(question, &votes),
+                SurveyType::Approval => tally_approval(items, &votes),
+            };
+
+            QuestionResults {
+                index,
+                answered: votes.len(),
+                tally,
+            }
+        })
+        .collect();
+
+    SurveyResults {
+        ballots: survey.votes.len(),
+        questions,
+    }
+}
+
+fn tally_choice(items: &[String], votes: &[(&StoredVote, &Answer)]) -> Tally {
+    let mut counts = vec![0usize; items.len()];
+
+    for (_, answer) in votes {
+        for &index in answer.vote.keys() {
+            if let Some(count) = counts.get_mut(index) {
+                *count += 1;
+            }
+        }
+    }
+
+    Tally::Choice {
+        items: items
+            .iter()
+            .enumerate()
+            .map(|(index, label)| ChoiceTally {
+                index,
+                label: label.clone(),
+                count: counts[index],
+                percentage: percentage(counts[index], votes.len()),
+            })
+            .collect(),
+    }
+}
+
+fn tally_approval(items: &[String], votes: &[(&StoredVote, &Answer)]) -> Tally {
+    let Tally::Choice { items } = tally_choice(items, votes) else {
+        unreachable!("tally_choice always returns a choice tally");
+    };
+
+    let best = items.iter().map(|t| t.count).max().unwrap_or(0);
+    let winners = if best == 0 {
+        Vec::new()
+    } else {
+        items.iter().filter(|t| t.count == best).map(|t| t.index).collect()
+    };
+
+    Tally::Approval { items, winners }
+}
+
+fn tally_text(items: &[String], votes: &[(&StoredVote, &Answer)]) -> Tally {
+    Tally::Text {
+        items: items
+            .iter()
+            .enumerate()
+            .map(|(index, label)| {
+                let answers: Vec<String> = votes
+                    .iter()
+                    .filter_map(|(_, answer)| answer.text.get(&index).cloned())
+                    .collect();
+
+                TextTally {
+                    index,
+                    label: label.clone(),
+                    count: answers.len(),
+                    answers,
+                }
+            })
+            .collect(),
+    }
+}
+
+fn tally_rating(content: &Question, votes: &[(&StoredVote, &Answer)]) -> Tally {
+    Tally::Rating {
+        items: content
+            .items
+            .iter()
+            .enumerate()
+            .map(|(index, label)| {
+                let range = rating_range_for(content, index);
+                let mut ratings: Vec<i32> = votes
+                    .iter()
+                    .filter_map(|(_, answer)| answer.vote.get(&index).copied())
+                    .filter(|&r| r >= range.min && r <= range.max)
+                    .collect();
+                ratings.sort_unstable();
+
+                let mut distribution = vec![0usize; (range.max - range.min + 1) as usize];
+                for &rating in &ratings {
+                    distribution[(rating - range.min) as usize] += 1;
+                }
+
+                let count = ratings.len();
+                let average = (count > 0)
+                    .then(|| ratings.iter().map(|&r| r as f64).sum::<f64>() / count as f64);
+                let median = (count > 0).then(|| {
+                    if count % 2 == 1 {
+                        ratings[count / 2] as f64
+                    } else {
+                        (ratings[count / 2 - 1] + ratings[count / 2]) as f64 / 2.0
+                    }
+                });
+
+                RatingTally {
+                    index,
+                    label: label.clone(),
+                    range,
+                    count,
+                    average,
+                    median,
+                    distribution,
+                }
+            })
+            .collect(),
+    }
+}
+
+fn rank_ballots(items: &[String], votes: &[(&StoredVote, &Answer)]) -> Vec<Ballot> {
+    votes
+        .iter()
+        .map(|(_, answer)| {
+            let mut entries: Vec<(i32, usize)> = answer
+                .vote
+                .iter()
+                .filter(|(&index, &rank)| index < items.len() && rank >= 0)
+                .map(|(&index, &rank)| (rank, index))
+                .collect();
+
+            entries.sort();
+            entries.into_iter().map(|(_, index)| index).collect()
+        })
+        .collect()
+}
+
+fn tally_rank(items: &[String], votes: &[(&StoredVote, &Answer)], method: RankMethod) -> Tally {
+    let n = items.len();
+    let ballots = rank_ballots(items, votes);
+    let points = ranking::borda_scores(n, &ballots);
+
+    let mut ranked = vec![0usize; n];
+    let mut position_sum = vec![0usize; n];
+
+    for ballot in &ballots {
+        for (position, &index) in ballot.iter().enumerate() {
+            ranked[index] += 1;
+            position_sum[index] += position + 1;
+        }
+    }
+
+    let outcome = ranking::count(method, n, &ballots);
+
+    Tally::Rank {
+        items: items
+            .iter()
+            .enumerate()
+            .map(|(index, label)| RankTally {
+                index,
+                label: label.clone(),
+                ranked: ranked[index],
+                points: points[index],
+                average_position: (ranked[index] > 0)
+                    .then(|| position_sum[index] as f64 / ranked[index] as f64),
+            })
+            .collect(),
+        ranking: outcome.ranking(n),
+        outcome,
+    }
+}
+
+fn tally_matrix(items: &[String], votes: &[(&StoredVote, &Answer)]) -> Tally {
+    let mut tallies: Vec<MatrixTally> = items
+        .iter()
+        .enumerate()
+        .map(|(index, label)| MatrixTally {
+            index,
+            label: label.clone(),
+            yes: 0,
+            maybe: 0,
+            no: 0,
+            score: 0,
+            heat: 0.0,
+            unavailable: Vec::new(),
+        })
+        .collect();
+
+    let grid = votes
+        .iter()
+        .map(|(vote, answer)| {
+            let answers = (0..items.len())
+                .map(|index| answer.vote.get(&index).copied())
+                .collect::<Vec<_>>();
+
+            let voter = vote.voter.clone().unwrap_or_default();
+
+            for (index, answer) in answers.iter().enumerate() {
+                match answer {
+                    Some(MATRIX_YES) => tallies[index].yes += 1,
+                    Some(MATRIX_MAYBE) => tallies[index].maybe += 1,
+                    Some(MATRIX_NO) => {
+                        tallies[index].no += 1;
+                        tallies[index].unavailable.push(voter.clone());
+                    }
+                    _ => {}
+                }
+            }
+
+            MatrixRow {
+                voter,
+                answers,
+            }
+        })
+        .collect();
+
+    // Yes counts double, maybe once
+    for tally in &mut tallies {
+        let answered = tally.yes + tally.maybe + tally.no;
+        tally.score = 2 * tally.yes + tally.maybe;
+        tally.heat = if answered == 0 { 0.0 } else { tally.score as f64 / (2 * answered) as f64 };
+    }
+
+    let ranking = matrix_ranking(&tallies);
+
+    Tally::Matrix {
+        items: tallies,
+        grid,
+        ranking,
+        required: Vec::new(),
+    }
+}
+
+// Ties go to more yes answers, then to the earlier item
+fn matrix_ranking(tallies: &[MatrixTally]) -> Vec<usize> {
+    let mut ranking: Vec<usize> = (0..tallies.len()).collect();
+
+    ranking.sort_by(|&a, &b| {
+        tallies[b].score.cmp(&tallies[a].score)
+            .then(tallies[b].yes.cmp(&tallies[a].yes))
+            .then(a.cmp(&b))
+    });
+
+    ranking
+}
+
+fn parse_required(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+// Narrows matrix rankings to the items every required voter answered with yes or maybe.
+// A required voter who skipped a question cannot attend any of its items
+fn require_participants(survey: &Survey, results: &mut SurveyResults, required: Vec<String>) -> Result<(), AskError> {
+    if !survey.content.questions.iter().any(|q| is_matrix(q.survey_type)) {
+        return Err(AskError::InvalidInput("Required participants only apply to matrix and scheduling questions".into()));
+    }
+
+    let same_name = |a: &str, b: &str| a.trim().to_lowercase() == b.trim().to_lowercase();
+
+    if let Some(unknown) = required.iter().find(|name| {
+        !survey.votes.iter().any(|v| v.voter.as_deref().is_some_and(|voter| same_name(voter, name)))
+    }) {
+        return Err(AskError::InvalidInput(format!("Unknown participant: {}", unknown)));
+    }
+
+    for question in &mut results.questions {
+        if let Tally::Matrix { grid, ranking, required: filter, .. } = &mut question.tally {
+            ranking.retain(|&index| {
+                required.iter().all(|name| {
+                    grid.iter().any(|row| {
+                        same_name(&row.voter, name)
+                            && matches!(row.answers[index], Some(MATRIX_YES) | Some(MATRIX_MAYBE))
+                    })
+                })
+            });
+
+            *filter = required.clone();
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * --- Handlers ---
+ */
+
+// Redirect /ask to /ask.html
+pub async fn ask_html() -> impl IntoResponse {
+    Redirect::to("/ask.html")
+}
+
+pub async fn serve_ask_html() -> impl IntoResponse {
+    let html = fs::read_to_string("static/ask.html")
+        .expect("ask.html not found");
+    Html(html)
+}
+
+// Create new survey 
+pub async fn create_survey(
+    State(state): State<AppState>,
+    Json(new_survey): Json<NewSurvey>,
+) -> Result<Json<CreateSurveyResponse>, AskError>{
+    let id = Uuid::new_v4().to_string();
+
+    // Sanitize and validate settings values 
+    let expiry = validate_expiry(new_survey.expiry)?;
+    
+    // Sanitize and validate title; templates bring their own
+    let title = match new_survey.template {
+        Some(template) if new_survey.title.trim().is_empty() => template.title().to_string(),
+        _ => new_survey.title,
+    };
+    let title = sanitize_title(&title)?;
+
+    // A template, a list of questions or a single question in the legacy flat layout
+    let new_questions = match (new_survey.template, new_survey.questions.is_empty(), new_survey.question) {
+        (Some(template), true, None) => vec![template.question()],
+        (Some(_), _, _) => {
+            return Err(AskError::InvalidInput("Send either a template or questions, not both".into()));
+        }
+        (None, false, Some(_)) => {
+            return Err(AskError::InvalidInput("Send either questions or a single survey_type, not both".into()));
+        }
+        (None, false, None) => new_survey.questions,
+        (None, true, Some(question)) => vec![question],
+        (None, true, None) => return Err(AskError::InvalidInput("At least one question is required".into())),
+    };
+
+    if new_questions.len() > MAX_QUESTIONS {
+        return Err(AskError::InvalidInput(format!("Too many questions (max {})", MAX_QUESTIONS)));
+    }
+
+    let multi = new_questions.len() > 1;
+    let mut questions = Vec::with_capacity(new_questions.len());
+
+    for (index, new_question) in new_questions.into_iter().enumerate() {
+        questions.push(build_question(new_question, multi).map_err(|e| question_error(multi, index, e))?);
+    }
+
+    // Create password hash if a password was set
+    let password_hash = hash_password(&new_survey.password)?;
+
+    // Create management token for the owner
+    let (manage_token, manage_token_hash) = owner::generate_token();
+
+    // Optional voting window inside the survey lifetime
+    validate_voting_window(new_survey.opens_at, new_survey.closes_at)?;
+
+    // Build content
+    let survey_content = SurveyContent {
+        title,
+        questions,
+        opens_at: new_survey.opens_at,
+        closes_at: new_survey.closes_at,
+        closed_at: None,
+        results_visibility: new_survey.results_visibility,
+        one_vote_per_participant: new_survey.one_vote_per_participant,
+    };
+    
+    // Create proper survey
+    let survey = Survey {
+        id: id.clone(),
+        content: survey_content,
+        password_hash,
+        expiry,
+        created_at: SystemTime::now(),
+        votes: Vec::new(),
+        manage_token_hash: Some(manage_token_hash),
+        version: 0,
+    };
+
+    state.ask.create_survey(survey).await?;
+
+    // Return the survey id and management token to the frontend
+    Ok(Json(CreateSurveyResponse {
+        status: SurveyStatus::Success,
+        id,
+        manage_token,
+        message: "Survey created successfully.".into(),
+    }))
+}
+
+// Start a new survey from the content of an existing one, without its votes
+pub async fn clone_survey(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+    headers: HeaderMap,
+    body: Result<Json<CloneRequest>, JsonRejection>,
+) -> Result<Response, AskError> {
+    let clone = optional_json(&headers, body)?;
+    let survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check 
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&survey, &auth) {
+        Ok(()) => {},
+        Err(PasswordError::Missing) => {
+            return Ok(Json(protected_response("This survey is password protected.")).into_response());
+        }
+        Err(PasswordError::Incorrect) => {
+            return Ok(Json(protected_response("Incorrect password.")).into_response());
+        }
+    }
+
+    // Sanitize and validate settings values 
+    let expiry = validate_expiry(clone.expiry.unwrap_or(survey.expiry))?;
+
+    let password_hash = match clone.password {
+        Some(password) => hash_password(&password)?,
+        None => survey.password_hash,
+    };
+
+    // Copy content; everything tied to the old round of voting starts over
+    let mut content = survey.content;
+
+    if let Some(title) = clone.title {
+        content.title = sanitize_title(&title)?;
+    }
+
+    content.opens_at = None;
+    content.closes_at = None;
+    content.closed_at = None;
+
+    for question in &mut content.questions {
+        question.finalized = None;
+
+        if let Some(write_ins) = &mut question.write_ins {
+            write_ins.added = 0;
+        }
+    }
+
+    // Create management token for the owner of the copy
+    let (manage_token, manage_token_hash) = owner::generate_token();
+
+    let clone_id = Uuid::new_v4().to_string();
+    let survey = Survey {
+        id: clone_id.clone(),
+        content,
+        password_hash,
+        expiry,
+        created_at: SystemTime::now(),
+        votes: Vec::new(),
+        manage_token_hash: Some(manage_token_hash),
+        version: 0,
+    };
+
+    state.ask.create_survey(survey).await?;
+
+    Ok(Json(CreateSurveyResponse {
+        status: SurveyStatus::Success,
+        id: clone_id,
+        manage_token,
+        message: "Survey cloned successfully.".into(),
+    }).into_response())
+}
+
+// Retrieve existing survey
+pub async fn get_survey(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Json<SurveyResponse>, AskError> {
+    let survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check 
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&survey, &auth) {
+        Ok(()) => {
+            Ok(Json(success_response(&survey, results_visible(&state, &survey, &headers, false))))
+        }
+        Err(PasswordError::Missing) => {
+            Ok(Json(protected_response("This survey is password protected.")))
+        }
+        Err(PasswordError::Incorrect) => {
+            Ok(Json(protected_response("Incorrect password.")))
+        }
+    }
+}
+
+// Retrieve server-side tallies, optionally without the raw ballots
+pub async fn get_survey_results(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<ResultsQuery>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Json<SurveyResponse>, AskError> {
+    let survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check 
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&survey, &auth) {
+        Ok(()) => {
+            // Visibility check
+            if !results_visible(&state, &survey, &headers, false) {
+                return Err(AskError::ResultsHidden);
+            }
+
+            let mut response = results_response(&survey, query.ballots);
+
+            // Optional filter to the slots everyone required can make
+            let required = query.required.as_deref().map(parse_required).unwrap_or_default();
+            if !required.is_empty() {
+                if let Some(results) = response.data.as_mut().and_then(|data| data.results.as_mut()) {
+                    require_participants(&survey, results, required)?;
+                }
+            }
+
+            Ok(Json(response))
+        }
+        Err(PasswordError::Missing) => {
+            Ok(Json(protected_response("This survey is password protected.")))
+        }
+        Err(PasswordError::Incorrect) => {
+            Ok(Json(protected_response("Incorrect password.")))
+        }
+    }
+}
+
+// Push updated tallies as server-sent events whenever the ballots change
+pub async fn stream_survey(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, AskError> {
+    // Subscribe first so no vote slips in between loading and listening
+    let updates = state.survey_updates.subscribe();
+    let survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check 
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&survey, &auth) {
+        Ok(()) => {},
+        Err(PasswordError::Missing) => {
+            return Ok(Json(protected_response("This survey is password protected.")).into_response());
+        }
+        Err(PasswordError::Incorrect) => {
+            return Ok(Json(protected_response("Incorrect password.")).into_response());
+        }
+    }
+
+    // Visibility check
+    if !results_visible(&state, &survey, &headers, false) {
+        return Err(AskError::ResultsHidden);
+    }
+
+    // Stream slot check
+    let ip = normalize_ip(&extract_real_ip(&headers, addr));
+    let slot = StreamSlot::acquire(&state.stream_slots, &ip).ok_or(AskError::TooManyStreams)?;
+
+    let live = LiveResults {
+        state: state.clone(),
+        id,
+        headers,
+        updates,
+        deadline: Instant::now() + STREAM_MAX_DURATION,
+        initial: Some(survey),
+        finished: false,
+        _slot: slot,
+    };
+
+    let stream = futures::stream::unfold(live, |mut live| async move {
+        let event = live.next_event().await?;
+        Some((Ok::<_, Infallible>(event), live))
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()).into_response())
+}
+
+// Download ballots and tallies for spreadsheets
+pub async fn export_survey(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<ExportQuery>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, AskError> {
+    let survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check 
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&survey, &auth) {
+        Ok(()) => {},
+        Err(PasswordError::Missing) => {
+            return Ok(Json(protected_response("This survey is password protected.")).into_response());
+        }
+        Err(PasswordError::Incorrect) => {
+            return Ok(Json(protected_response("Incorrect password.")).into_response());
+        }
+    }
+
+    // Visibility check
+    if !results_visible(&state, &survey, &headers, false) {
+        return Err(AskError::ResultsHidden);
+    }
+
+    let results = compute_results(&survey);
+    let ballots = export::ballot_sheet(&survey);
+
+    let body = match query.format {
+        ExportFormat::Json => export::to_json(&survey, &ballots, &results).to_string().into_bytes(),
+        ExportFormat::Csv => {
+            let summary = export::summary_sheet(&survey, &results);
+            export::to_csv(&[ballots, summary]).map_err(AskError::Internal)?
+        }
+        ExportFormat::Ods => {
+            let summary = export::summary_sheet(&survey, &results);
+            export::to_ods(&[ballots, summary]).map_err(AskError::Internal)?
+        }
+    };
+
+    let disposition = format!("attachment; filename=\"survey-{}.{}\"", survey.id, query.format.extension());
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ).into_response())
+}
+
+pub async fn vote_survey(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+    Json(vote_req): Json<VoteRequest>,
+) -> Result<Response, AskError> {
+    let survey = state.ask.get_survey(&id).await?;
     
     // Expiry check
     if is_expired(&survey) {
         return Err(AskError::Expired);
     }
 
+    // Password check
+    match verify_password(&survey, &auth) {
+        Ok(()) => {},
+        Err(PasswordError::Missing) => return Ok(Json(protected_response("This survey is password protected.")).into_response()),
+        Err(PasswordError::Incorrect) => return Ok(Json(protected_response("Incorrect password.")).into_response()),
+    }
+
+    // Voting window and finalization check
+    check_voting_open(&survey)?;
+
+    // One ballot per participant, recognized by the voter cookie (best-effort, see receipt::generate_voter_token);
+    // further changes go through the receipt.
+    // The store enforces this too, for requests racing each other.
+    let voter_token = receipt::voter_token(&headers);
+    let new_voter_token = voter_token.is_none().then(receipt::generate_voter_token);
+
+    let participant = if survey.content.one_vote_per_participant {
+        let token = voter_token.as_deref().or(new_voter_token.as_deref()).unwrap_or_default();
+        let participant = receipt::participant(&state.receipt_secret, &id, token);
+
+        if survey.votes.iter().any(|v| v.participant.as_deref() == Some(participant.as_str())) {
+            return Err(AskError::AlreadyVoted);
+        }
+
+        Some(participant)
+    } else {
+        None
+    };
+
+    // Write-ins are checked as items on a preview, the store adds them together with the vote
+    let (preview, vote_req, write_ins) = preview_write_ins(&survey, vote_req)?;
+
+    // Validation: type-aware ballot checks per question and voter name
+    let mut stored_vote = build_vote(&preview, vote_req, None)?;
+    let ballot_id = Uuid::new_v4().to_string();
+    stored_vote.ballot_id = Some(ballot_id.clone());
+    stored_vote.participant = participant;
+
+    let survey = state.ask.add_vote(&id, stored_vote, write_ins).await?;
+    publish_update(&state, &id);
+
+    // Respond
+    let mut response = success_response(&survey, results_visible(&state, &survey, &headers, true));
+    if survey.content.one_vote_per_participant {
+        attach_receipt(&mut response, receipt::issue(&state.receipt_secret, &id, &ballot_id));
+    }
+
+    let mut response = Json(response).into_response();
+    if let Some(token) = new_voter_token {
+        if let Ok(cookie) = header::HeaderValue::from_str(&receipt::voter_cookie(&token)) {
+            response.headers_mut().insert(header::SET_COOKIE, cookie);
+        }
+    }
+
+    Ok(response)
+}
+
+// Replace the ballot a vote receipt belongs to
+pub async fn change_vote(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+    Json(vote_req): Json<VoteRequest>,
+) -> Result<Json<SurveyResponse>, AskError> {
+    let survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
+    }
+
     // Password check
     match verify_password(&survey, &auth) {
         Ok(()) => {},
@@ -370,68 +2399,670 @@ pub async fn vote_survey(
         Err(PasswordError::Incorrect) => return Ok(Json(protected_response("Incorrect password."))),
     }
 
-    // Validation: make sure vote indices exist
-    let num_items = survey.content.items.len();
-    for &index in vote_req.vote.keys() {
-        if index >= num_items {
-            return Err(AskError::InvalidInput(format!("Invalid vote index: {}", index)));
-        }
+    // Receipt check
+    let existing = receipt_ballot(&state, &survey, &headers)?.clone();
+
+    // Voting window and finalization check
+    check_voting_open(&survey)?;
+
+    // Write-ins are checked as items on a preview, the store adds them together with the vote
+    let (preview, vote_req, write_ins) = preview_write_ins(&survey, vote_req)?;
+
+    // Validation: the replaced ballot keeps its id and participant
+    let mut stored_vote = build_vote(&preview, vote_req, existing.ballot_id.as_deref())?;
+    stored_vote.ballot_id = existing.ballot_id.clone();
+    stored_vote.participant = existing.participant.clone();
+
+    let survey = state.ask.update_vote(&id, stored_vote, write_ins).await?;
+    publish_update(&state, &id);
+
+    // Respond with the unchanged receipt
+    let mut response = success_response(&survey, results_visible(&state, &survey, &headers, true));
+    if let Some(ballot_id) = &existing.ballot_id {
+        attach_receipt(&mut response, receipt::issue(&state.receipt_secret, &id, ballot_id));
     }
 
-    // Add vote to survey only if it's not empty
-    if vote_req.vote.is_empty() {
-        return Err(AskError::InvalidInput("Cannot submit empty vote.".into()));
+    Ok(Json(response))
+}
+
+// Remove the ballot a vote receipt belongs to
+pub async fn withdraw_vote(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Json<SurveyResponse>, AskError> {
+    let survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
     }
 
-    // Matrix-only voter name validation
-    match survey.content.survey_type {
-        SurveyType::MatrixChoice => {
-            let name = vote_req.voter.as_ref().ok_or_else(|| {
-                AskError::InvalidInput(
-                    "Voter name is required for matrix surveys.".into()
-                )
-            })?;
+    // Password check
+    match verify_password(&survey, &auth) {
+        Ok(()) => {},
+        Err(PasswordError::Missing) => return Ok(Json(protected_response("This survey is password protected."))),
+        Err(PasswordError::Incorrect) => return Ok(Json(protected_response("Incorrect password."))),
+    }
 
-            if name.trim().is_empty() {
-                return Err(AskError::InvalidInput(
-                    "Voter name cannot be empty.".into()
-                ));
-            }
+    // Receipt check
+    let existing = receipt_ballot(&state, &survey, &headers)?;
 
-            if name.len() > MAX_VOTER_LEN {
-                return Err(AskError::InvalidInput
-                    ("Voter name too long.".into()
-                ));
-            }
+    // Voting window and finalization check
+    check_voting_open(&survey)?;
 
-            if survey.votes.iter().any(|v| {
-                v.voter
-                    .as_ref()
-                    .map(|existing| existing.trim().to_lowercase() == name.to_lowercase().trim())
-                    .unwrap_or(false)
-            }) {
-                return Err(AskError::InvalidInput(
-                    "Voter name has already been used.".into(),
-                ));
-            }
+    let ballot_id = existing.ballot_id.clone().unwrap_or_default();
+    let survey = state.ask.delete_vote(&id, &ballot_id).await?;
+    publish_update(&state, &id);
+
+    // Respond
+    Ok(Json(success_response(&survey, results_visible(&state, &survey, &headers, false))))
+}
+
+// Update existing survey (owner only)
+pub async fn update_survey(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(update): Json<UpdateSurvey>,
+) -> Result<Json<ManageSurveyResponse>, AskError> {
+    let mut survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
+    }
+
+    // Ownership check
+    if !owner::verify_token(&survey.manage_token_hash, &headers) {
+        return Err(AskError::Forbidden);
+    }
+
+    // Apply changes
+    if let Some(title) = update.title {
+        survey.content.title = sanitize_title(&title)?;
+    }
+
+    if let Some(items) = update.items {
+        let index = update.question.unwrap_or(0);
+        let question = survey
+            .content
+            .questions
+            .get_mut(index)
+            .ok_or_else(|| AskError::InvalidInput(format!("Invalid question index: {}", index)))?;
+
+        if question.survey_type == SurveyType::Schedule {
+            return Err(AskError::InvalidInput("Slots of scheduling polls cannot be changed".into()));
         }
-        _ => {
-            if vote_req.voter.is_some() {
-                return Err(AskError::InvalidInput(
-                    "Voter name is only allowed for matrix surveys.".into(),
-                ));
+
+        // Votes reference items by index, so items are frozen once voting started
+        if !survey.votes.is_empty() {
+            return Err(AskError::InvalidInput("Items cannot be changed after votes were cast".into()));
+        }
+
+        let items = sanitize_items(items)?;
+        validate_choice_limits(
+            question.survey_type,
+            question.min_choices,
+            question.max_choices,
+            items.len(),
+        )?;
+
+        // A uniform rating range follows the new item count; per-item ranges cannot be remapped
+        if let Some(ranges) = &question.rating_ranges {
+            if ranges.len() != items.len() {
+                match ranges.first() {
+                    Some(&first) if ranges.iter().all(|&r| r == first) => {
+                        question.rating_ranges = Some(vec![first; items.len()]);
+                    }
+                    _ => {
+                        return Err(AskError::InvalidInput("Items with individual rating ranges cannot be resized".into()));
+                    }
+                }
             }
         }
+
+        question.items = items;
     }
 
-    // Add vote to survey
-    let stored_vote = StoredVote {
-        vote: vote_req.vote,
-        voter: vote_req.voter,
+    if let Some(visibility) = update.results_visibility {
+        survey.content.results_visibility = visibility;
+    }
+
+    if let Some(expiry) = update.expiry {
+        survey.expiry = validate_expiry(expiry)?;
+    }
+
+    if let Some(password) = update.password {
+        survey.password_hash = hash_password(&password)?; // empty password removes protection
+    }
+
+    state.ask.update_survey(survey).await?;
+    publish_update(&state, &id);
+
+    Ok(Json(ManageSurveyResponse {
+        status: SurveyStatus::Success,
+        id,
+        message: "Survey updated successfully.".into(),
+    }))
+}
+
+// Delete existing survey and its votes (owner only)
+pub async fn delete_survey(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ManageSurveyResponse>, AskError> {
+    let survey = state.ask.get_survey(&id).await?;
+
+    // Ownership check
+    if !owner::verify_token(&survey.manage_token_hash, &headers) {
+        return Err(AskError::Forbidden);
+    }
+
+    state.ask.delete_survey(&id).await?;
+    publish_update(&state, &id);
+
+    Ok(Json(ManageSurveyResponse {
+        status: SurveyStatus::Success,
+        id,
+        message: "Survey deleted successfully.".into(),
+    }))
+}
+
+// End voting early; the survey stays readable until it expires (owner only)
+pub async fn close_survey(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ManageSurveyResponse>, AskError> {
+    let mut survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
+    }
+
+    // Ownership check
+    if !owner::verify_token(&survey.manage_token_hash, &headers) {
+        return Err(AskError::Forbidden);
+    }
+
+    if voting_state(&survey.content) == VotingState::Closed {
+        return Err(AskError::VotingClosed);
+    }
+
+    survey.content.closed_at = Some(Utc::now());
+    state.ask.update_survey(survey).await?;
+    publish_update(&state, &id);
+
+    Ok(Json(ManageSurveyResponse {
+        status: SurveyStatus::Success,
+        id,
+        message: "Voting closed successfully.".into(),
+    }))
+}
+
+// Open voting again right away, optionally until a new closing time (owner only)
+pub async fn reopen_survey(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Result<Json<ReopenRequest>, JsonRejection>,
+) -> Result<Json<ManageSurveyResponse>, AskError> {
+    let reopen = optional_json(&headers, body)?;
+    let mut survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
+    }
+
+    // Ownership check
+    if !owner::verify_token(&survey.manage_token_hash, &headers) {
+        return Err(AskError::Forbidden);
+    }
+
+    if voting_state(&survey.content) == VotingState::Open {
+        return Err(AskError::InvalidInput("Voting is already open".into()));
+    }
+
+    validate_voting_window(None, reopen.closes_at)?;
+
+    survey.content.opens_at = None;
+    survey.content.closes_at = reopen.closes_at;
+    survey.content.closed_at = None;
+    state.ask.update_survey(survey).await?;
+    publish_update(&state, &id);
+
+    Ok(Json(ManageSurveyResponse {
+        status: SurveyStatus::Success,
+        id,
+        message: "Voting reopened successfully.".into(),
+    }))
+}
+
+// Pick the winning slot of a scheduling poll and turn it into a Cal event (owner only)
+pub async fn finalize_survey(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(finalize): Json<FinalizeRequest>,
+) -> Result<Json<FinalizeResponse>, AskError> {
+    let mut survey = state.ask.get_survey(&id).await?;
+
+    // Expiry check
+    if is_expired(&survey) {
+        return Err(AskError::Expired);
+    }
+
+    // Ownership check
+    if !owner::verify_token(&survey.manage_token_hash, &headers) {
+        return Err(AskError::Forbidden);
+    }
+
+    // Defaults to the first scheduling question
+    let question = match finalize.question {
+        Some(index) if index < survey.content.questions.len() => index,
+        Some(index) => return Err(AskError::InvalidInput(format!("Invalid question index: {}", index))),
+        None => survey
+            .content
+            .questions
+            .iter()
+            .position(|q| q.survey_type == SurveyType::Schedule)
+            .unwrap_or(0),
     };
 
-    let survey = state.ask.add_vote(&id, stored_vote).await?;
+    if survey.content.questions[question].survey_type != SurveyType::Schedule {
+        return Err(AskError::InvalidInput("Only scheduling polls can be finalized".into()));
+    }
 
-    // Respond
-    Ok(Json(success_response(&survey)))
+    if survey.content.questions.iter().any(|q| q.finalized.is_some()) {
+        return Err(AskError::InvalidInput("This scheduling poll has already been finalized".into()));
+    }
+
+    let slots = survey.content.questions[question].slots.clone().unwrap_or_default();
+
+    // Owner may override the automatic pick
+    let slot = match finalize.slot {
+        Some(slot) if slot < slots.len() => slot,
+        Some(slot) => return Err(AskError::InvalidInput(format!("Invalid slot index: {}", slot))),
+        None => best_slot(&survey, question)
+            .ok_or_else(|| AskError::InvalidInput("No slot has any yes or maybe answers yet".into()))?,
+    };
+
+    // Build event content from the winning slot; blank location and description are left out
+    let location = finalize.location.as_deref().map(cal::sanitize_location).transpose()?;
+    let description = finalize.description.as_deref().map(cal::sanitize_description).transpose()?;
+
+    let event_content = EventContent {
+        title: survey.content.title.clone(),
+        location: location.filter(|location| !location.is_empty()),
+        start: slots[slot].start,
+        end: slots[slot].end,
+        timezone: survey.content.questions[question].timezone.clone().unwrap_or_default(),
+        description: description.filter(|description| !description.is_empty()),
+    };
+
+    // The event inherits the poll's password; uses default to the maximum so every participant can open it
+    let (event, event_manage_token) = cal::build_event(
+        event_content,
+        survey.password_hash.clone(),
+        finalize.expiry.unwrap_or(survey.expiry),
+        finalize.uses.unwrap_or(9999),
+    )?;
+
+    let event_id = event.id.clone();
+    let ics = event.content.to_ics_string(&event.id, event.created_utc);
+
+    // Claim the poll first; of two finalizes racing each other only one gets past the version check
+    survey.content.questions[question].finalized = Some(FinalizedSlot {
+        slot,
+        event_id: event_id.clone(),
+    });
+
+    state.ask.update_survey(survey.clone()).await?;
+
+    // Without the event the claim is undone; the update above bumped the version by one
+    if let Err(e) = state.cal.create_event(event).await {
+        survey.content.questions[question].finalized = None;
+        survey.version += 1;
+        state.ask.update_survey(survey).await.ok();
+        return Err(e.into());
+    }
+
+    publish_update(&state, &id);
+
+    Ok(Json(FinalizeResponse {
+        status: SurveyStatus::Success,
+        id,
+        question,
+        slot,
+        event_id,
+        event_manage_token,
+        ics,
+        message: "Scheduling poll finalized successfully.".into(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Method;
+
+    use crate::owner::MANAGE_TOKEN_HEADER;
+    use crate::testing::{TestApp, TestResponse};
+
+    fn question(new_question: serde_json::Value) -> Question {
+        let new_question: NewQuestion = serde_json::from_value(new_question).expect("valid question");
+        build_question(new_question, false).expect("question builds")
+    }
+
+    fn votes(pairs: &[(usize, i32)]) -> HashMap<usize, i32> {
+        pairs.iter().copied().collect()
+    }
+
+    fn texts(pairs: &[(usize, &str)]) -> HashMap<usize, String> {
+        pairs.iter().map(|&(index, text)| (index, text.to_string())).collect()
+    }
+
+    fn accepts(question: &Question, vote: &[(usize, i32)]) -> bool {
+        validate_ballot(question, &votes(vote), &HashMap::new()).is_ok()
+    }
+
+    fn rejection(result: Result<(), AskError>) -> String {
+        match result {
+            Err(AskError::InvalidInput(message)) => message,
+            Err(e) => panic!("unexpected error: {:?}", e),
+            Ok(()) => panic!("ballot was accepted"),
+        }
+    }
+
+    fn reject(question: &Question, vote: &[(usize, i32)]) -> String {
+        rejection(validate_ballot(question, &votes(vote), &HashMap::new()))
+    }
+
+    #[test]
+    fn single_choice_takes_exactly_one_selection() {
+        let q = question(json!({ "survey_type": "singlechoice", "items": ["a", "b", "c"] }));
+
+        assert!(accepts(&q, &[(1, CHOICE_SELECTED)]));
+        assert_eq!(reject(&q, &[(0, CHOICE_SELECTED), (1, CHOICE_SELECTED)]), "Single choice surveys require exactly one selection.");
+        assert_eq!(reject(&q, &[(0, 1)]), "Invalid selection value.");
+        assert_eq!(reject(&q, &[(3, CHOICE_SELECTED)]), "Invalid vote index: 3");
+        assert_eq!(reject(&q, &[]), "Cannot submit empty vote.");
+    }
+
+    #[test]
+    fn multiple_choice_respects_selection_limits() {
+        let q = question(json!({ "survey_type": "multiplechoice", "items": ["a", "b", "c", "d"], "min_choices": 2, "max_choices": 3 }));
+
+        assert!(accepts(&q, &[(0, CHOICE_SELECTED), (2, CHOICE_SELECTED)]));
+        assert_eq!(reject(&q, &[(0, CHOICE_SELECTED)]), "Select at least 2 items.");
+        assert_eq!(reject(&q, &[(0, 0), (1, 0), (2, 0), (3, 0)]), "Select at most 3 items.");
+        assert_eq!(reject(&q, &[(0, CHOICE_SELECTED), (1, 2)]), "Invalid selection value.");
+    }
+
+    #[test]
+    fn rank_choice_needs_every_item_ranked_once() {
+        let q = question(json!({ "survey_type": "rankchoice", "items": ["a", "b", "c"] }));
+
+        assert!(accepts(&q, &[(2, 0), (0, 1), (1, 2)]));
+        assert_eq!(reject(&q, &[(0, 0), (1, 0), (2, 1)]), "Each rank can only be used once.");
+        assert_eq!(reject(&q, &[(2, 0), (0, 1)]), "Rank all 3 items, not 2.");
+        assert_eq!(reject(&q, &[(0, 0), (1, 1), (2, 3)]), "Ranks must be positions 1 to 3.");
+        assert_eq!(reject(&q, &[(0, -1)]), "Ranks cannot be negative.");
+    }
+
+    #[test]
+    fn matrix_and_schedule_take_yes_maybe_or_no() {
+        let matrix = question(json!({ "survey_type": "matrixchoice", "items": ["mon", "tue"] }));
+        let schedule = question(json!({
+            "survey_type": "schedule",
+            "slots": [{ "from": "2026-11-02", "to": "2026-11-02", "start": "10:00", "end": "11:00" }],
+            "timezone": "UTC",
+        }));
+
+        assert!(accepts(&matrix, &[(0, MATRIX_YES), (1, MATRIX_NO)]));
+        assert_eq!(reject(&matrix, &[(0, 3)]), "Matrix answers must be yes, maybe or no.");
+        assert!(accepts(&schedule, &[(0, MATRIX_MAYBE)]));
+        assert_eq!(reject(&schedule, &[(0, -1)]), "Matrix answers must be yes, maybe or no.");
+        assert_eq!(reject(&schedule, &[(1, MATRIX_YES)]), "Invalid vote index: 1");
+    }
+
+    #[test]
+    fn rating_stays_within_each_items_range() {
+        let q = question(json!({
+            "survey_type": "ratingscale",
+            "items": ["food", "music"],
+            "rating_ranges": [{ "min": 1, "max": 5 }, { "min": -2, "max": 2 }],
+        }));
+
+        assert!(accepts(&q, &[(0, 5), (1, -2)]));
+        assert_eq!(reject(&q, &[(0, 0)]), "Rating for item 1 must be between 1 and 5.");
+        assert_eq!(reject(&q, &[(1, 3)]), "Rating for item 2 must be between -2 and 2.");
+    }
+
+    #[test]
+    fn approval_only_marks_items() {
+        let q = question(json!({ "survey_type": "approval", "items": ["a", "b", "c"] }));
+
+        assert!(accepts(&q, &[(0, CHOICE_SELECTED), (1, CHOICE_SELECTED), (2, CHOICE_SELECTED)]));
+        assert_eq!(reject(&q, &[(0, 1)]), "Invalid approval value.");
+    }
+
+    #[test]
+    fn free_text_takes_text_within_limits() {
+        let q = question(json!({ "survey_type": "freetext", "items": ["why"], "min_text_len": 2, "max_text_len": 5 }));
+        let check = |vote: &[(usize, i32)], text: &[(usize, &str)]| validate_ballot(&q, &votes(vote), &texts(text));
+
+        assert!(check(&[], &[(0, "fine")]).is_ok());
+        assert_eq!(rejection(check(&[(0, 0)], &[(0, "fine")])), "Free text surveys only accept text answers.");
+        assert_eq!(rejection(check(&[], &[(0, "   ")])), "Cannot submit empty answers.");
+        assert_eq!(rejection(check(&[], &[(0, "x")])), "Answer 1 is too short (min 2 chars).");
+        assert_eq!(rejection(check(&[], &[(0, "too long")])), "Answer 1 is too long (max 5 chars).");
+    }
+
+    #[test]
+    fn text_is_refused_outside_free_text() {
+        let q = question(json!({ "survey_type": "singlechoice", "items": ["a", "b"] }));
+        let result = validate_ballot(&q, &votes(&[(0, CHOICE_SELECTED)]), &texts(&[(0, "note")]));
+
+        assert_eq!(rejection(result), "Text answers are only allowed for free text surveys.");
+    }
+
+    fn matrix_survey(items: &[&str], ballots: &[(&str, &[i32])]) -> Survey {
+        let q = question(json!({ "survey_type": "matrixchoice", "items": items }));
+
+        Survey {
+            id: "matrix".into(),
+            content: SurveyContent {
+                title: "Team dinner".into(),
+                questions: vec![q],
+                opens_at: None,
+                closes_at: None,
+                closed_at: None,
+                results_visibility: ResultsVisibility::Always,
+                one_vote_per_participant: false,
+            },
+            password_hash: None,
+            expiry: 1,
+            created_at: SystemTime::now(),
+            votes: ballots
+                .iter()
+                .map(|&(voter, answers)| StoredVote {
+                    answers: BTreeMap::from([(0, Answer {
+                        vote: answers.iter().copied().enumerate().collect(),
+                        text: HashMap::new(),
+                    })]),
+                    voter: Some(voter.into()),
+                    ballot_id: None,
+                    participant: None,
+                })
+                .collect(),
+            manage_token_hash: None,
+            version: 0,
+        }
+    }
+
+    fn matrix_ranking_of(results: &SurveyResults) -> &[usize] {
+        match &results.questions[0].tally {
+            Tally::Matrix { ranking, .. } => ranking,
+            _ => panic!("not a matrix tally"),
+        }
+    }
+
+    #[test]
+    fn matrix_scores_yes_double_and_maybe_once() {
+        let survey = matrix_survey(&["mon", "tue", "wed"], &[
+            ("Ann", &[MATRIX_YES, MATRIX_MAYBE, MATRIX_NO]),
+            ("Bob", &[MATRIX_MAYBE, MATRIX_YES, MATRIX_NO]),
+            ("Cem", &[MATRIX_NO, MATRIX_YES, MATRIX_YES]),
+        ]);
+        let results = compute_results(&survey);
+
+        let Tally::Matrix { items, .. } = &results.questions[0].tally else { panic!("not a matrix tally") };
+        assert_eq!(items.iter().map(|t| t.score).collect::<Vec<_>>(), vec![3, 5, 2]);
+        assert_eq!(items[1].heat, 5.0 / 6.0);
+        assert_eq!(items[2].unavailable, vec!["Ann".to_string(), "Bob".to_string()]);
+        assert_eq!(matrix_ranking_of(&results), &[1, 0, 2]);
+    }
+
+    #[test]
+    fn matrix_ties_go_to_more_yes_answers() {
+        let survey = matrix_survey(&["mon", "tue"], &[
+            ("Ann", &[MATRIX_MAYBE, MATRIX_YES]),
+            ("Bob", &[MATRIX_MAYBE, MATRIX_NO]),
+        ]);
+
+        assert_eq!(matrix_ranking_of(&compute_results(&survey)), &[1, 0]);
+    }
+
+    #[test]
+    fn required_participants_narrow_the_ranking() {
+        let survey = matrix_survey(&["mon", "tue", "wed"], &[
+            ("Ann", &[MATRIX_YES, MATRIX_YES, MATRIX_MAYBE]),
+            ("Bob", &[MATRIX_YES, MATRIX_YES, MATRIX_YES]),
+            ("Cem", &[MATRIX_NO, MATRIX_MAYBE, MATRIX_YES]),
+        ]);
+        let mut results = compute_results(&survey);
+        assert_eq!(matrix_ranking_of(&results), &[1, 2, 0]);
+
+        require_participants(&survey, &mut results, parse_required(" cem , ,ANN")).expect("known participants");
+        assert_eq!(matrix_ranking_of(&results), &[1, 2]);
+
+        let Tally::Matrix { required, .. } = &results.questions[0].tally else { panic!("not a matrix tally") };
+        assert_eq!(required, &["cem".to_string(), "ANN".to_string()]);
+    }
+
+    #[test]
+    fn required_participants_must_have_voted() {
+        let survey = matrix_survey(&["mon"], &[("Ann", &[MATRIX_YES])]);
+        let mut results = compute_results(&survey);
+        let result = require_participants(&survey, &mut results, vec!["Dora".into()]);
+
+        assert_eq!(rejection(result), "Unknown participant: Dora");
+    }
+
+    #[tokio::test]
+    async fn manage_token_is_required_to_update_and_delete() {
+        let app = TestApp::new().await;
+        let (id, token) = app.create("/api/ask", json!({
+            "title": "Lunch",
+            "survey_type": "singlechoice",
+            "items": ["pizza", "sushi"],
+            "expiry": 1,
+        })).await;
+        let uri = format!("/api/ask/{}", id);
+
+        let response = app.send_json(Method::PATCH, &uri, &[(MANAGE_TOKEN_HEADER, "wrong")], json!({ "title": "Dinner" })).await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+        let response = app.send_json(Method::PATCH, &uri, &[(MANAGE_TOKEN_HEADER, &token)], json!({ "title": "Dinner" })).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        assert_eq!(app.get(&uri, &[]).await.json()["data"]["content"]["title"], "Dinner");
+
+        let response = app.request(Method::DELETE, &uri, &[(MANAGE_TOKEN_HEADER, &token)], Body::empty()).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(app.get(&uri, &[]).await.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn after_vote_results_follow_the_voter() {
+        let app = TestApp::new().await;
+        let (id, _) = app.create("/api/ask", json!({
+            "title": "Lunch",
+            "survey_type": "singlechoice",
+            "items": ["pizza", "sushi"],
+            "expiry": 1,
+            "results_visibility": "aftervote",
+            "one_vote_per_participant": true,
+        })).await;
+        let uri = format!("/api/ask/{}", id);
+        let visible = |response: TestResponse| response.json()["data"]["results_visible"].clone();
+
+        assert_eq!(visible(app.get(&uri, &[]).await), json!(false));
+
+        let response = app.post_json(&format!("{}/vote", uri), json!({ "vote": { "0": CHOICE_SELECTED } })).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        assert_eq!(response.json()["data"]["results_visible"], true);
+
+        let receipt = response.json()["data"]["receipt"].as_str().unwrap().to_string();
+        let cookie = response.header("set-cookie").unwrap().split(';').next().unwrap().to_string();
+
+        assert_eq!(visible(app.get(&uri, &[("Cookie", &cookie)]).await), json!(true));
+        assert_eq!(visible(app.get(&uri, &[(receipt::RECEIPT_HEADER, &receipt)]).await), json!(true));
+        assert_eq!(visible(app.get(&uri, &[("Cookie", &format!("{}={}", receipt::VOTER_COOKIE, receipt::generate_voter_token()))]).await), json!(false));
+        assert_eq!(visible(app.get(&uri, &[(receipt::RECEIPT_HEADER, "forged.receipt")]).await), json!(false));
+    }
+
+    #[tokio::test]
+    async fn clone_bodies_are_optional_but_must_be_json() {
+        let app = TestApp::new().await;
+        let (id, _) = app.create("/api/ask", json!({
+            "title": "Lunch",
+            "survey_type": "singlechoice",
+            "items": ["pizza", "sushi"],
+            "expiry": 1,
+        })).await;
+        let uri = format!("/api/ask/{}/clone", id);
+
+        let response = app.request(Method::POST, &uri, &[], Body::empty()).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+
+        let (copy, _) = app.create(&uri, json!({ "title": "Dinner" })).await;
+        assert_eq!(app.get(&format!("/api/ask/{}", copy), &[]).await.json()["data"]["content"]["title"], "Dinner");
+
+        for (content_type, body) in [("application/json", "{\"title\": "), ("application/json", "{\"expiry\": \"soon\"}"), ("text/plain", "{}")] {
+            let response = app.request(Method::POST, &uri, &[("Content-Type", content_type)], Body::from(body)).await;
+            assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", body);
+        }
+    }
+
+    #[tokio::test]
+    async fn reopen_bodies_are_optional_but_must_be_json() {
+        let app = TestApp::new().await;
+        let (id, token) = app.create("/api/ask", json!({
+            "title": "Lunch",
+            "survey_type": "singlechoice",
+            "items": ["pizza", "sushi"],
+            "expiry": 1,
+        })).await;
+        let owner = [(MANAGE_TOKEN_HEADER, token.as_str())];
+        let close_uri = format!("/api/ask/{}/close", id);
+        let close = || app.request(Method::POST, &close_uri, &owner, Body::empty());
+        let uri = format!("/api/ask/{}/reopen", id);
+
+        assert_eq!(close().await.status, StatusCode::OK);
+        let response = app.request(Method::POST, &uri, &owner, Body::empty()).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+
+        assert_eq!(close().await.status, StatusCode::OK);
+        let headers = [owner[0], ("Content-Type", "application/json")];
+        let response = app.request(Method::POST, &uri, &headers, Body::from("{\"closes_at\": \"tomorrow\"}")).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert!(app.get(&format!("/api/ask/{}", id), &[]).await.json()["data"]["content"]["closed_at"].is_string());
+    }
 }
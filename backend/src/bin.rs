@@ -1,6 +1,7 @@
 use axum::{
-    http::StatusCode,
-    extract::{Path, State},
+    body::{Body, Bytes},
+    http::{header, StatusCode, HeaderMap, HeaderValue},
+    extract::{rejection::JsonRejection, FromRequest, Multipart, Path, Query, Request, State},
     response::{Redirect, IntoResponse, Response, Html},
     Json
 };
@@ -16,15 +17,27 @@ use rand_core::OsRng;
 use uuid::Uuid;
 use std::fs;
 use std::time::{SystemTime, Duration};
+use chrono::{DateTime, Utc};
+use similar::TextDiff;
+use tokio_util::io::ReaderStream;
 
 use crate::state::AppState;
+use crate::owner;
+use crate::language;
+use crate::envelope::{self, Envelope};
+use crate::crawler;
 
 /*
  * --- Limits ---
  */
 
 const MAX_PASTE_SIZE: usize = 256 * 1024; // 256 KB
+pub const MAX_PASTE_BODY_SIZE: usize = envelope::encoded_len(MAX_PASTE_SIZE) + 64 * 1024; // an encrypted full-size paste plus settings
 const MAX_PASSWORD_LENGTH: usize = 256;
+const MAX_REVISIONS: u32 = 100;
+const MAX_FILES: usize = 20;
+const MAX_FORM_FIELD_LEN: usize = 1024; // text fields of attachment uploads
+const DIFF_CONTEXT_LINES: usize = 3;
 
 /*
  * --- Data Types ---
@@ -38,19 +51,149 @@ pub struct Paste {
     pub expiry: u32,
     pub uses: u32,
     pub created_at: SystemTime,
+    pub manage_token_hash: Option<String>,
+    pub revision: u32, // number of the current content, starting at 1
+    pub language: Option<String>,
+    pub filename: Option<String>,
+    pub files: Vec<PasteFile>, // multi-file pastes only, `content` is empty then
+    pub attachment: Option<Attachment>, // file drops, the bytes live in the data directory
+    pub encrypted: bool, // `content` of every revision is an envelope as JSON
+    pub parent_id: Option<String>, // paste this one was forked from
+    pub forks_consume: bool, // forks are allowed, their uses come out of this paste's
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub size: u64, // bytes
+}
+
+// One named file of a multi-file paste
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct PasteFile {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub content: String,
+}
+
+// Every version of the content, including the current one
+#[derive(Clone)]
+pub struct PasteRevision {
+    pub number: u32,
+    pub content: String,
+    pub files: Vec<PasteFile>,
+    pub created_at: SystemTime,
+}
+
+#[derive(Serialize)]
+pub struct RevisionInfo {
+    pub number: u32,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Deserialize)]
 pub struct NewPaste {
+    #[serde(default)]
     pub content: String,
     #[serde(default)]
     pub password: String,
     pub expiry: u32,
     pub uses: u32,
+    #[serde(default)]
+    pub language: Option<String>, // guessed from filename and content when missing
+    #[serde(default)]
+    pub filename: Option<String>,
+    #[serde(default)]
+    pub files: Vec<NewPasteFile>, // instead of content, in display order
+    #[serde(default)]
+    pub encrypted: Option<Envelope>, // zero-knowledge mode, instead of content and files
+    #[serde(default)]
+    pub forks_consume: bool,
+}
+
+#[derive(Deserialize)]
+pub struct NewPasteFile {
+    pub name: String,
+    pub content: String,
+    #[serde(default)]
+    pub language: Option<String>, // guessed like for single-file pastes
+}
+
+#[derive(Deserialize)]
+pub struct UpdatePaste {
+    pub content: Option<String>,
+    pub files: Option<Vec<NewPasteFile>>,
+    pub encrypted: Option<Envelope>, // encrypted pastes only
+    pub language: Option<String>, // empty string clears
+    pub filename: Option<String>, // empty string clears
+    pub password: Option<String>,
+    pub expiry: Option<u32>,
+    pub uses: Option<u32>,
+    pub forks_consume: Option<bool>,
+}
+
+// Everything is optional, a fork copies the content and settings it does not override
+#[derive(Deserialize, Default)]
+pub struct ForkRequest {
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub files: Option<Vec<NewPasteFile>>,
+    #[serde(default)]
+    pub encrypted: Option<Envelope>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub filename: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>, // keeps the original password unless set
+    #[serde(default)]
+    pub expiry: Option<u32>, // never outlives the original
+    #[serde(default)]
+    pub uses: Option<u32>, // taken from the uses the original has left, one by default
+    #[serde(default)]
+    pub forks_consume: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct PasteQuery {
+    #[serde(default)]
+    pub rev: Option<u32>,
+}
+
+// Settings of raw uploads, as query parameters or X-Paste-* headers
+#[derive(Deserialize)]
+pub struct RawPasteQuery {
+    #[serde(default)]
+    pub expiry: Option<u32>,
+    #[serde(default)]
+    pub uses: Option<u32>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub filename: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    #[serde(default)]
+    pub from: Option<u32>, // defaults to the revision before `to`
+    #[serde(default)]
+    pub to: Option<u32>, // defaults to the current revision
 }
 
 #[derive(Serialize)]
 pub struct CreatePasteResponse {
+    pub status: PasteStatus,
+    pub id: String,
+    pub manage_token: String,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct ManagePasteResponse {
     pub status: PasteStatus,
     pub id: String,
     pub message: String,
@@ -60,6 +203,72 @@ pub struct CreatePasteResponse {
 pub struct PasteData {
     pub id: String,
     pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<PasteFile>, // multi-file pastes, `content` is null then
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Attachment>, // downloaded through /api/bin/:id/download
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted: Option<Envelope>, // returned as stored, decrypted by the client
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub forks: Vec<String>, // ids of live forks, oldest first
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub forks_consume: bool,
+    pub revision: u32,
+    pub revisions: Vec<RevisionInfo>,
+}
+
+// What a paste is without what it says; reading this never counts as a use
+#[derive(Serialize)]
+pub struct PasteInfo {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>, // names of the files of multi-file pastes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Attachment>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+    pub uses: u32, // left, revealing takes one
+    pub expires_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub forks: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub forks_consume: bool,
+    pub revision: u32,
+    pub revisions: Vec<RevisionInfo>,
+}
+
+#[derive(Serialize)]
+pub struct PasteInfoResponse {
+    pub status: PasteStatus,
+    pub data: Option<PasteInfo>,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct DiffData {
+    pub id: String,
+    pub from: u32,
+    pub to: u32,
+    pub diff: String, // unified diff
+}
+
+#[derive(Serialize)]
+pub struct DiffResponse {
+    pub status: PasteStatus,
+    pub data: Option<DiffData>,
+    pub message: String,
 }
 
 #[derive(Serialize)]
@@ -82,7 +291,11 @@ pub enum BinError {
     Internal(String),
     InvalidInput(String),
     PasswordHashError(String),
+    Forbidden,
     NotFound,
+    RevisionNotFound,
+    FileNotFound,
+    TooLarge(String),
     Expired,
 }
 
@@ -99,8 +312,16 @@ impl IntoResponse for BinError {
                 (StatusCode::BAD_REQUEST, e),
             BinError::PasswordHashError(e) => 
                 (StatusCode::INTERNAL_SERVER_ERROR, e),
+            BinError::Forbidden => 
+                (StatusCode::FORBIDDEN, "Invalid or missing management token".into()),
             BinError::NotFound => 
                 (StatusCode::NOT_FOUND, "Paste not found".into()),
+            BinError::RevisionNotFound => 
+                (StatusCode::NOT_FOUND, "Revision not found".into()),
+            BinError::FileNotFound => 
+                (StatusCode::NOT_FOUND, "File not found".into()),
+            BinError::TooLarge(e) => 
+                (StatusCode::PAYLOAD_TOO_LARGE, e),
             BinError::Expired => 
                 (StatusCode::GONE, "Paste expired".into()),
         };
@@ -120,6 +341,12 @@ impl From<rusqlite::Error> for BinError {
     }
 }
 
+impl From<std::io::Error> for BinError {
+    fn from(err: std::io::Error) -> Self {
+        BinError::Internal(err.to_string())
+    }
+}
+
 /*
  * --- Helper Functions ---
  */
@@ -133,7 +360,7 @@ fn verify_password(paste: &Paste, auth: &Option<TypedHeader<Authorization<Basic>
 
             if parsed_hash
                 .ok()
-                .map_or(false, |ph| argon2.verify_password(provided_pw.as_bytes(), &ph).is_ok())
+                .is_some_and(|ph| argon2.verify_password(provided_pw.as_bytes(), &ph).is_ok())
             {
                 Ok(())
             } else {
@@ -152,120 +379,883 @@ enum PasswordError {
     Incorrect,
 }
 
-fn success_response(paste: &Paste) -> PasteResponse {
-    PasteResponse {
+// Bodies that are all optional fields may be left out entirely, anything sent has to be valid JSON
+fn optional_json<T: Default>(headers: &HeaderMap, body: Result<Json<T>, JsonRejection>) -> Result<T, BinError> {
+    let empty = headers
+        .get(header::CONTENT_LENGTH)
+        .map_or(!headers.contains_key(header::TRANSFER_ENCODING), |length| length == "0");
+
+    match body {
+        Ok(Json(body)) => Ok(body),
+        Err(JsonRejection::MissingJsonContentType(_)) if empty => Ok(T::default()),
+        Err(rejection) => Err(BinError::InvalidInput(rejection.body_text())),
+    }
+}
+
+fn success_response(
+    paste: &Paste,
+    revision: &PasteRevision,
+    revisions: Vec<RevisionInfo>,
+    forks: Vec<String>,
+) -> Result<PasteResponse, BinError> {
+    // Earlier revisions may have had files while the paste now has one text, or the other way round
+    let single_file = revision.files.is_empty() && paste.attachment.is_none() && !paste.encrypted;
+
+    let encrypted = if paste.encrypted {
+        Some(serde_json::from_str(&revision.content).map_err(|e| BinError::Internal(e.to_string()))?)
+    } else {
+        None
+    };
+
+    Ok(PasteResponse {
         status: PasteStatus::Success,
         data: Some(PasteData {
             id: paste.id.clone(),
-            content: Some(paste.content.clone()),
+            content: Some(revision.content.clone()).filter(|_| single_file),
+            language: paste.language.clone().filter(|_| single_file),
+            filename: paste.filename.clone().filter(|_| single_file),
+            files: revision.files.clone(),
+            attachment: paste.attachment.clone(),
+            encrypted,
+            parent_id: paste.parent_id.clone(),
+            forks,
+            forks_consume: paste.forks_consume,
+            revision: revision.number,
+            revisions,
         }),
         message: "Paste retrieved successfully.".into(),
-    }
+    })
 }
 
-fn protected_response(message: &str) -> PasteResponse {
-    PasteResponse {
-        status: PasteStatus::Protected,
-        data: None,
-        message: message.into(),
+// (name, content) pairs; single-file content counts as one file without a name
+fn revision_files(revision: &PasteRevision) -> Vec<(&str, &str)> {
+    if revision.files.is_empty() {
+        return vec![("", revision.content.as_str())];
     }
+
+    revision.files
+        .iter()
+        .map(|file| (file.name.as_str(), file.content.as_str()))
+        .collect()
 }
 
-fn is_expired(paste: &Paste) -> bool {
-    if let Ok(elapsed) = paste.created_at.elapsed() {
-        elapsed > Duration::from_secs(paste.expiry as u64 * 3600)
-    } else {
-        true // treat weird timestamps as expired
+// File by file in order, files only on one side diff against nothing
+fn unified_diff(from: &PasteRevision, to: &PasteRevision) -> String {
+    let old = revision_files(from);
+    let new = revision_files(to);
+
+    let mut names: Vec<&str> = old.iter().map(|(name, _)| *name).collect();
+    for (name, _) in &new {
+        if !names.contains(name) {
+            names.push(name);
+        }
     }
-}
 
-/*
- * --- Handlers ---
- */
+    fn content<'a>(files: &[(&str, &'a str)], name: &str) -> &'a str {
+        files.iter().find(|(file, _)| *file == name).map_or("", |(_, content)| *content)
+    }
+    let label = |number: u32, name: &str| match name {
+        "" => format!("revision {}", number),
+        name => format!("revision {}/{}", number, name),
+    };
 
-// Redirect /bin to /bin.html
-pub async fn bin_html() -> impl IntoResponse {
-    Redirect::to("/bin.html")
+    names
+        .iter()
+        .map(|name| (name, content(&old, name), content(&new, name)))
+        .filter(|(_, old, new)| old != new)
+        .map(|(name, old, new)| {
+            TextDiff::from_lines(old, new)
+                .unified_diff()
+                .context_radius(DIFF_CONTEXT_LINES)
+                .header(&label(from.number, name), &label(to.number, name))
+                .to_string()
+        })
+        .collect()
 }
 
-pub async fn serve_bin_html() -> impl IntoResponse {
-    let html = fs::read_to_string("static/bin.html")
-        .expect("bin.html not found");
-    Html(html)
+// A single-file paste also answers under its own filename, so raw URLs look alike
+fn select_file(paste: &Paste, revision: PasteRevision, name: Option<&str>) -> Result<PasteFile, BinError> {
+    if revision.files.is_empty() {
+        if name.is_some_and(|name| paste.filename.as_deref() != Some(name)) {
+            return Err(BinError::FileNotFound);
+        }
+
+        return Ok(PasteFile {
+            name: paste.filename.clone().unwrap_or_default(),
+            language: paste.language.clone(),
+            content: revision.content,
+        });
+    }
+
+    match name {
+        Some(name) => revision.files
+            .into_iter()
+            .find(|file| file.name == name)
+            .ok_or(BinError::FileNotFound),
+        None if revision.files.len() == 1 => revision.files
+            .into_iter()
+            .next()
+            .ok_or(BinError::FileNotFound),
+        None => {
+            let names: Vec<&str> = revision.files.iter().map(|file| file.name.as_str()).collect();
+            Err(BinError::InvalidInput(format!("This paste has several files, request one of: {}", names.join(", "))))
+        }
+    }
 }
 
-// Create new paste 
-pub async fn create_paste(
-    State(state): State<AppState>,
-    Json(new_paste): Json<NewPaste>,
-) -> Result<Json<CreatePasteResponse>, BinError>{
-    let id = Uuid::new_v4().to_string();
+fn protected_response(message: &str) -> PasteResponse {
+    PasteResponse {
+        status: PasteStatus::Protected,
+        data: None,
+        message: message.into(),
+    }
+}
 
-    // Sanitize and validate settings values 
-    if new_paste.expiry == 0 || new_paste.expiry > 9999 {
+fn validate_expiry(expiry: u32) -> Result<u32, BinError> {
+    if expiry == 0 || expiry > 9999 {
         return Err(BinError::InvalidInput("Invalid expiry value".into()));
     }
-    
-    if new_paste.uses == 0 || new_paste.uses > 9999 {
+
+    Ok(expiry)
+}
+
+fn validate_uses(uses: u32) -> Result<u32, BinError> {
+    if uses == 0 || uses > 9999 {
         return Err(BinError::InvalidInput("Invalid uses value".into()));
     }
 
-    // Sanitize and validate content
-    let content = new_paste.content.trim().to_string();
-    
+    Ok(uses)
+}
+
+fn sanitize_content(raw: &str) -> Result<String, BinError> {
+    let content = raw.trim().to_string();
+
     if content.is_empty() {
         return Err(BinError::InvalidInput("Content cannot be empty".into()));
     }
-    
+
     if content.len() > MAX_PASTE_SIZE {
         return Err(BinError::InvalidInput(format!("Paste content exceeds maximum size of {} bytes", MAX_PASTE_SIZE)));
     }
 
+    Ok(content)
+}
+
+fn hash_password(password: &str) -> Result<Option<String>, BinError> {
     // Validate password length
-    if new_paste.password.len() > MAX_PASSWORD_LENGTH {
+    if password.len() > MAX_PASSWORD_LENGTH {
         return Err(BinError::InvalidInput("Password too long".into()));
     }
 
     // Create password hash if a password was set
-    let password_hash = if !new_paste.password.is_empty() {
-        let mut rng = OsRng;
-        let salt = SaltString::generate(&mut rng);
-        let argon2 = Argon2::default();
-        let hash = argon2
-            .hash_password(new_paste.password.as_bytes(), &salt)
-            .map_err(|e| BinError::PasswordHashError(e.to_string()))?
-            .to_string();
-        Some(hash)
+    if password.is_empty() {
+        return Ok(None);
+    }
+
+    let mut rng = OsRng;
+    let salt = SaltString::generate(&mut rng);
+    let argon2 = Argon2::default();
+    let hash = argon2
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| BinError::PasswordHashError(e.to_string()))?
+        .to_string();
+
+    Ok(Some(hash))
+}
+
+// Query parameters win over headers
+fn raw_setting(query: Option<u32>, headers: &HeaderMap, name: &str) -> Result<u32, BinError> {
+    optional_raw_setting(query, headers, name)?.ok_or_else(|| {
+        BinError::InvalidInput(format!("Missing {} (query parameter or X-Paste-{} header)", name.to_lowercase(), name))
+    })
+}
+
+fn optional_raw_setting(query: Option<u32>, headers: &HeaderMap, name: &str) -> Result<Option<u32>, BinError> {
+    if let Some(value) = query {
+        return Ok(Some(value));
+    }
+
+    let header = format!("X-Paste-{}", name);
+    headers
+        .get(&header)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or_else(|| BinError::InvalidInput(format!("Invalid {} header", header)))
+        })
+        .transpose()
+}
+
+fn raw_text_setting(query: Option<String>, headers: &HeaderMap, name: &str) -> Option<String> {
+    query.or_else(|| {
+        headers
+            .get(format!("X-Paste-{}", name))
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    })
+}
+
+fn is_raw_content_type(headers: &HeaderMap) -> bool {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase());
+
+    matches!(content_type.as_deref(), Some("text/plain") | Some("application/octet-stream"))
+}
+
+// Raw clients get a Basic auth challenge instead of a JSON status
+fn auth_required(message: &'static str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [
+            (header::WWW_AUTHENTICATE, "Basic realm=\"Polly\", charset=\"UTF-8\""),
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+        ],
+        format!("{}\n", message),
+    ).into_response()
+}
+
+// Plain filename for old clients plus the RFC 5987 form, so non-ASCII names survive
+fn content_disposition(kind: &str, filename: &str) -> Option<HeaderValue> {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii_graphic() || c == ' ' { c } else { '_' })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            b => format!("%{:02X}", b),
+        })
+        .collect();
+
+    HeaderValue::from_str(&format!("{}; filename=\"{}\"; filename*=UTF-8''{}", kind, fallback, encoded)).ok()
+}
+
+// Only a bare type/subtype is kept; anything odd is served as opaque bytes
+fn sanitize_content_type(raw: Option<&str>) -> String {
+    raw.and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .filter(|value| {
+            value.split_once('/').is_some_and(|(kind, subtype)| !kind.is_empty() && !subtype.is_empty() && !subtype.contains('/'))
+                && value.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '+' | '-' | '_'))
+        })
+        .unwrap_or_else(|| "application/octet-stream".into())
+}
+
+fn is_multipart(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().to_ascii_lowercase().starts_with("multipart/form-data"))
+}
+
+// Attachments have no text to show, diff or edit, encrypted pastes none the server can read
+fn text_only(paste: &Paste) -> Result<(), BinError> {
+    if paste.attachment.is_some() {
+        return Err(BinError::InvalidInput(format!("This paste is a file attachment, download it from /api/bin/{}/download", paste.id)));
+    }
+
+    if paste.encrypted {
+        return Err(BinError::InvalidInput("This paste is encrypted and can only be read with its key".into()));
+    }
+
+    Ok(())
+}
+
+fn envelope_content(envelope: &Envelope) -> Result<String, BinError> {
+    envelope::validate(envelope, MAX_PASTE_SIZE).map_err(BinError::InvalidInput)?;
+    serde_json::to_string(envelope).map_err(|e| BinError::Internal(e.to_string()))
+}
+
+// What an upload brought along besides the bytes
+#[derive(Default)]
+struct Upload {
+    size: Option<u64>,
+    filename: Option<String>,
+    content_type: Option<String>,
+    expiry: Option<u32>,
+    uses: Option<u32>,
+    password: Option<String>,
+}
+
+// Rejects settings known to be invalid before any bytes are written; while form fields
+// may still follow the file, missing expiry and uses are not an error yet
+fn check_upload(upload: &Upload, query: &RawPasteQuery, headers: &HeaderMap, complete: bool) -> Result<(), BinError> {
+    let expiry = optional_raw_setting(upload.expiry.or(query.expiry), headers, "Expiry")?;
+    let uses = optional_raw_setting(upload.uses.or(query.uses), headers, "Uses")?;
+
+    if complete {
+        raw_setting(expiry, headers, "Expiry")?;
+        raw_setting(uses, headers, "Uses")?;
+    }
+
+    expiry.map(validate_expiry).transpose()?;
+    uses.map(validate_uses).transpose()?;
+
+    let password = upload.password.clone().or_else(|| raw_text_setting(None, headers, "Password"));
+    if password.is_some_and(|password| password.len() > MAX_PASSWORD_LENGTH) {
+        return Err(BinError::InvalidInput("Password too long".into()));
+    }
+
+    let filename = upload.filename.clone().or_else(|| raw_text_setting(query.filename.clone(), headers, "Filename"));
+    sanitize_filename(filename.as_deref())?;
+
+    Ok(())
+}
+
+// Field order is up to the client, so settings may arrive before or after the file
+async fn receive_multipart(
+    state: &AppState,
+    id: &str,
+    request: Request,
+    query: &RawPasteQuery,
+    headers: &HeaderMap,
+) -> Result<Upload, BinError> {
+    let mut multipart = Multipart::from_request(request, state)
+        .await
+        .map_err(|e| BinError::InvalidInput(e.body_text()))?;
+    let mut upload = Upload::default();
+
+    while let Some(mut field) = multipart.next_field().await.map_err(|e| BinError::InvalidInput(e.body_text()))? {
+        let name = field.name().unwrap_or_default().to_string();
+
+        if name == "file" {
+            if upload.size.is_some() {
+                return Err(BinError::InvalidInput("Only one file per upload".into()));
+            }
+
+            upload.filename = upload.filename.or(field.file_name().map(str::to_string));
+            upload.content_type = field.content_type().map(str::to_string);
+            check_upload(&upload, query, headers, false)?;
+
+            upload.size = Some(state.attachments.receive(id, field).await?);
+            continue;
+        }
+
+        if !matches!(name.as_str(), "expiry" | "uses" | "password" | "filename") {
+            continue; // unknown fields are ignored
+        }
+
+        let mut value = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(|e| BinError::InvalidInput(e.body_text()))? {
+            value.extend_from_slice(&chunk);
+            if value.len() > MAX_FORM_FIELD_LEN {
+                return Err(BinError::InvalidInput(format!("Field {} is too long", name)));
+            }
+        }
+        let value = String::from_utf8(value)
+            .map_err(|_| BinError::InvalidInput(format!("Field {} must be valid UTF-8", name)))?;
+
+        match name.as_str() {
+            "expiry" => upload.expiry = Some(value.trim().parse().map_err(|_| BinError::InvalidInput("Invalid expiry value".into()))?),
+            "uses" => upload.uses = Some(value.trim().parse().map_err(|_| BinError::InvalidInput("Invalid uses value".into()))?),
+            "password" => upload.password = Some(value),
+            _ => upload.filename = Some(value),
+        }
+    }
+
+    Ok(upload)
+}
+
+fn sanitize_filename(raw: Option<&str>) -> Result<Option<String>, BinError> {
+    raw.map_or(Ok(None), |raw| language::sanitize_filename(raw).map_err(BinError::InvalidInput))
+}
+
+// Highlighting metadata; the language is guessed unless given
+fn paste_language(language: Option<&str>, filename: Option<&str>, content: &str) -> Result<Option<String>, BinError> {
+    match language.map(language::sanitize_language) {
+        Some(Ok(Some(language))) => Ok(Some(language)),
+        Some(Err(e)) => Err(BinError::InvalidInput(e)),
+        Some(Ok(None)) | None => Ok(language::detect(filename, content)),
+    }
+}
+
+// Names double as raw URL segments, so they are required and unique
+fn sanitize_files(new_files: Vec<NewPasteFile>) -> Result<Vec<PasteFile>, BinError> {
+    if new_files.len() > MAX_FILES {
+        return Err(BinError::InvalidInput(format!("Paste cannot have more than {} files", MAX_FILES)));
+    }
+
+    let mut files: Vec<PasteFile> = Vec::with_capacity(new_files.len());
+
+    for new_file in new_files {
+        let name = sanitize_filename(Some(&new_file.name))?
+            .ok_or_else(|| BinError::InvalidInput("Every file needs a name".into()))?;
+
+        if files.iter().any(|file| file.name.eq_ignore_ascii_case(&name)) {
+            return Err(BinError::InvalidInput(format!("Duplicate file name: {}", name)));
+        }
+
+        let content = new_file.content.trim().to_string();
+
+        if content.is_empty() {
+            return Err(BinError::InvalidInput(format!("File {} cannot be empty", name)));
+        }
+
+        if content.len() > MAX_PASTE_SIZE {
+            return Err(BinError::InvalidInput(format!("File {} exceeds maximum size of {} bytes", name, MAX_PASTE_SIZE)));
+        }
+
+        let language = paste_language(new_file.language.as_deref(), Some(&name), &content)?;
+        files.push(PasteFile { name, language, content });
+    }
+
+    // The whole paste has the same budget as a single-file one
+    let total: usize = files.iter().map(|file| file.content.len()).sum();
+    if total > MAX_PASTE_SIZE {
+        return Err(BinError::InvalidInput(format!("Paste files exceed a combined maximum size of {} bytes", MAX_PASTE_SIZE)));
+    }
+
+    Ok(files)
+}
+
+fn build_paste(new_paste: NewPaste) -> Result<(Paste, String), BinError> {
+    let id = Uuid::new_v4().to_string();
+
+    // Sanitize and validate settings values 
+    let expiry = validate_expiry(new_paste.expiry)?;
+    let uses = validate_uses(new_paste.uses)?;
+
+    // Sanitize and validate content, either one text, a list of named files or an envelope
+    let encrypted = new_paste.encrypted.is_some();
+
+    let (content, language, filename, files) = if let Some(envelope) = &new_paste.encrypted {
+        if !new_paste.content.trim().is_empty() || new_paste.language.is_some() || new_paste.filename.is_some() || !new_paste.files.is_empty() {
+            return Err(BinError::InvalidInput("Encrypted pastes keep content, files and metadata inside the envelope".into()));
+        }
+        (envelope_content(envelope)?, None, None, Vec::new())
+    } else if new_paste.files.is_empty() {
+        let content = sanitize_content(&new_paste.content)?;
+        let filename = sanitize_filename(new_paste.filename.as_deref())?;
+        let language = paste_language(new_paste.language.as_deref(), filename.as_deref(), &content)?;
+        (content, language, filename, Vec::new())
     } else {
-        None
+        if !new_paste.content.trim().is_empty() || new_paste.language.is_some() || new_paste.filename.is_some() {
+            return Err(BinError::InvalidInput("Send either content or files, not both".into()));
+        }
+        (String::new(), None, None, sanitize_files(new_paste.files)?)
     };
 
+    // Create password hash if a password was set
+    let password_hash = hash_password(&new_paste.password)?;
+
+    // Create management token for the owner
+    let (manage_token, manage_token_hash) = owner::generate_token();
+
     // Create proper paste
     let paste = Paste {
-        id: id.clone(),
-        content: content,
+        id,
+        content,
         password_hash,
-        expiry: new_paste.expiry,
-        uses: new_paste.uses,
+        expiry,
+        uses,
         created_at: SystemTime::now(),
+        manage_token_hash: Some(manage_token_hash),
+        revision: 1,
+        language,
+        filename,
+        files,
+        attachment: None,
+        encrypted,
+        parent_id: None,
+        forks_consume: new_paste.forks_consume,
     };
 
-    state.bin.create_paste(paste).await?;
+    Ok((paste, manage_token))
+}
 
-    // Return the paste id to the frontend
-    Ok(Json(CreatePasteResponse {
-        status: PasteStatus::Success,
-        id,
-        message: "Paste created successfully.".into(),
-    }))
+fn expires_at(paste: &Paste) -> DateTime<Utc> {
+    DateTime::<Utc>::from(paste.created_at + Duration::from_secs(paste.expiry as u64 * 3600))
 }
 
-// Retrieve existing paste
-pub async fn get_paste(
-    Path(id): Path<String>,
-    State(state): State<AppState>,
-    auth: Option<TypedHeader<Authorization<Basic>>>,
-) -> Result<Json<PasteResponse>, BinError> {
+pub fn is_expired(paste: &Paste) -> bool {
+    if let Ok(elapsed) = paste.created_at.elapsed() {
+        elapsed > Duration::from_secs(paste.expiry as u64 * 3600)
+    } else {
+        true // treat weird timestamps as expired
+    }
+}
+
+/*
+ * --- Handlers ---
+ */
+
+// Redirect /bin to /bin.html
+pub async fn bin_html() -> impl IntoResponse {
+    Redirect::to("/bin.html")
+}
+
+pub async fn serve_bin_html() -> impl IntoResponse {
+    let html = fs::read_to_string("static/bin.html")
+        .expect("bin.html not found");
+    Html(html)
+}
+
+// Create new paste 
+pub async fn create_paste(
+    State(state): State<AppState>,
+    Json(new_paste): Json<NewPaste>,
+) -> Result<Json<CreatePasteResponse>, BinError>{
+    let (paste, manage_token) = build_paste(new_paste)?;
+    let id = paste.id.clone();
+
+    state.bin.create_paste(paste).await?;
+
+    // Return the paste id and management token to the frontend
+    Ok(Json(CreatePasteResponse {
+        status: PasteStatus::Success,
+        id,
+        manage_token,
+        message: "Paste created successfully.".into(),
+    }))
+}
+
+// Create new paste from a plain request body, e.g. `curl --data-binary @log.txt`
+pub async fn create_raw_paste(
+    State(state): State<AppState>,
+    Query(query): Query<RawPasteQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, BinError> {
+    if !is_raw_content_type(&headers) {
+        return Err(BinError::InvalidInput("Content-Type must be text/plain or application/octet-stream".into()));
+    }
+
+    let content = String::from_utf8(body.to_vec())
+        .map_err(|_| BinError::InvalidInput("Content must be valid UTF-8 text".into()))?;
+
+    let (paste, manage_token) = build_paste(NewPaste {
+        content,
+        password: String::new(),
+        expiry: raw_setting(query.expiry, &headers, "Expiry")?,
+        uses: raw_setting(query.uses, &headers, "Uses")?,
+        language: raw_text_setting(query.language, &headers, "Language"),
+        filename: raw_text_setting(query.filename, &headers, "Filename"),
+        files: Vec::new(),
+        encrypted: None,
+        forks_consume: false,
+    })?;
+    let id = paste.id.clone();
+
+    state.bin.create_paste(paste).await?;
+
+    // One key=value per line, easy to grep or eval in scripts
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        format!("id={}\nmanage_token={}\nraw=/bin/{}/raw\n", id, manage_token, id),
+    ).into_response())
+}
+
+// Upload a binary file as multipart form (field "file") or as the plain request body, e.g.
+//   curl -F file=@photo.jpg -F expiry=24 -F uses=1 ...
+//   curl -T photo.jpg -H "X-Paste-Expiry: 24" -H "X-Paste-Uses: 1" ".../api/bin/upload?filename=photo.jpg"
+pub async fn upload_attachment(
+    State(state): State<AppState>,
+    Query(query): Query<RawPasteQuery>,
+    request: Request,
+) -> Result<Json<CreatePasteResponse>, BinError> {
+    let id = Uuid::new_v4().to_string();
+    let headers = request.headers().clone();
+
+    let upload = if is_multipart(&headers) {
+        receive_multipart(&state, &id, request, &query, &headers).await
+    } else {
+        // Everything but the bytes is known up front
+        check_upload(&Upload::default(), &query, &headers, true)?;
+
+        let size = state.attachments.receive(&id, request.into_body().into_data_stream()).await;
+        size.map(|size| Upload {
+            size: Some(size),
+            content_type: headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).map(str::to_string),
+            ..Upload::default()
+        })
+    };
+
+    // Nothing may stay behind on disk when the upload is refused
+    let created = match upload {
+        Ok(upload) => store_attachment(&state, &id, upload, query, &headers).await,
+        Err(e) => Err(e),
+    };
+
+    let manage_token = match created {
+        Ok(manage_token) => manage_token,
+        Err(e) => {
+            state.attachments.discard(&id).await;
+            return Err(e);
+        }
+    };
+
+    Ok(Json(CreatePasteResponse {
+        status: PasteStatus::Success,
+        id,
+        manage_token,
+        message: "Attachment uploaded successfully.".into(),
+    }))
+}
+
+async fn store_attachment(
+    state: &AppState,
+    id: &str,
+    upload: Upload,
+    query: RawPasteQuery,
+    headers: &HeaderMap,
+) -> Result<String, BinError> {
+    let size = upload.size.ok_or_else(|| BinError::InvalidInput("Missing file".into()))?;
+
+    // Form fields win over query parameters, which win over headers
+    let expiry = validate_expiry(raw_setting(upload.expiry.or(query.expiry), headers, "Expiry")?)?;
+    let uses = validate_uses(raw_setting(upload.uses.or(query.uses), headers, "Uses")?)?;
+    let password = upload.password.or_else(|| raw_text_setting(None, headers, "Password")).unwrap_or_default();
+
+    let filename = upload.filename.or_else(|| raw_text_setting(query.filename, headers, "Filename"));
+    let filename = sanitize_filename(filename.as_deref())?.unwrap_or_else(|| "attachment".into());
+
+    // Create password hash if a password was set
+    let password_hash = hash_password(&password)?;
+
+    // Create management token for the owner
+    let (manage_token, manage_token_hash) = owner::generate_token();
+
+    let paste = Paste {
+        id: id.to_string(),
+        content: String::new(),
+        password_hash,
+        expiry,
+        uses,
+        created_at: SystemTime::now(),
+        manage_token_hash: Some(manage_token_hash),
+        revision: 1,
+        language: None,
+        filename: None,
+        files: Vec::new(),
+        attachment: Some(Attachment {
+            filename,
+            content_type: sanitize_content_type(upload.content_type.as_deref()),
+            size,
+        }),
+        encrypted: false,
+        parent_id: None,
+        forks_consume: false,
+    };
+
+    // The paste goes first, so the cleanup task never finds a file without one
+    state.bin.create_paste(paste).await?;
+
+    if let Err(e) = state.attachments.commit(id).await {
+        state.bin.delete_paste(id).await.ok();
+        return Err(e);
+    }
+
+    Ok(manage_token)
+}
+
+// Download an attachment; every download counts as a use
+pub async fn download_attachment(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, BinError> {
+    if crawler::is_crawler(&headers) {
+        return info_response(&state, &id, &auth).await;
+    }
+
+    let preview = state.bin.get_paste(&id).await?;
+
+    // Expiry check 
+    if is_expired(&preview) {
+        return Err(BinError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&preview, &auth) {
+        Ok(()) => {},
+        Err(PasswordError::Missing) => return Ok(auth_required("This paste is password protected.")),
+        Err(PasswordError::Incorrect) => return Ok(auth_required("Incorrect password.")),
+    }
+
+    let attachment = preview.attachment
+        .ok_or_else(|| BinError::InvalidInput("This paste has no attachment".into()))?;
+
+    let file = state.attachments.open(&id).await?;
+    let consumed = state.bin.consume_paste(&id, 1).await?;
+
+    // The open handle keeps streaming after the last use removed the file
+    if consumed.uses == 0 {
+        state.attachments.remove(&id).await?;
+    }
+
+    let mut response = Body::from_stream(ReaderStream::new(file)).into_response();
+    let headers = response.headers_mut();
+
+    if let Ok(content_type) = HeaderValue::from_str(&attachment.content_type) {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(attachment.size));
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+
+    // Always saved, never rendered in the browser
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        content_disposition("attachment", &attachment.filename).unwrap_or(HeaderValue::from_static("attachment")),
+    );
+
+    Ok(response)
+}
+
+// Retrieve existing paste as plain text
+pub async fn get_raw_paste(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<PasteQuery>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, BinError> {
+    raw_response(&state, &id, None, query, &headers, auth).await
+}
+
+// Retrieve one file of a paste as plain text
+pub async fn get_raw_file(
+    Path((id, name)): Path<(String, String)>,
+    State(state): State<AppState>,
+    Query(query): Query<PasteQuery>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, BinError> {
+    raw_response(&state, &id, Some(&name), query, &headers, auth).await
+}
+
+async fn raw_response(
+    state: &AppState,
+    id: &str,
+    name: Option<&str>,
+    query: PasteQuery,
+    headers: &HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, BinError> {
+    if crawler::is_crawler(headers) {
+        return info_response(state, id, &auth).await;
+    }
+
+    let preview = state.bin.get_paste(id).await?;
+
+    // Expiry check 
+    if is_expired(&preview) {
+        return Err(BinError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&preview, &auth) {
+        Ok(()) => {},
+        Err(PasswordError::Missing) => return Ok(auth_required("This paste is password protected.")),
+        Err(PasswordError::Incorrect) => return Ok(auth_required("Incorrect password.")),
+    }
+
+    text_only(&preview)?;
+
+    let revision = state.bin.get_revision(id, query.rev.unwrap_or(preview.revision)).await?;
+    let file = select_file(&preview, revision, name)?;
+    state.bin.consume_paste(id, 1).await?;
+
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, language::content_type(file.language.as_deref())),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        ],
+        file.content,
+    ).into_response();
+
+    // Lets `curl -OJ` save the paste under its own name
+    if !file.name.is_empty() {
+        if let Some(value) = content_disposition("inline", &file.name) {
+            response.headers_mut().insert(header::CONTENT_DISPOSITION, value);
+        }
+    }
+
+    Ok(response)
+}
+
+// Describe an existing paste without its content; never counts as a use.
+// Content, of any revision, comes from POST /api/bin/{id}/reveal?rev=N.
+pub async fn get_paste(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<PasteQuery>,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, BinError> {
+    if query.rev.is_some() {
+        return Err(BinError::InvalidInput(format!("Revisions are read with POST /api/bin/{}/reveal?rev=N", id)));
+    }
+
+    info_response(&state, &id, &auth).await
+}
+
+async fn info_response(
+    state: &AppState,
+    id: &str,
+    auth: &Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, BinError> {
+    let paste = state.bin.get_paste(id).await?;
+
+    // Expiry check 
+    if is_expired(&paste) {
+        return Err(BinError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&paste, auth) {
+        Ok(()) => {},
+        Err(PasswordError::Missing) => {
+            return Ok(Json(protected_response("This paste is password protected.")).into_response());
+        }
+        Err(PasswordError::Incorrect) => {
+            return Ok(Json(protected_response("Incorrect password.")).into_response());
+        }
+    }
+
+    let revisions = state.bin.list_revisions(id).await?;
+    let forks = state.bin.list_forks(id).await?;
+    let single_file = paste.files.is_empty() && paste.attachment.is_none() && !paste.encrypted;
+
+    Ok(Json(PasteInfoResponse {
+        status: PasteStatus::Success,
+        data: Some(PasteInfo {
+            id: paste.id.clone(),
+            language: paste.language.clone().filter(|_| single_file),
+            filename: paste.filename.clone().filter(|_| single_file),
+            files: paste.files.iter().map(|file| file.name.clone()).collect(),
+            attachment: paste.attachment.clone(),
+            encrypted: paste.encrypted,
+            uses: paste.uses,
+            expires_at: expires_at(&paste),
+            parent_id: paste.parent_id.clone(),
+            forks,
+            forks_consume: paste.forks_consume,
+            revision: paste.revision,
+            revisions,
+        }),
+        message: "Paste info retrieved successfully.".into(),
+    }).into_response())
+}
+
+// Retrieve the content of an existing paste, optionally an earlier revision of it; counts as a use
+pub async fn reveal_paste(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<PasteQuery>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, BinError> {
+    // Link previews only ever get the description
+    if crawler::is_crawler(&headers) {
+        return info_response(&state, &id, &auth).await;
+    }
+
     let preview = state.bin.get_paste(&id).await?;
 
     // Expiry check 
@@ -276,14 +1266,825 @@ pub async fn get_paste(
     // Password check 
     match verify_password(&preview, &auth) {
         Ok(()) => {
-            let consumed = state.bin.consume_paste(&id).await?;
-            Ok(Json(success_response(&consumed)))
+            // Read the history first, the last use deletes it
+            let revision = state.bin.get_revision(&id, query.rev.unwrap_or(preview.revision)).await?;
+            let revisions = state.bin.list_revisions(&id).await?;
+            let forks = state.bin.list_forks(&id).await?;
+
+            // Attachments count a use when downloaded, not when looked at
+            if preview.attachment.is_some() {
+                return Ok(Json(success_response(&preview, &revision, revisions, forks)?).into_response());
+            }
+
+            let consumed = state.bin.consume_paste(&id, 1).await?;
+            Ok(Json(success_response(&consumed, &revision, revisions, forks)?).into_response())
+        }
+        Err(PasswordError::Missing) => {
+            Ok(Json(protected_response("This paste is password protected.")).into_response())
         }
+        Err(PasswordError::Incorrect) => {
+            Ok(Json(protected_response("Incorrect password.")).into_response())
+        }
+    }
+}
+
+// Compare two revisions of a paste; reading a diff counts as a use
+pub async fn diff_paste(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<DiffQuery>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, BinError> {
+    if crawler::is_crawler(&headers) {
+        return info_response(&state, &id, &auth).await;
+    }
+
+    let preview = state.bin.get_paste(&id).await?;
+
+    // Expiry check 
+    if is_expired(&preview) {
+        return Err(BinError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&preview, &auth) {
+        Ok(()) => {},
         Err(PasswordError::Missing) => {
-            Ok(Json(protected_response("This paste is password protected.")))
+            return Ok(Json(protected_response("This paste is password protected.")).into_response());
         }
         Err(PasswordError::Incorrect) => {
-            Ok(Json(protected_response("Incorrect password.")))
+            return Ok(Json(protected_response("Incorrect password.")).into_response());
+        }
+    }
+
+    text_only(&preview)?;
+
+    let to = query.to.unwrap_or(preview.revision);
+    let from = query.from.unwrap_or(to.saturating_sub(1).max(1));
+
+    let from = state.bin.get_revision(&id, from).await?;
+    let to = state.bin.get_revision(&id, to).await?;
+
+    state.bin.consume_paste(&id, 1).await?;
+
+    Ok(Json(DiffResponse {
+        status: PasteStatus::Success,
+        data: Some(DiffData {
+            id,
+            from: from.number,
+            to: to.number,
+            diff: unified_diff(&from, &to),
+        }),
+        message: "Paste diff retrieved successfully.".into(),
+    }).into_response())
+}
+
+// Start a new paste from an existing one, optionally an earlier revision or with changed content.
+// Only pastes whose owner set forks_consume can be forked. Every read of a fork is a read of the
+// original, so the fork's uses are taken from the original's and it never outlives it.
+pub async fn fork_paste(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Query(query): Query<PasteQuery>,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+    headers: HeaderMap,
+    body: Result<Json<ForkRequest>, JsonRejection>,
+) -> Result<Response, BinError> {
+    let fork = optional_json(&headers, body)?;
+    let source = state.bin.get_paste(&id).await?;
+
+    // Expiry check 
+    if is_expired(&source) {
+        return Err(BinError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&source, &auth) {
+        Ok(()) => {},
+        Err(PasswordError::Missing) => {
+            return Ok(Json(protected_response("This paste is password protected.")).into_response());
+        }
+        Err(PasswordError::Incorrect) => {
+            return Ok(Json(protected_response("Incorrect password.")).into_response());
+        }
+    }
+
+    if source.attachment.is_some() {
+        return Err(BinError::InvalidInput("Attachments cannot be forked".into()));
+    }
+
+    if !source.forks_consume {
+        return Err(BinError::InvalidInput("This paste cannot be forked, its owner has not allowed forks".into()));
+    }
+
+    let uses = fork.uses.unwrap_or(1);
+    if uses > source.uses {
+        return Err(BinError::InvalidInput(format!("The original has only {} uses left", source.uses)));
+    }
+
+    let revision = state.bin.get_revision(&id, query.rev.unwrap_or(source.revision)).await?;
+    let single_file_source = revision.files.is_empty() && !source.encrypted;
+
+    // Copy the content unless the fork brings its own; it is validated like a new paste either way
+    let changed = fork.content.is_some() || fork.files.is_some() || fork.encrypted.is_some();
+
+    let (content, files, encrypted) = if changed {
+        (fork.content.unwrap_or_default(), fork.files.unwrap_or_default(), fork.encrypted)
+    } else if source.encrypted {
+        let envelope = serde_json::from_str(&revision.content).map_err(|e| BinError::Internal(e.to_string()))?;
+        (String::new(), Vec::new(), Some(envelope))
+    } else {
+        let files = revision.files
+            .into_iter()
+            .map(|file| NewPasteFile {
+                name: file.name,
+                content: file.content,
+                language: file.language,
+            })
+            .collect();
+        (revision.content, files, None)
+    };
+
+    // Language and filename of the original carry over while both are one text
+    let single_file = single_file_source && files.is_empty() && encrypted.is_none();
+
+    let (mut paste, manage_token) = build_paste(NewPaste {
+        content,
+        password: fork.password.clone().unwrap_or_default(),
+        expiry: fork.expiry.unwrap_or(source.expiry),
+        uses,
+        language: fork.language.or(source.language.clone().filter(|_| single_file)),
+        filename: fork.filename.or(source.filename.clone().filter(|_| single_file)),
+        files,
+        encrypted,
+        forks_consume: fork.forks_consume.unwrap_or(source.forks_consume),
+    })?;
+
+    if fork.password.is_none() {
+        paste.password_hash = source.password_hash.clone();
+    }
+    paste.parent_id = Some(id.clone());
+
+    // Forks end with the original at the latest, which `expiry` alone cannot express
+    if expires_at(&paste) > expires_at(&source) {
+        paste.created_at = source.created_at;
+        paste.expiry = source.expiry;
+    }
+
+    let fork_id = paste.id.clone();
+
+    state.bin.create_paste(paste).await?;
+
+    // Taken once the fork is stored; a concurrent read may have used them up in the meantime
+    if let Err(e) = state.bin.consume_paste(&id, uses).await {
+        state.bin.delete_paste(&fork_id).await.ok();
+        return Err(e);
+    }
+
+    Ok(Json(CreatePasteResponse {
+        status: PasteStatus::Success,
+        id: fork_id,
+        manage_token,
+        message: "Paste forked successfully.".into(),
+    }).into_response())
+}
+
+// Update existing paste (owner only)
+pub async fn update_paste(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(update): Json<UpdatePaste>,
+) -> Result<Json<ManagePasteResponse>, BinError> {
+    let mut paste = state.bin.get_paste(&id).await?;
+
+    // Expiry check 
+    if is_expired(&paste) {
+        return Err(BinError::Expired);
+    }
+
+    // Ownership check
+    if !owner::verify_token(&paste.manage_token_hash, &headers) {
+        return Err(BinError::Forbidden);
+    }
+
+    let text_update = update.content.is_some() || update.files.is_some() || update.language.is_some() || update.filename.is_some();
+
+    // Apply changes; new content, files or envelopes become a new revision
+    let revision = if paste.encrypted {
+        // The server cannot edit what it cannot read, the client sends a new envelope instead
+        if text_update {
+            return Err(BinError::InvalidInput("Encrypted pastes are changed by sending a new envelope".into()));
+        }
+
+        match update.encrypted {
+            Some(envelope) => Some((envelope_content(&envelope)?, Vec::new())),
+            None => None,
+        }
+    } else {
+        if update.encrypted.is_some() {
+            return Err(BinError::InvalidInput("Only encrypted pastes take an envelope".into()));
+        }
+
+        if text_update {
+            text_only(&paste)?;
+        }
+
+        match (update.content, update.files) {
+            (Some(_), Some(_)) => {
+                return Err(BinError::InvalidInput("Send either content or files, not both".into()));
+            }
+            (Some(content), None) => Some((sanitize_content(&content)?, Vec::new())),
+            (None, Some(files)) if files.is_empty() => {
+                return Err(BinError::InvalidInput("Paste needs at least one file".into()));
+            }
+            (None, Some(files)) => Some((String::new(), sanitize_files(files)?)),
+            (None, None) => None,
+        }
+    }.filter(|(content, files)| *content != paste.content || *files != paste.files);
+
+    if revision.is_some() && paste.revision >= MAX_REVISIONS {
+        return Err(BinError::InvalidInput(format!("Paste cannot have more than {} revisions", MAX_REVISIONS)));
+    }
+
+    // Language and filename describe single-file pastes, files carry their own
+    let multi_file = revision.as_ref().map_or(!paste.files.is_empty(), |(_, files)| !files.is_empty());
+
+    if multi_file {
+        if update.language.is_some() || update.filename.is_some() {
+            return Err(BinError::InvalidInput("Set language and name per file for multi-file pastes".into()));
+        }
+        paste.language = None;
+        paste.filename = None;
+    }
+
+    if let Some(filename) = update.filename {
+        paste.filename = sanitize_filename(Some(&filename))?;
+    }
+
+    // New text is highlighted by its guessed language, like on create, unless one is given
+    match update.language {
+        Some(language) => {
+            paste.language = language::sanitize_language(&language).map_err(BinError::InvalidInput)?;
+        }
+        None if !paste.encrypted && !multi_file => {
+            if let Some((content, _)) = &revision {
+                paste.language = language::detect(paste.filename.as_deref(), content);
+            }
+        }
+        None => {}
+    }
+
+    if let Some(expiry) = update.expiry {
+        paste.expiry = validate_expiry(expiry)?;
+    }
+
+    let uses = update.uses.map(validate_uses).transpose()?;
+
+    if let Some(password) = update.password {
+        paste.password_hash = hash_password(&password)?; // empty password removes protection
+    }
+
+    if let Some(forks_consume) = update.forks_consume {
+        paste.forks_consume = forks_consume;
+    }
+
+    state.bin.update_paste(paste, uses, revision).await?;
+
+    Ok(Json(ManagePasteResponse {
+        status: PasteStatus::Success,
+        id,
+        message: "Paste updated successfully.".into(),
+    }))
+}
+
+// Delete existing paste (owner only)
+pub async fn delete_paste(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ManagePasteResponse>, BinError> {
+    let paste = state.bin.get_paste(&id).await?;
+
+    // Ownership check
+    if !owner::verify_token(&paste.manage_token_hash, &headers) {
+        return Err(BinError::Forbidden);
+    }
+
+    state.bin.delete_paste(&id).await?;
+
+    if paste.attachment.is_some() {
+        state.attachments.remove(&id).await?;
+    }
+
+    Ok(Json(ManagePasteResponse {
+        status: PasteStatus::Success,
+        id,
+        message: "Paste deleted successfully.".into(),
+    }))
+}
+
+/*
+ * --- Maintenance ---
+ */
+
+// Removes attachment files whose paste expired or is gone; run by the cleanup task
+pub async fn cleanup_attachments(state: &AppState) -> Result<(), BinError> {
+    for id in state.attachments.list().await? {
+        let orphaned = match state.bin.get_paste(&id).await {
+            Ok(paste) if is_expired(&paste) => {
+                state.bin.delete_paste(&id).await.ok();
+                true
+            }
+            Ok(_) => false,
+            Err(BinError::NotFound) => true,
+            Err(e) => return Err(e),
+        };
+
+        if orphaned {
+            state.attachments.remove(&id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use serde_json::{json, Value};
+
+    use crate::owner::MANAGE_TOKEN_HEADER;
+    use crate::testing::{basic_auth, TestApp};
+
+    async fn create_paste(app: &TestApp, paste: Value) -> (String, String) {
+        app.create("/api/bin", paste).await
+    }
+
+    async fn update(app: &TestApp, id: &str, token: &str, update: Value) -> (StatusCode, Value) {
+        let response = app
+            .send_json(Method::PATCH, &format!("/api/bin/{}", id), &[(MANAGE_TOKEN_HEADER, token)], update)
+            .await;
+        (response.status, response.json())
+    }
+
+    // Content of the paste, or of one revision with a "?rev=N" query; counts as a use
+    async fn read(app: &TestApp, id: &str, query: &str) -> Value {
+        let response = app.request(Method::POST, &format!("/api/bin/{}/reveal{}", id, query), &[], Body::empty()).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        response.json()["data"].clone()
+    }
+
+    async fn info(app: &TestApp, id: &str) -> Value {
+        let response = app.get(&format!("/api/bin/{}", id), &[]).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        response.json()["data"].clone()
+    }
+
+    #[tokio::test]
+    async fn manage_token_is_required_to_update_and_delete() {
+        let app = TestApp::new().await;
+        let (id, token) = create_paste(&app, json!({ "content": "hello", "expiry": 1, "uses": 5 })).await;
+
+        assert_eq!(update(&app, &id, "wrong", json!({ "expiry": 2 })).await.0, StatusCode::FORBIDDEN);
+        assert_eq!(update(&app, &id, &token, json!({ "expiry": 2 })).await.0, StatusCode::OK);
+
+        let uri = format!("/api/bin/{}", id);
+        let response = app.request(Method::DELETE, &uri, &[], Body::empty()).await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+        let response = app.request(Method::DELETE, &uri, &[(MANAGE_TOKEN_HEADER, &token)], Body::empty()).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(app.get(&uri, &[]).await.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn owner_updates_leave_remaining_uses_alone() {
+        let app = TestApp::new().await;
+        let (id, token) = create_paste(&app, json!({ "content": "hello", "expiry": 1, "uses": 3 })).await;
+
+        read(&app, &id, "").await;
+        assert_eq!(update(&app, &id, &token, json!({ "expiry": 2 })).await.0, StatusCode::OK);
+        assert_eq!(info(&app, &id).await["uses"], 2);
+
+        assert_eq!(update(&app, &id, &token, json!({ "uses": 7 })).await.0, StatusCode::OK);
+        assert_eq!(info(&app, &id).await["uses"], 7);
+    }
+
+    #[tokio::test]
+    async fn edits_become_revisions() {
+        let app = TestApp::new().await;
+        let (id, token) = create_paste(&app, json!({ "content": "one\ntwo", "expiry": 1, "uses": 10 })).await;
+
+        assert_eq!(update(&app, &id, &token, json!({ "content": "one\nthree" })).await.0, StatusCode::OK);
+        assert_eq!(update(&app, &id, &token, json!({ "content": "one\nthree" })).await.0, StatusCode::OK);
+
+        let data = info(&app, &id).await;
+        assert_eq!(data["revision"], 2);
+        assert_eq!(data["revisions"].as_array().unwrap().len(), 2);
+
+        assert_eq!(read(&app, &id, "").await["content"], "one\nthree");
+        assert_eq!(read(&app, &id, "?rev=1").await["content"], "one\ntwo");
+
+        let missing = app.request(Method::POST, &format!("/api/bin/{}/reveal?rev=3", id), &[], Body::empty()).await;
+        assert_eq!(missing.status, StatusCode::NOT_FOUND);
+        assert_eq!(missing.json()["message"], "Revision not found");
+    }
+
+    #[tokio::test]
+    async fn revision_queries_pick_the_revision_or_are_refused() {
+        let app = TestApp::new().await;
+        let (id, token) = create_paste(&app, json!({ "content": "one", "expiry": 1, "uses": 10, "forks_consume": true })).await;
+        update(&app, &id, &token, json!({ "content": "two" })).await;
+
+        let response = app.get(&format!("/api/bin/{}?rev=1", id), &[]).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["message"], format!("Revisions are read with POST /api/bin/{}/reveal?rev=N", id));
+
+        assert_eq!(read(&app, &id, "?rev=1").await["content"], "one");
+        assert_eq!(app.get(&format!("/bin/{}/raw?rev=1", id), &[]).await.text(), "one");
+        assert_eq!(app.get(&format!("/bin/{}/raw", id), &[]).await.text(), "two");
+
+        let response = app.get(&format!("/api/bin/{}/diff?from=2&to=1", id), &[]).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        assert_eq!((response.json()["data"]["from"].clone(), response.json()["data"]["to"].clone()), (json!(2), json!(1)));
+
+        let (fork, _) = app.create(&format!("/api/bin/{}/fork?rev=1", id), json!({})).await;
+        assert_eq!(read(&app, &fork, "").await["content"], "one");
+
+        let missing = app.get(&format!("/bin/{}/raw?rev=3", id), &[]).await;
+        assert_eq!(missing.status, StatusCode::NOT_FOUND);
+        let missing = app.request(Method::POST, &format!("/api/bin/{}/fork?rev=3", id), &[], Body::empty()).await;
+        assert_eq!(missing.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn diff_compares_revisions() {
+        let app = TestApp::new().await;
+        let (id, token) = create_paste(&app, json!({ "content": "one\ntwo\n", "expiry": 1, "uses": 10 })).await;
+        update(&app, &id, &token, json!({ "content": "one\nthree\n" })).await;
+
+        let response = app.get(&format!("/api/bin/{}/diff", id), &[]).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+
+        let data = &response.json()["data"];
+        assert_eq!((data["from"].clone(), data["to"].clone()), (json!(1), json!(2)));
+
+        let diff = data["diff"].as_str().unwrap();
+        assert!(diff.contains("--- revision 1\n+++ revision 2\n"), "{}", diff);
+        assert!(diff.contains("\n one\n-two\n"), "{}", diff);
+        assert!(diff.contains("\n+three\n"), "{}", diff);
+
+        let response = app.get(&format!("/api/bin/{}/diff?from=2&to=5", id), &[]).await;
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn raw_pastes_round_trip_as_plain_text() {
+        let app = TestApp::new().await;
+
+        let response = app
+            .request(Method::POST, "/api/bin/raw?expiry=1&uses=2", &[("Content-Type", "text/plain")], Body::from("echo hi\n"))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+
+        let text = response.text();
+        let id = text.lines().find_map(|line| line.strip_prefix("id=")).expect("id line");
+        assert!(text.contains(&format!("raw=/bin/{}/raw", id)));
+
+        let raw = app.get(&format!("/bin/{}/raw", id), &[]).await;
+        assert_eq!(raw.status, StatusCode::OK);
+        assert_eq!(raw.header("content-type"), Some("text/plain; charset=utf-8"));
+        assert_eq!(raw.text(), "echo hi");
+    }
+
+    #[tokio::test]
+    async fn raw_pastes_need_settings_and_plain_bodies() {
+        let app = TestApp::new().await;
+
+        let response = app
+            .request(Method::POST, "/api/bin/raw?uses=2", &[("Content-Type", "text/plain")], Body::from("text"))
+            .await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["message"], "Missing expiry (query parameter or X-Paste-Expiry header)");
+
+        let headers = [("Content-Type", "text/plain"), ("X-Paste-Expiry", "1"), ("X-Paste-Uses", "1")];
+        let response = app.request(Method::POST, "/api/bin/raw", &headers, Body::from("text")).await;
+        assert_eq!(response.status, StatusCode::OK);
+
+        let response = app
+            .request(Method::POST, "/api/bin/raw?expiry=1&uses=1", &[("Content-Type", "image/png")], Body::from("text"))
+            .await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn raw_reads_of_protected_pastes_ask_for_basic_auth() {
+        let app = TestApp::new().await;
+        let (id, _) = create_paste(&app, json!({ "content": "secret", "password": "pw", "expiry": 1, "uses": 5 })).await;
+        let uri = format!("/bin/{}/raw", id);
+
+        let response = app.get(&uri, &[]).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert!(response.header("www-authenticate").is_some_and(|value| value.starts_with("Basic")));
+
+        let response = app.get(&uri, &[("Authorization", &basic_auth("pw"))]).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.text(), "secret");
+    }
+
+    #[tokio::test]
+    async fn language_is_detected_unless_given() {
+        let app = TestApp::new().await;
+
+        let (id, _) = create_paste(&app, json!({ "content": "print(1)", "filename": "job.py", "expiry": 1, "uses": 5 })).await;
+        let data = info(&app, &id).await;
+        assert_eq!((data["language"].clone(), data["filename"].clone()), (json!("python"), json!("job.py")));
+
+        let (id, _) = create_paste(&app, json!({ "content": "{\"a\": 1}", "expiry": 1, "uses": 5 })).await;
+        assert_eq!(info(&app, &id).await["language"], "json");
+        assert_eq!(app.get(&format!("/bin/{}/raw", id), &[]).await.header("content-type"), Some("application/json; charset=utf-8"));
+
+        let (id, _) = create_paste(&app, json!({ "content": "{}", "language": "Text", "expiry": 1, "uses": 5 })).await;
+        assert_eq!(info(&app, &id).await["language"], "text");
+
+        let response = app.post_json("/api/bin", json!({ "content": "x", "filename": "../etc/passwd", "expiry": 1, "uses": 5 })).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn edited_content_is_detected_again() {
+        let app = TestApp::new().await;
+        let (id, token) = create_paste(&app, json!({ "content": "{\"a\": 1}", "expiry": 1, "uses": 5 })).await;
+
+        update(&app, &id, &token, json!({ "content": "#!/bin/sh\necho hi" })).await;
+        assert_eq!(info(&app, &id).await["language"], "shell");
+    }
+
+    #[tokio::test]
+    async fn multi_file_pastes_serve_each_file() {
+        let app = TestApp::new().await;
+        let (id, _) = create_paste(&app, json!({
+            "files": [
+                { "name": "main.rs", "content": "fn main() {}" },
+                { "name": "notes.md", "content": "# Notes" },
+            ],
+            "expiry": 1,
+            "uses": 10,
+        })).await;
+
+        assert_eq!(info(&app, &id).await["files"], json!(["main.rs", "notes.md"]));
+
+        let data = read(&app, &id, "").await;
+        assert_eq!(data["content"], Value::Null);
+        assert_eq!(data["files"][0]["language"], "rust");
+
+        let file = app.get(&format!("/bin/{}/raw/notes.md", id), &[]).await;
+        assert_eq!((file.status, file.text()), (StatusCode::OK, "# Notes".to_string()));
+
+        let all = app.get(&format!("/bin/{}/raw", id), &[]).await;
+        assert_eq!(all.status, StatusCode::BAD_REQUEST);
+        assert_eq!(all.json()["message"], "This paste has several files, request one of: main.rs, notes.md");
+
+        assert_eq!(app.get(&format!("/bin/{}/raw/other.txt", id), &[]).await.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn multi_file_names_are_unique() {
+        let app = TestApp::new().await;
+        let response = app.post_json("/api/bin", json!({
+            "files": [{ "name": "a.txt", "content": "1" }, { "name": "A.TXT", "content": "2" }],
+            "expiry": 1,
+            "uses": 1,
+        })).await;
+
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["message"], "Duplicate file name: A.TXT");
+    }
+
+    #[tokio::test]
+    async fn attachments_download_until_their_uses_run_out() {
+        let app = TestApp::new().await;
+        let bytes: Vec<u8> = (0..=255).collect();
+
+        let response = app
+            .request(
+                Method::PUT,
+                "/api/bin/upload?expiry=1&uses=1&filename=data.bin",
+                &[("Content-Type", "application/octet-stream")],
+                Body::from(bytes.clone()),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        let id = response.json()["id"].as_str().unwrap().to_string();
+
+        let attachment = &info(&app, &id).await["attachment"];
+        assert_eq!((attachment["filename"].clone(), attachment["size"].clone()), (json!("data.bin"), json!(256)));
+        assert_eq!(app.attachment_files(), vec![id.clone()]);
+
+        let download = app.get(&format!("/api/bin/{}/download", id), &[]).await;
+        assert_eq!(download.status, StatusCode::OK);
+        assert_eq!(download.body.to_vec(), bytes);
+        assert!(download.header("content-disposition").is_some_and(|value| value.starts_with("attachment;")));
+
+        assert_eq!(app.get(&format!("/api/bin/{}/download", id), &[]).await.status, StatusCode::NOT_FOUND);
+        assert!(app.attachment_files().is_empty());
+    }
+
+    #[tokio::test]
+    async fn multipart_uploads_take_settings_after_the_file() {
+        let app = TestApp::new().await;
+        let body = "--x\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"photo.jpg\"\r\n\
+            Content-Type: image/jpeg\r\n\r\n\
+            JPEGDATA\r\n\
+            --x\r\nContent-Disposition: form-data; name=\"expiry\"\r\n\r\n2\r\n\
+            --x\r\nContent-Disposition: form-data; name=\"uses\"\r\n\r\n3\r\n\
+            --x--\r\n";
+
+        let response = app
+            .request(Method::POST, "/api/bin/upload", &[("Content-Type", "multipart/form-data; boundary=x")], Body::from(body))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        let id = response.json()["id"].as_str().unwrap().to_string();
+
+        let data = info(&app, &id).await;
+        assert_eq!(data["uses"], 3);
+        assert_eq!(data["attachment"]["content_type"], "image/jpeg");
+
+        let download = app.get(&format!("/api/bin/{}/download", id), &[]).await;
+        assert_eq!(download.text(), "JPEGDATA");
+    }
+
+    #[tokio::test]
+    async fn refused_uploads_leave_nothing_on_disk() {
+        let app = TestApp::new().await;
+        let upload = |uri: &'static str| {
+            app.request(Method::PUT, uri, &[("Content-Type", "application/octet-stream")], Body::from("bytes"))
+        };
+
+        let response = upload("/api/bin/upload?expiry=0&uses=1").await;
+        assert_eq!(response.json()["message"], "Invalid expiry value");
+        let response = upload("/api/bin/upload?expiry=1").await;
+        assert_eq!(response.json()["message"], "Missing uses (query parameter or X-Paste-Uses header)");
+        let response = upload("/api/bin/upload?expiry=1&uses=1&filename=a/b").await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+        let body = "--x\r\nContent-Disposition: form-data; name=\"uses\"\r\n\r\n0\r\n\
+            --x\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\r\nbytes\r\n--x--\r\n";
+        let response = app
+            .request(Method::POST, "/api/bin/upload?expiry=1", &[("Content-Type", "multipart/form-data; boundary=x")], Body::from(body))
+            .await;
+        assert_eq!(response.json()["message"], "Invalid uses value");
+
+        assert!(app.attachment_files().is_empty());
+    }
+
+    fn envelope() -> Value {
+        json!({
+            "version": 1,
+            "cipher": "xchacha20-poly1305",
+            "nonce": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+            "ciphertext": "c2VjcmV0IGJ5dGVzIGFuZCBhIHRhZyBvZiAxNiBieXRlcw==",
+        })
+    }
+
+    #[tokio::test]
+    async fn encrypted_pastes_are_stored_as_sent() {
+        let app = TestApp::new().await;
+        let (id, _) = create_paste(&app, json!({ "encrypted": envelope(), "expiry": 1, "uses": 5 })).await;
+
+        assert_eq!(info(&app, &id).await["encrypted"], true);
+
+        let data = read(&app, &id, "").await;
+        assert_eq!(data["encrypted"], envelope());
+        assert_eq!(data["content"], Value::Null);
+
+        let raw = app.get(&format!("/bin/{}/raw", id), &[]).await;
+        assert_eq!(raw.status, StatusCode::BAD_REQUEST);
+        assert_eq!(raw.json()["message"], "This paste is encrypted and can only be read with its key");
+    }
+
+    #[tokio::test]
+    async fn encrypted_pastes_keep_everything_in_the_envelope() {
+        let app = TestApp::new().await;
+
+        let response = app.post_json("/api/bin", json!({ "encrypted": envelope(), "filename": "a.txt", "expiry": 1, "uses": 5 })).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+        let mut envelope = envelope();
+        envelope["cipher"] = json!("rot13");
+        let response = app.post_json("/api/bin", json!({ "encrypted": envelope, "expiry": 1, "uses": 5 })).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["message"], "Unsupported cipher, use one of: aes-256-gcm, xchacha20-poly1305");
+    }
+
+    #[tokio::test]
+    async fn forks_copy_the_content_and_link_back() {
+        let app = TestApp::new().await;
+        let (id, token) = create_paste(&app, json!({ "content": "v1", "filename": "a.txt", "expiry": 1, "uses": 10, "forks_consume": true })).await;
+        update(&app, &id, &token, json!({ "content": "v2" })).await;
+
+        let (fork, _) = app.create(&format!("/api/bin/{}/fork?rev=1", id), json!({})).await;
+        assert_eq!(info(&app, &id).await["forks"], json!([fork]));
+
+        let data = read(&app, &fork, "").await;
+        assert_eq!((data["content"].clone(), data["filename"].clone()), (json!("v1"), json!("a.txt")));
+        assert_eq!(data["parent_id"], id);
+
+        let (changed, _) = app.create(&format!("/api/bin/{}/fork", id), json!({ "content": "v3" })).await;
+        assert_eq!(read(&app, &changed, "").await["content"], "v3");
+    }
+
+    #[tokio::test]
+    async fn forks_keep_the_password_unless_replaced() {
+        let app = TestApp::new().await;
+        let (id, _) = create_paste(&app, json!({ "content": "secret", "password": "pw", "expiry": 1, "uses": 10, "forks_consume": true })).await;
+        let uri = format!("/api/bin/{}/fork", id);
+
+        let response = app.post_json(&uri, json!({})).await;
+        assert_eq!(response.json()["status"], "protected");
+
+        let auth = basic_auth("pw");
+        let response = app.send_json(Method::POST, &uri, &[("Authorization", &auth)], json!({})).await;
+        let fork = response.json()["id"].as_str().unwrap().to_string();
+        assert_eq!(app.get(&format!("/api/bin/{}", fork), &[]).await.json()["status"], "protected");
+
+        let response = app.send_json(Method::POST, &uri, &[("Authorization", &auth)], json!({ "password": "" })).await;
+        let fork = response.json()["id"].as_str().unwrap().to_string();
+        assert_eq!(app.get(&format!("/api/bin/{}", fork), &[]).await.json()["status"], "success");
+    }
+
+    #[tokio::test]
+    async fn forks_take_their_uses_from_the_original() {
+        let app = TestApp::new().await;
+        let (closed, _) = create_paste(&app, json!({ "content": "mine", "expiry": 1, "uses": 3 })).await;
+        let response = app.post_json(&format!("/api/bin/{}/fork", closed), json!({})).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(info(&app, &closed).await["uses"], 3);
+
+        let (id, _) = create_paste(&app, json!({ "content": "twice", "expiry": 1, "uses": 3, "forks_consume": true })).await;
+        let uri = format!("/api/bin/{}/fork", id);
+        let (fork, _) = app.create(&uri, json!({ "uses": 2 })).await;
+        assert_eq!(info(&app, &id).await["uses"], 1);
+        assert_eq!(app.post_json(&uri, json!({ "uses": 2 })).await.status, StatusCode::BAD_REQUEST);
+
+        // Three reads in all, however they are spread over the original and its forks
+        read(&app, &id, "").await;
+        read(&app, &fork, "").await;
+        read(&app, &fork, "").await;
+
+        for id in [&id, &fork] {
+            assert_eq!(app.get(&format!("/api/bin/{}", id), &[]).await.status, StatusCode::NOT_FOUND);
         }
+        assert_eq!(app.post_json(&uri, json!({})).await.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn fork_bodies_are_optional_but_must_be_json() {
+        let app = TestApp::new().await;
+        let (id, _) = create_paste(&app, json!({ "content": "v1", "expiry": 1, "uses": 5, "forks_consume": true })).await;
+        let uri = format!("/api/bin/{}/fork", id);
+
+        let response = app.request(Method::POST, &uri, &[], Body::empty()).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+
+        for (content_type, body) in [("application/json", "{\"uses\": "), ("application/json", "42"), ("text/plain", "{}")] {
+            let response = app.request(Method::POST, &uri, &[("Content-Type", content_type)], Body::from(body)).await;
+            assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", body);
+        }
+
+        assert_eq!(info(&app, &id).await["uses"], 4);
+    }
+
+    #[tokio::test]
+    async fn info_does_not_use_up_a_read() {
+        let app = TestApp::new().await;
+        let (id, _) = create_paste(&app, json!({ "content": "once", "expiry": 1, "uses": 1 })).await;
+
+        for _ in 0..3 {
+            let data = info(&app, &id).await;
+            assert_eq!(data["uses"], 1);
+            assert!(data.get("content").is_none());
+        }
+
+        assert_eq!(read(&app, &id, "").await["content"], "once");
+        assert_eq!(app.get(&format!("/api/bin/{}", id), &[]).await.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn link_previews_never_reveal() {
+        let app = TestApp::new().await;
+        let (id, _) = create_paste(&app, json!({ "content": "once", "expiry": 1, "uses": 1 })).await;
+        let preview = [("User-Agent", "Slackbot-LinkExpanding 1.0 (+https://api.slack.com/robots)")];
+
+        let response = app.request(Method::POST, &format!("/api/bin/{}/reveal", id), &preview, Body::empty()).await;
+        assert!(response.json()["data"].get("content").is_none());
+        let response = app.get(&format!("/bin/{}/raw", id), &preview).await;
+        assert_eq!(response.json()["data"]["uses"], 1);
+
+        assert_eq!(read(&app, &id, "").await["content"], "once");
     }
 }
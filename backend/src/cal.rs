@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{StatusCode, HeaderMap},
     extract::{Path, State},
     response::{Redirect, IntoResponse, Response, Html},
     Json
@@ -20,6 +20,8 @@ use chrono::{NaiveDate, NaiveTime, TimeZone, DateTime, Utc};
 use chrono_tz::Tz;
 
 use crate::state::AppState;
+use crate::owner;
+use crate::crawler;
 
 /*
  * --- Limits ---
@@ -43,6 +45,7 @@ pub struct Event {
     pub uses: u32,
     pub created_at: SystemTime,
     pub created_utc: DateTime<Utc>,
+    pub manage_token_hash: Option<String>,
 }
 
 
@@ -72,8 +75,31 @@ pub struct EventContent {
     pub description: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub struct UpdateEvent {
+    pub title: Option<String>,
+    pub location: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub timezone: Option<String>,
+    pub description: Option<String>,
+    pub password: Option<String>,
+    pub expiry: Option<u32>,
+    pub uses: Option<u32>,
+}
+
 #[derive(Serialize)]
 pub struct CreateEventResponse {
+    pub status: EventStatus,
+    pub id: String,
+    pub manage_token: String,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct ManageEventResponse {
     pub status: EventStatus,
     pub id: String,
     pub message: String,
@@ -86,6 +112,21 @@ pub struct EventData {
     pub ics: Option<String>,
 }
 
+// What is known about an event before it is revealed; reading this never counts as a use
+#[derive(Serialize)]
+pub struct EventInfo {
+    pub id: String,
+    pub uses: u32, // left, revealing takes one
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct EventInfoResponse {
+    pub status: EventStatus,
+    pub data: Option<EventInfo>,
+    pub message: String,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "lowercase")] 
 pub enum EventStatus {
@@ -111,6 +152,7 @@ pub enum CalError {
     InvalidDateTime,
     InvalidDateRange,
     PasswordHashError(String),
+    Forbidden,
     NotFound,
     Expired,
 }
@@ -121,7 +163,20 @@ pub enum CalError {
 
 impl IntoResponse for CalError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
+        let (status, message) = self.status_and_message();
+
+        let body = Json(json!({
+            "status": "error",
+            "message": message,
+        }));
+
+        (status, body).into_response()
+    }
+}
+
+impl CalError {
+    pub fn status_and_message(self) -> (StatusCode, String) {
+        match self {
             CalError::Internal(e) =>
                 (StatusCode::INTERNAL_SERVER_ERROR, e),
             CalError::InvalidInput(e) =>
@@ -138,18 +193,13 @@ impl IntoResponse for CalError {
                 (StatusCode::BAD_REQUEST, "End date must be after start date".into()),
             CalError::PasswordHashError(e) => 
                 (StatusCode::INTERNAL_SERVER_ERROR, e),
+            CalError::Forbidden => 
+                (StatusCode::FORBIDDEN, "Invalid or missing management token".into()),
             CalError::NotFound => 
                 (StatusCode::NOT_FOUND, "Event not found".into()),
             CalError::Expired => 
                 (StatusCode::GONE, "Event expired".into()),
-        };
-
-        let body = Json(json!({
-            "status": "error",
-            "message": message,
-        }));
-
-        (status, body).into_response()
+        }
     }
 }
 
@@ -202,7 +252,7 @@ fn verify_password(event: &Event, auth: &Option<TypedHeader<Authorization<Basic>
 
             if parsed_hash
                 .ok()
-                .map_or(false, |ph| argon2.verify_password(provided_pw.as_bytes(), &ph).is_ok())
+                .is_some_and(|ph| argon2.verify_password(provided_pw.as_bytes(), &ph).is_ok())
             {
                 Ok(())
             } else {
@@ -241,47 +291,25 @@ fn protected_response(message: &str) -> EventResponse {
     }
 }
 
-fn is_expired(event: &Event) -> bool {
-    if let Ok(elapsed) = event.created_at.elapsed() {
-        elapsed > Duration::from_secs(event.expiry as u64 * 3600)
-    } else {
-        true // treat weird timestamps as expired
+fn validate_expiry(expiry: u32) -> Result<u32, CalError> {
+    if expiry == 0 || expiry > 9999 {
+        return Err(CalError::InvalidInput("Invalid expiry value".into()));
     }
-}
-
-/*
- * --- Handlers ---
- */
 
-// Redirect /cal to /cal.html
-pub async fn cal_html() -> impl IntoResponse {
-    Redirect::to("/cal.html")
-}
-
-pub async fn serve_cal_html() -> impl IntoResponse {
-    let html = fs::read_to_string("static/cal.html")
-        .expect("cal.html not found");
-    Html(html)
+    Ok(expiry)
 }
 
-// Create new event 
-pub async fn create_event(
-    State(state): State<AppState>,
-    Json(new_event): Json<NewEvent>,
-) -> Result<Json<CreateEventResponse>, CalError>{
-    let id = Uuid::new_v4().to_string();
-
-    // Sanitize and validate settings values 
-    if new_event.expiry == 0 || new_event.expiry > 9999 {
-        return Err(CalError::InvalidInput("Invalid expiry value".into()));
-    }
-    
-    if new_event.uses == 0 || new_event.uses > 9999 {
+fn validate_uses(uses: u32) -> Result<u32, CalError> {
+    if uses == 0 || uses > 9999 {
         return Err(CalError::InvalidInput("Invalid uses value".into()));
     }
 
-    // Sanitize and validate title, location and description
-    let title = new_event.title.trim().to_string();
+    Ok(uses)
+}
+
+fn sanitize_title(raw: &str) -> Result<String, CalError> {
+    let title = raw.trim().to_string();
+
     if title.is_empty() {
         return Err(CalError::InvalidInput("Title cannot be empty".into()));
     }
@@ -290,35 +318,48 @@ pub async fn create_event(
         return Err(CalError::InvalidInput(format!("Title too long (max {} chars)", MAX_TITLE_LEN)));
     }
 
-    let location = new_event.location
-    .unwrap_or_default()
-    .trim()
-    .to_string();
+    Ok(title)
+}
+
+pub fn sanitize_location(raw: &str) -> Result<String, CalError> {
+    let location = raw.trim().to_string();
 
     if location.len() > MAX_LOCATION_LEN {
         return Err(CalError::InvalidInput(format!("Location too long (max {} chars)", MAX_LOCATION_LEN)));
     }
 
-    let description = new_event.description
-    .unwrap_or_default()
-    .trim()
-    .to_string();
+    Ok(location)
+}
+
+pub fn sanitize_description(raw: &str) -> Result<String, CalError> {
+    let description = raw.trim().to_string();
 
     if description.len() > MAX_DESCRIPTION_LEN {
         return Err(CalError::InvalidInput(format!("Description too long (max {} chars)", MAX_DESCRIPTION_LEN)));
     }
 
+    Ok(description)
+}
+
+// Parses local dates and times in the given timezone and returns the range in UTC
+pub fn parse_event_range(
+    from: &str,
+    to: &str,
+    start: &str,
+    end: &str,
+    timezone: &str,
+) -> Result<(DateTime<Utc>, DateTime<Utc>, String), CalError> {
     // Sanitize and validate dates and times
-    let date_from = NaiveDate::parse_from_str(&new_event.from, "%Y-%m-%d")
+    let date_from = NaiveDate::parse_from_str(from, "%Y-%m-%d")
     .map_err(|_| CalError::InvalidDate)?;
 
-    let date_to = NaiveDate::parse_from_str(&new_event.to, "%Y-%m-%d")
+    let date_to = NaiveDate::parse_from_str(to, "%Y-%m-%d")
     .map_err(|_| CalError::InvalidDate)?;
 
-    let time_start = NaiveTime::parse_from_str(&new_event.start, "%H:%M")
+    let time_start = NaiveTime::parse_from_str(start, "%H:%M")
     .map_err(|_| CalError::InvalidTime)?;
 
-    let time_end = NaiveTime::parse_from_str(&new_event.end, "%H:%M")
+    let time_end = NaiveTime::parse_from_str(end, "%H:%M")
     .map_err(|_| CalError::InvalidTime)?;
 
     // Combine dates + times
@@ -326,8 +367,8 @@ pub async fn create_event(
     let end_naive = date_to.and_time(time_end);
 
     // Sanitize and validate timezone
-    let tz_str = new_event.timezone.trim();
-    let tz: Tz = if tz_str.trim().is_empty() {
+    let tz_str = timezone.trim();
+    let tz: Tz = if tz_str.is_empty() {
         chrono_tz::UTC
     } else {
         tz_str
@@ -352,24 +393,104 @@ pub async fn create_event(
         return Err(CalError::InvalidDateRange);
     }
 
+    Ok((start_dt, end_dt, tz_str.to_string()))
+}
+
+fn hash_password(password: &str) -> Result<Option<String>, CalError> {
     // Validate password length
-    if new_event.password.len() > MAX_PASSWORD_LENGTH {
+    if password.len() > MAX_PASSWORD_LENGTH {
         return Err(CalError::InvalidInput("Password too long".into()));
     }
 
     // Create password hash if a password was set
-    let password_hash = if !new_event.password.is_empty() {
-        let mut rng = OsRng;
-        let salt = SaltString::generate(&mut rng);
-        let argon2 = Argon2::default();
-        let hash = argon2
-            .hash_password(new_event.password.as_bytes(), &salt)
-            .map_err(|e| CalError::PasswordHashError(e.to_string()))?
-            .to_string();
-        Some(hash)
-    } else {
-        None
+    if password.is_empty() {
+        return Ok(None);
+    }
+
+    let mut rng = OsRng;
+    let salt = SaltString::generate(&mut rng);
+    let argon2 = Argon2::default();
+    let hash = argon2
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| CalError::PasswordHashError(e.to_string()))?
+        .to_string();
+
+    Ok(Some(hash))
+}
+
+// Builds a new event together with the owner's management token
+pub fn build_event(
+    content: EventContent,
+    password_hash: Option<String>,
+    expiry: u32,
+    uses: u32,
+) -> Result<(Event, String), CalError> {
+    let expiry = validate_expiry(expiry)?;
+    let uses = validate_uses(uses)?;
+    let (manage_token, manage_token_hash) = owner::generate_token();
+
+    let event = Event {
+        id: Uuid::new_v4().to_string(),
+        content,
+        password_hash,
+        expiry,
+        uses,
+        created_at: SystemTime::now(),
+        created_utc: Utc::now(),
+        manage_token_hash: Some(manage_token_hash),
     };
+
+    Ok((event, manage_token))
+}
+
+fn expires_at(event: &Event) -> DateTime<Utc> {
+    DateTime::<Utc>::from(event.created_at + Duration::from_secs(event.expiry as u64 * 3600))
+}
+
+fn is_expired(event: &Event) -> bool {
+    if let Ok(elapsed) = event.created_at.elapsed() {
+        elapsed > Duration::from_secs(event.expiry as u64 * 3600)
+    } else {
+        true // treat weird timestamps as expired
+    }
+}
+
+/*
+ * --- Handlers ---
+ */
+
+// Redirect /cal to /cal.html
+pub async fn cal_html() -> impl IntoResponse {
+    Redirect::to("/cal.html")
+}
+
+pub async fn serve_cal_html() -> impl IntoResponse {
+    let html = fs::read_to_string("static/cal.html")
+        .expect("cal.html not found");
+    Html(html)
+}
+
+// Create new event 
+pub async fn create_event(
+    State(state): State<AppState>,
+    Json(new_event): Json<NewEvent>,
+) -> Result<Json<CreateEventResponse>, CalError>{
+    // Sanitize and validate title, location and description
+    let title = sanitize_title(&new_event.title)?;
+    let location = sanitize_location(&new_event.location.unwrap_or_default())?;
+    let description = sanitize_description(&new_event.description.unwrap_or_default())?;
+
+    // Sanitize and validate dates, times and timezone
+    let (start_dt, end_dt, tz_str) = parse_event_range(
+        &new_event.from,
+        &new_event.to,
+        &new_event.start,
+        &new_event.end,
+        &new_event.timezone,
+    )?;
+
+    // Create password hash if a password was set
+    let password_hash = hash_password(&new_event.password)?;
     
     // Build content
     let event_content = EventContent {
@@ -377,37 +498,80 @@ pub async fn create_event(
         location: Some(location),
         start: start_dt,
         end: end_dt,
-        timezone: tz_str.to_string(),
+        timezone: tz_str,
         description: Some(description),
     };
 
-    // Create proper event
-    let event = Event {
-        id: id.clone(),
-        content: event_content,
-        password_hash,
-        expiry: new_event.expiry,
-        uses: new_event.uses,
-        created_at: SystemTime::now(),
-        created_utc: Utc::now(),
-    };
+    // Create proper event (validates expiry and uses)
+    let (event, manage_token) = build_event(event_content, password_hash, new_event.expiry, new_event.uses)?;
+    let id = event.id.clone();
 
     state.cal.create_event(event).await?;
 
-    // Return the event id to the frontend
+    // Return the event id and management token to the frontend
     Ok(Json(CreateEventResponse {
         status: EventStatus::Success,
         id,
+        manage_token,
         message: "Event created successfully.".into(),
     }))
 }
 
-// Retrieve existing event
+// Describe an existing event without its details; never counts as a use
 pub async fn get_event(
     Path(id): Path<String>,
     State(state): State<AppState>,
     auth: Option<TypedHeader<Authorization<Basic>>>,
-) -> Result<Json<EventResponse>, CalError> {
+) -> Result<Response, CalError> {
+    info_response(&state, &id, &auth).await
+}
+
+async fn info_response(
+    state: &AppState,
+    id: &str,
+    auth: &Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, CalError> {
+    let event = state.cal.get_event(id).await?;
+
+    // Expiry check 
+    if is_expired(&event) {
+        return Err(CalError::Expired);
+    }
+
+    // Password check 
+    match verify_password(&event, auth) {
+        Ok(()) => {},
+        Err(PasswordError::Missing) => {
+            return Ok(Json(protected_response("This event is password protected.")).into_response());
+        }
+        Err(PasswordError::Incorrect) => {
+            return Ok(Json(protected_response("Incorrect password.")).into_response());
+        }
+    }
+
+    Ok(Json(EventInfoResponse {
+        status: EventStatus::Success,
+        data: Some(EventInfo {
+            id: event.id.clone(),
+            uses: event.uses,
+            expires_at: expires_at(&event),
+        }),
+        message: "Event info retrieved successfully.".into(),
+    }).into_response())
+}
+
+// Retrieve the details of an existing event; counts as a use
+pub async fn reveal_event(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    auth: Option<TypedHeader<Authorization<Basic>>>,
+) -> Result<Response, CalError> {
+    // Link previews only ever get the description
+    if crawler::is_crawler(&headers) {
+        return info_response(&state, &id, &auth).await;
+    }
+
     let preview = state.cal.get_event(&id).await?;
     
     // Expiry check 
@@ -419,13 +583,188 @@ pub async fn get_event(
     match verify_password(&preview, &auth) {
         Ok(()) => {
             let consumed = state.cal.consume_event(&id).await?;
-            Ok(Json(success_response(&consumed)))
+            Ok(Json(success_response(&consumed)).into_response())
         }
         Err(PasswordError::Missing) => {
-            Ok(Json(protected_response("This event is password protected.")))
+            Ok(Json(protected_response("This event is password protected.")).into_response())
         }
         Err(PasswordError::Incorrect) => {
-            Ok(Json(protected_response("Incorrect password.")))
+            Ok(Json(protected_response("Incorrect password.")).into_response())
+        }
+    }
+}
+
+// Update existing event (owner only)
+pub async fn update_event(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(update): Json<UpdateEvent>,
+) -> Result<Json<ManageEventResponse>, CalError> {
+    let mut event = state.cal.get_event(&id).await?;
+
+    // Expiry check 
+    if is_expired(&event) {
+        return Err(CalError::Expired);
+    }
+
+    // Ownership check
+    if !owner::verify_token(&event.manage_token_hash, &headers) {
+        return Err(CalError::Forbidden);
+    }
+
+    // Apply changes
+    if let Some(title) = update.title {
+        event.content.title = sanitize_title(&title)?;
+    }
+
+    if let Some(location) = update.location {
+        event.content.location = Some(sanitize_location(&location)?);
+    }
+
+    if let Some(description) = update.description {
+        event.content.description = Some(sanitize_description(&description)?);
+    }
+
+    // Dates and times can only be changed together
+    match (update.from, update.to, update.start, update.end) {
+        (Some(from), Some(to), Some(start), Some(end)) => {
+            let timezone = update.timezone.unwrap_or_else(|| event.content.timezone.clone());
+            let (start_dt, end_dt, tz_str) = parse_event_range(&from, &to, &start, &end, &timezone)?;
+
+            event.content.start = start_dt;
+            event.content.end = end_dt;
+            event.content.timezone = tz_str;
         }
+        (None, None, None, None) => {
+            if update.timezone.is_some() {
+                return Err(CalError::InvalidInput("Timezone can only be changed together with dates and times".into()));
+            }
+        }
+        _ => {
+            return Err(CalError::InvalidInput("Dates and times must be updated together".into()));
+        }
+    }
+
+    if let Some(expiry) = update.expiry {
+        event.expiry = validate_expiry(expiry)?;
+    }
+
+    let uses = update.uses.map(validate_uses).transpose()?;
+
+    if let Some(password) = update.password {
+        event.password_hash = hash_password(&password)?; // empty password removes protection
+    }
+
+    state.cal.update_event(event, uses).await?;
+
+    Ok(Json(ManageEventResponse {
+        status: EventStatus::Success,
+        id,
+        message: "Event updated successfully.".into(),
+    }))
+}
+
+// Delete existing event (owner only)
+pub async fn delete_event(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ManageEventResponse>, CalError> {
+    let event = state.cal.get_event(&id).await?;
+
+    // Ownership check
+    if !owner::verify_token(&event.manage_token_hash, &headers) {
+        return Err(CalError::Forbidden);
+    }
+
+    state.cal.delete_event(&id).await?;
+
+    Ok(Json(ManageEventResponse {
+        status: EventStatus::Success,
+        id,
+        message: "Event deleted successfully.".into(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Method, StatusCode};
+    use serde_json::{json, Value};
+
+    use crate::owner::MANAGE_TOKEN_HEADER;
+    use crate::testing::TestApp;
+
+    fn new_event(uses: u32) -> Value {
+        json!({
+            "title": "Retro",
+            "from": "2026-11-02",
+            "to": "2026-11-02",
+            "start": "10:00",
+            "end": "11:00",
+            "timezone": "Europe/Berlin",
+            "expiry": 1,
+            "uses": uses,
+        })
+    }
+
+    async fn reveal(app: &TestApp, id: &str, headers: &[(&str, &str)]) -> Value {
+        let response = app.request(Method::POST, &format!("/api/cal/{}/reveal", id), headers, Body::empty()).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+        response.json()["data"].clone()
+    }
+
+    #[tokio::test]
+    async fn manage_token_is_required_to_update_and_delete() {
+        let app = TestApp::new().await;
+        let (id, token) = app.create("/api/cal", new_event(5)).await;
+        let uri = format!("/api/cal/{}", id);
+
+        let response = app.send_json(Method::PATCH, &uri, &[(MANAGE_TOKEN_HEADER, "wrong")], json!({ "title": "Planning" })).await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+        let response = app.send_json(Method::PATCH, &uri, &[(MANAGE_TOKEN_HEADER, &token)], json!({ "title": "Planning" })).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(reveal(&app, &id, &[]).await["content"]["title"], "Planning");
+
+        let response = app.send_json(Method::PATCH, &uri, &[(MANAGE_TOKEN_HEADER, &token)], json!({ "start": "09:00" })).await;
+        assert_eq!(response.json()["message"], "Dates and times must be updated together");
+
+        let response = app.request(Method::DELETE, &uri, &[(MANAGE_TOKEN_HEADER, &token)], Body::empty()).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(app.get(&uri, &[]).await.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn owner_updates_leave_remaining_uses_alone() {
+        let app = TestApp::new().await;
+        let (id, token) = app.create("/api/cal", new_event(3)).await;
+        let uri = format!("/api/cal/{}", id);
+
+        reveal(&app, &id, &[]).await;
+        app.send_json(Method::PATCH, &uri, &[(MANAGE_TOKEN_HEADER, &token)], json!({ "title": "Planning" })).await;
+
+        assert_eq!(app.get(&uri, &[]).await.json()["data"]["uses"], 2);
+    }
+
+    #[tokio::test]
+    async fn only_revealing_uses_up_a_view() {
+        let app = TestApp::new().await;
+        let (id, _) = app.create("/api/cal", new_event(1)).await;
+        let uri = format!("/api/cal/{}", id);
+
+        let data = app.get(&uri, &[]).await.json()["data"].clone();
+        assert_eq!(data["uses"], 1);
+        assert!(data.get("content").is_none());
+
+        let preview = reveal(&app, &id, &[("User-Agent", "Mozilla/5.0 (Windows NT 10.0) SkypeUriPreview Preview/0.5")]).await;
+        assert!(preview.get("content").is_none());
+
+        let data = reveal(&app, &id, &[]).await;
+        assert_eq!(data["content"]["title"], "Retro");
+        assert!(data["ics"].as_str().is_some_and(|ics| ics.contains("SUMMARY:Retro")));
+
+        assert_eq!(app.get(&uri, &[]).await.status, StatusCode::NOT_FOUND);
     }
 }
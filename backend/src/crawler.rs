@@ -0,0 +1,36 @@
+use axum::http::{header, HeaderMap};
+
+/*
+ * --- Configuration ---
+ */
+
+// Lowercase User-Agent fragments of link previews (Slack, Teams, Matrix, Discord, ...) and search engines
+const CRAWLER_AGENTS: &[&str] = &[
+    "bot", // Slackbot, Discordbot, TelegramBot, Twitterbot, Synapse (bot; ...), Googlebot, ...
+    "crawler",
+    "spider",
+    "preview", // SkypeUriPreview (Teams), Microsoft Office Preview
+    "facebookexternalhit",
+    "whatsapp",
+    "embedly",
+    "iframely",
+    "mattermost",
+    "vkshare",
+];
+
+/*
+ * --- Detection ---
+ */
+
+// Crawlers only ever get a description of a secret, so they cannot use it up
+pub fn is_crawler(headers: &HeaderMap) -> bool {
+    let Some(agent) = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_ascii_lowercase)
+    else {
+        return false;
+    };
+
+    CRAWLER_AGENTS.iter().any(|fragment| agent.contains(fragment))
+}
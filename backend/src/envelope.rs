@@ -0,0 +1,126 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+/*
+ * --- Limits ---
+ */
+
+pub const ENVELOPE_VERSION: u8 = 1;
+const AEAD_TAG_LEN: usize = 16;
+
+// Cipher id to nonce length in bytes; both are AEADs with a 16 byte tag
+const CIPHERS: &[(&str, usize)] = &[
+    ("aes-256-gcm", 12),
+    ("xchacha20-poly1305", 24),
+];
+
+/*
+ * --- Encrypted content ---
+ */
+
+// Encrypted in the browser; the key stays in the URL fragment and never reaches the server.
+// Nonce and ciphertext are standard base64, the server only checks their shape.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Envelope {
+    pub version: u8,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/*
+ * --- Validation ---
+ */
+
+pub fn validate(envelope: &Envelope, max_plaintext_len: usize) -> Result<(), String> {
+    if envelope.version != ENVELOPE_VERSION {
+        return Err(format!("Unsupported envelope version (expected {})", ENVELOPE_VERSION));
+    }
+
+    let nonce_len = CIPHERS
+        .iter()
+        .find(|(cipher, _)| *cipher == envelope.cipher)
+        .map(|(_, nonce_len)| *nonce_len)
+        .ok_or_else(|| {
+            let ciphers: Vec<&str> = CIPHERS.iter().map(|(cipher, _)| *cipher).collect();
+            format!("Unsupported cipher, use one of: {}", ciphers.join(", "))
+        })?;
+
+    let nonce = STANDARD
+        .decode(&envelope.nonce)
+        .map_err(|_| "Nonce must be base64".to_string())?;
+
+    if nonce.len() != nonce_len {
+        return Err(format!("Nonce must be {} bytes for {}", nonce_len, envelope.cipher));
+    }
+
+    // Checked on the encoded length first, so oversized input is never decoded
+    if envelope.ciphertext.len() > encoded_len(max_plaintext_len) {
+        return Err(format!("Encrypted content exceeds maximum size of {} bytes", max_plaintext_len));
+    }
+
+    let ciphertext = STANDARD
+        .decode(&envelope.ciphertext)
+        .map_err(|_| "Ciphertext must be base64".to_string())?;
+
+    if ciphertext.len() <= AEAD_TAG_LEN {
+        return Err("Ciphertext is too short to hold any content".into());
+    }
+
+    if ciphertext.len() > max_plaintext_len + AEAD_TAG_LEN {
+        return Err(format!("Encrypted content exceeds maximum size of {} bytes", max_plaintext_len));
+    }
+
+    Ok(())
+}
+
+// Base64 length of the ciphertext of a plaintext this long; request body limits have to allow for it
+pub const fn encoded_len(max_plaintext_len: usize) -> usize {
+    (max_plaintext_len + AEAD_TAG_LEN).div_ceil(3) * 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope(cipher: &str, nonce_len: usize, ciphertext_len: usize) -> Envelope {
+        Envelope {
+            version: ENVELOPE_VERSION,
+            cipher: cipher.into(),
+            nonce: STANDARD.encode(vec![7u8; nonce_len]),
+            ciphertext: STANDARD.encode(vec![9u8; ciphertext_len]),
+        }
+    }
+
+    #[test]
+    fn accepts_both_ciphers() {
+        assert!(validate(&envelope("aes-256-gcm", 12, 17), 100).is_ok());
+        assert!(validate(&envelope("xchacha20-poly1305", 24, 100 + AEAD_TAG_LEN), 100).is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_envelopes() {
+        let mut old = envelope("aes-256-gcm", 12, 32);
+        old.version = 0;
+        assert_eq!(validate(&old, 100).unwrap_err(), "Unsupported envelope version (expected 1)");
+
+        assert!(validate(&envelope("aes-128-cbc", 16, 32), 100).is_err());
+        assert_eq!(validate(&envelope("aes-256-gcm", 24, 32), 100).unwrap_err(), "Nonce must be 12 bytes for aes-256-gcm");
+        assert_eq!(validate(&envelope("aes-256-gcm", 12, AEAD_TAG_LEN), 100).unwrap_err(), "Ciphertext is too short to hold any content");
+
+        let mut garbled = envelope("aes-256-gcm", 12, 32);
+        garbled.ciphertext = "not base64!".into();
+        assert_eq!(validate(&garbled, 100).unwrap_err(), "Ciphertext must be base64");
+    }
+
+    #[test]
+    fn limits_the_plaintext_size() {
+        let too_large = envelope("aes-256-gcm", 12, 101 + AEAD_TAG_LEN);
+        assert_eq!(validate(&too_large, 100).unwrap_err(), "Encrypted content exceeds maximum size of 100 bytes");
+
+        let full = envelope("aes-256-gcm", 12, 100 + AEAD_TAG_LEN);
+        assert!(validate(&full, 100).is_ok());
+        assert!(full.ciphertext.len() <= encoded_len(100));
+    }
+}
@@ -0,0 +1,391 @@
+use std::io::{Cursor, Write};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::ask::{
+    self, Survey, SurveyResults, SurveyType, Tally, CHOICE_SELECTED, MATRIX_MAYBE, MATRIX_NO, MATRIX_YES,
+};
+
+/*
+ * --- Data Types ---
+ */
+
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+    Ods,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Ods => "application/vnd.oasis.opendocument.spreadsheet",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Ods => "ods",
+        }
+    }
+}
+
+pub enum Cell {
+    Empty,
+    Text(String),
+    Number(f64),
+}
+
+// The first row of a sheet is its header
+pub struct Sheet {
+    pub name: &'static str,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+/*
+ * --- Sheets ---
+ */
+
+// One row per ballot, one column per item of every question
+pub fn ballot_sheet(survey: &Survey) -> Sheet {
+    let content = &survey.content;
+    let multi = content.questions.len() > 1;
+    let with_voter = ask::needs_voter(content);
+
+    let mut header = vec![Cell::Text("Ballot".into())];
+    if with_voter {
+        header.push(Cell::Text("Voter".into()));
+    }
+
+    for (q, question) in content.questions.iter().enumerate() {
+        for label in &question.items {
+            header.push(Cell::Text(if multi {
+                format!("{}: {}", question_label(&question.prompt, q), label)
+            } else {
+                label.clone()
+            }));
+        }
+    }
+
+    let mut rows = vec![header];
+
+    for (number, vote) in survey.votes.iter().enumerate() {
+        let mut row = vec![Cell::Number((number + 1) as f64)];
+        if with_voter {
+            row.push(Cell::Text(vote.voter.clone().unwrap_or_default()));
+        }
+
+        for (q, question) in content.questions.iter().enumerate() {
+            let answer = vote.answers.get(&q);
+
+            for index in 0..question.items.len() {
+                let cell = match answer {
+                    None => Cell::Empty,
+                    Some(answer) if question.survey_type == SurveyType::FreeText => answer
+                        .text
+                        .get(&index)
+                        .map_or(Cell::Empty, |text| Cell::Text(text.clone())),
+                    Some(answer) => answer
+                        .vote
+                        .get(&index)
+                        .map_or(Cell::Empty, |&value| vote_cell(question.survey_type, value)),
+                };
+
+                row.push(cell);
+            }
+        }
+
+        rows.push(row);
+    }
+
+    Sheet { name: "Ballots", rows }
+}
+
+// One block per question with the server-side tallies
+pub fn summary_sheet(survey: &Survey, results: &SurveyResults) -> Sheet {
+    let content = &survey.content;
+    let multi = content.questions.len() > 1;
+
+    let mut rows = vec![
+        vec![Cell::Text("Survey".into()), Cell::Text(content.title.clone())],
+        vec![Cell::Text("Ballots".into()), Cell::Number(results.ballots as f64)],
+    ];
+
+    for (question, result) in content.questions.iter().zip(&results.questions) {
+        rows.push(Vec::new());
+
+        if multi {
+            rows.push(vec![
+                Cell::Text(question_label(&question.prompt, result.index)),
+                Cell::Text(format!("{} answered", result.answered)),
+            ]);
+        }
+
+        match &result.tally {
+            Tally::Choice { items } => {
+                rows.push(text_row(&["Item", "Count", "Percentage"]));
+                for item in items {
+                    rows.push(vec![
+                        Cell::Text(item.label.clone()),
+                        Cell::Number(item.count as f64),
+                        Cell::Number(round(item.percentage)),
+                    ]);
+                }
+            }
+            Tally::Approval { items, winners } => {
+                rows.push(text_row(&["Item", "Approvals", "Percentage", "Winner"]));
+                for item in items {
+                    rows.push(vec![
+                        Cell::Text(item.label.clone()),
+                        Cell::Number(item.count as f64),
+                        Cell::Number(round(item.percentage)),
+                        flag(winners.contains(&item.index)),
+                    ]);
+                }
+            }
+            Tally::Rank { items, ranking, .. } => {
+                rows.push(text_row(&["Place", "Item", "Points", "Ranked", "Average position"]));
+                for (place, &index) in ranking.iter().enumerate() {
+                    let item = &items[index];
+                    rows.push(vec![
+                        Cell::Number((place + 1) as f64),
+                        Cell::Text(item.label.clone()),
+                        Cell::Number(item.points as f64),
+                        Cell::Number(item.ranked as f64),
+                        item.average_position.map_or(Cell::Empty, |p| Cell::Number(round(p))),
+                    ]);
+                }
+            }
+            Tally::Matrix { items, .. } => {
+                rows.push(text_row(&["Item", "Yes", "Maybe", "No", "Score"]));
+                for item in items {
+                    rows.push(vec![
+                        Cell::Text(item.label.clone()),
+                        Cell::Number(item.yes as f64),
+                        Cell::Number(item.maybe as f64),
+                        Cell::Number(item.no as f64),
+                        Cell::Number(item.score as f64),
+                    ]);
+                }
+            }
+            Tally::Text { items } => {
+                rows.push(text_row(&["Item", "Answers"]));
+                for item in items {
+                    rows.push(vec![Cell::Text(item.label.clone()), Cell::Number(item.count as f64)]);
+                }
+            }
+            Tally::Rating { items } => {
+                rows.push(text_row(&["Item", "Ratings", "Average", "Median", "Min", "Max"]));
+                for item in items {
+                    rows.push(vec![
+                        Cell::Text(item.label.clone()),
+                        Cell::Number(item.count as f64),
+                        item.average.map_or(Cell::Empty, |a| Cell::Number(round(a))),
+                        item.median.map_or(Cell::Empty, Cell::Number),
+                        Cell::Number(item.range.min as f64),
+                        Cell::Number(item.range.max as f64),
+                    ]);
+                }
+            }
+        }
+    }
+
+    Sheet { name: "Summary", rows }
+}
+
+/*
+ * --- Formats ---
+ */
+
+// Ballots first, then the summary section after an empty line
+pub fn to_csv(sheets: &[Sheet]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+
+    for (position, sheet) in sheets.iter().enumerate() {
+        if position > 0 {
+            out.push(b'\n');
+        }
+
+        for row in &sheet.rows {
+            // A csv writer would quote an empty record as "", so blank lines go straight to the buffer
+            if row.is_empty() {
+                out.push(b'\n');
+                continue;
+            }
+
+            let mut writer = csv::Writer::from_writer(&mut out);
+            writer.write_record(row.iter().map(csv_value)).map_err(|e| e.to_string())?;
+            writer.flush().map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(out)
+}
+
+pub fn to_json(survey: &Survey, ballots: &Sheet, results: &SurveyResults) -> Value {
+    let mut rows = ballots.rows.iter().map(|row| row.iter().map(json_value).collect::<Vec<_>>());
+    let columns = rows.next().unwrap_or_default();
+
+    json!({
+        "id": survey.id,
+        "title": survey.content.title,
+        "ballots": {
+            "columns": columns,
+            "rows": rows.collect::<Vec<_>>(),
+        },
+        "summary": results,
+    })
+}
+
+// Minimal OpenDocument spreadsheet: mimetype, manifest and one table per sheet
+pub fn to_ods(sheets: &[Sheet]) -> Result<Vec<u8>, String> {
+    let mut content = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        r#"<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" "#,
+        r#"xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" "#,
+        r#"xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" office:version="1.2">"#,
+        r#"<office:body><office:spreadsheet>"#,
+    ));
+
+    for sheet in sheets {
+        content.push_str(&format!(r#"<table:table table:name="{}">"#, xml_escape(sheet.name)));
+
+        for row in &sheet.rows {
+            content.push_str("<table:table-row>");
+            if row.is_empty() {
+                content.push_str("<table:table-cell/>");
+            }
+            for cell in row {
+                content.push_str(&ods_cell(cell));
+            }
+            content.push_str("</table:table-row>");
+        }
+
+        content.push_str("</table:table>");
+    }
+
+    content.push_str("</office:spreadsheet></office:body></office:document-content>");
+
+    let manifest = concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        r#"<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">"#,
+        r#"<manifest:file-entry manifest:full-path="/" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/>"#,
+        r#"<manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>"#,
+        r#"</manifest:manifest>"#,
+    );
+
+    // The mimetype entry has to come first and stay uncompressed
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let files = [
+        ("mimetype", ExportFormat::Ods.content_type(), stored),
+        ("META-INF/manifest.xml", manifest, deflated),
+        ("content.xml", content.as_str(), deflated),
+    ];
+
+    for (name, data, options) in files {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(data.as_bytes()).map_err(|e| e.to_string())?;
+    }
+
+    Ok(zip.finish().map_err(|e| e.to_string())?.into_inner())
+}
+
+/*
+ * --- Helper Functions ---
+ */
+
+fn question_label(prompt: &str, index: usize) -> String {
+    if prompt.is_empty() {
+        format!("Question {}", index + 1)
+    } else {
+        prompt.to_string()
+    }
+}
+
+fn vote_cell(survey_type: SurveyType, value: i32) -> Cell {
+    match survey_type {
+        SurveyType::SingleChoice | SurveyType::MultipleChoice | SurveyType::Approval => {
+            flag(value == CHOICE_SELECTED)
+        }
+        SurveyType::RankChoice => Cell::Number((value + 1) as f64), // 1 = first place
+        SurveyType::MatrixChoice | SurveyType::Schedule => match value {
+            MATRIX_YES => Cell::Text("yes".into()),
+            MATRIX_MAYBE => Cell::Text("maybe".into()),
+            MATRIX_NO => Cell::Text("no".into()),
+            _ => Cell::Empty,
+        },
+        SurveyType::RatingScale => Cell::Number(value as f64),
+        SurveyType::FreeText => Cell::Empty,
+    }
+}
+
+fn flag(set: bool) -> Cell {
+    if set {
+        Cell::Text("x".into())
+    } else {
+        Cell::Empty
+    }
+}
+
+fn text_row(labels: &[&str]) -> Vec<Cell> {
+    labels.iter().map(|label| Cell::Text(label.to_string())).collect()
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+// Spreadsheets evaluate cells starting with these characters as formulas
+fn csv_value(cell: &Cell) -> String {
+    match cell {
+        Cell::Empty => String::new(),
+        Cell::Number(n) => n.to_string(),
+        Cell::Text(t) if t.starts_with(['=', '+', '-', '@']) => format!("'{}", t),
+        Cell::Text(t) => t.clone(),
+    }
+}
+
+fn json_value(cell: &Cell) -> Value {
+    match cell {
+        Cell::Empty => Value::Null,
+        Cell::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => json!(*n as i64),
+        Cell::Number(n) => json!(n),
+        Cell::Text(t) => json!(t),
+    }
+}
+
+fn ods_cell(cell: &Cell) -> String {
+    match cell {
+        Cell::Empty => "<table:table-cell/>".into(),
+        Cell::Number(n) => format!(
+            r#"<table:table-cell office:value-type="float" office:value="{0}"><text:p>{0}</text:p></table:table-cell>"#,
+            n
+        ),
+        Cell::Text(t) => format!(
+            r#"<table:table-cell office:value-type="string"><text:p>{}</text:p></table:table-cell>"#,
+            xml_escape(t)
+        ),
+    }
+}
+
+// Control characters other than tab and newlines are not allowed in XML at all
+fn xml_escape(raw: &str) -> String {
+    let cleaned: String = raw
+        .chars()
+        .filter(|&c| c >= ' ' || matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+
+    html_escape::encode_double_quoted_attribute(&cleaned).into_owned()
+}
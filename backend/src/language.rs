@@ -0,0 +1,265 @@
+/*
+ * --- Limits ---
+ */
+
+const MAX_LANGUAGE_LEN: usize = 32;
+const MAX_FILENAME_LEN: usize = 255;
+
+/*
+ * --- Known languages ---
+ */
+
+// File extension to language id, ids follow the usual highlighter names
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("py", "python"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("ts", "typescript"),
+    ("json", "json"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+    ("toml", "toml"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("css", "css"),
+    ("md", "markdown"),
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cpp", "cpp"),
+    ("cc", "cpp"),
+    ("hpp", "cpp"),
+    ("go", "go"),
+    ("java", "java"),
+    ("kt", "kotlin"),
+    ("rb", "ruby"),
+    ("php", "php"),
+    ("pl", "perl"),
+    ("sql", "sql"),
+    ("xml", "xml"),
+    ("diff", "diff"),
+    ("patch", "diff"),
+    ("csv", "csv"),
+    ("ini", "ini"),
+    ("log", "plaintext"),
+    ("txt", "plaintext"),
+];
+
+// Interpreter named by a shebang to language id
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("python", "python"),
+    ("python3", "python"),
+    ("bash", "shell"),
+    ("sh", "shell"),
+    ("zsh", "shell"),
+    ("node", "javascript"),
+    ("ruby", "ruby"),
+    ("perl", "perl"),
+    ("php", "php"),
+];
+
+/*
+ * --- Validation ---
+ */
+
+// Languages are free-form ids so any highlighter can be used, but kept to a safe alphabet
+pub fn sanitize_language(raw: &str) -> Result<Option<String>, String> {
+    let language = raw.trim().to_ascii_lowercase();
+
+    if language.is_empty() {
+        return Ok(None);
+    }
+
+    if language.len() > MAX_LANGUAGE_LEN {
+        return Err(format!("Language too long (max {} chars)", MAX_LANGUAGE_LEN));
+    }
+
+    if !language.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '#' | '-' | '_' | '.')) {
+        return Err("Language may only contain letters, digits and + # - _ .".into());
+    }
+
+    Ok(Some(language))
+}
+
+// A bare file name, it ends up in Content-Disposition headers
+pub fn sanitize_filename(raw: &str) -> Result<Option<String>, String> {
+    let filename = raw.trim();
+
+    if filename.is_empty() {
+        return Ok(None);
+    }
+
+    if filename.len() > MAX_FILENAME_LEN {
+        return Err(format!("Filename too long (max {} chars)", MAX_FILENAME_LEN));
+    }
+
+    if filename.chars().any(|c| c.is_control() || matches!(c, '/' | '\\' | '"'))
+        || filename == "."
+        || filename == ".."
+    {
+        return Err("Filename may not contain paths, quotes or control characters".into());
+    }
+
+    Ok(Some(filename.to_string()))
+}
+
+/*
+ * --- Detection ---
+ */
+
+// File extension first, then the shebang, then a look at the content itself
+pub fn detect(filename: Option<&str>, content: &str) -> Option<String> {
+    filename
+        .and_then(from_extension)
+        .or_else(|| from_shebang(content))
+        .or_else(|| from_content(content))
+        .map(str::to_string)
+}
+
+fn from_extension(filename: &str) -> Option<&'static str> {
+    let lower = filename.to_ascii_lowercase();
+
+    match lower.as_str() {
+        "dockerfile" => return Some("dockerfile"),
+        "makefile" => return Some("makefile"),
+        _ => {}
+    }
+
+    let (_, extension) = lower.rsplit_once('.')?;
+    EXTENSIONS
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, language)| *language)
+}
+
+fn from_shebang(content: &str) -> Option<&'static str> {
+    let line = content.lines().next()?.strip_prefix("#!")?;
+    let mut parts = line.split_whitespace();
+
+    // "#!/usr/bin/env python3" names the interpreter as argument
+    let mut program = parts.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = parts.find(|part| !part.starts_with('-'))?;
+    }
+
+    INTERPRETERS
+        .iter()
+        .find(|(name, _)| *name == program)
+        .map(|(_, language)| *language)
+}
+
+fn from_content(content: &str) -> Option<&'static str> {
+    let trimmed = content.trim_start();
+    let lower: String = trimmed.chars().take(512).collect::<String>().to_ascii_lowercase();
+
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(content).is_ok()
+    {
+        return Some("json");
+    }
+
+    if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        return Some("html");
+    }
+    if lower.starts_with("<?xml") {
+        return Some("xml");
+    }
+    if lower.starts_with("<?php") {
+        return Some("php");
+    }
+    if trimmed.starts_with("diff --git") || (trimmed.starts_with("--- ") && content.contains("\n+++ ") && content.contains("\n@@")) {
+        return Some("diff");
+    }
+
+    let has_line = |prefix: &str| content.lines().any(|line| line.trim_start().starts_with(prefix));
+
+    if has_line("fn ") && (has_line("use ") || has_line("let ") || content.contains("impl ")) {
+        return Some("rust");
+    }
+    if has_line("package main") || (has_line("package ") && has_line("func ")) {
+        return Some("go");
+    }
+    if has_line("#include") {
+        return Some(if content.contains("std::") || has_line("namespace ") { "cpp" } else { "c" });
+    }
+    if has_line("public class ") || content.contains("public static void main") {
+        return Some("java");
+    }
+    if has_line("def ") && (has_line("import ") || has_line("from ") || content.contains("):\n")) {
+        return Some("python");
+    }
+    if has_line("function ") || (has_line("const ") && content.contains("=>")) {
+        return Some("javascript");
+    }
+    if ["select ", "insert into ", "create table ", "update ", "delete from "]
+        .iter()
+        .any(|keyword| lower.starts_with(keyword))
+    {
+        return Some("sql");
+    }
+
+    None
+}
+
+/*
+ * --- Raw responses ---
+ */
+
+// Only types browsers will not execute; everything else is served as plain text
+pub fn content_type(language: Option<&str>) -> &'static str {
+    match language {
+        Some("json") => "application/json; charset=utf-8",
+        Some("markdown") => "text/markdown; charset=utf-8",
+        Some("csv") => "text/csv; charset=utf-8",
+        Some("diff") => "text/x-diff; charset=utf-8",
+        _ => "text/plain; charset=utf-8",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extension_wins_over_content() {
+        assert_eq!(detect(Some("build.RS"), "{}").as_deref(), Some("rust"));
+        assert_eq!(detect(Some("Dockerfile"), "FROM alpine").as_deref(), Some("dockerfile"));
+        assert_eq!(detect(Some("notes"), "{\"a\": [1, 2]}").as_deref(), Some("json"));
+    }
+
+    #[test]
+    fn shebang_names_the_interpreter() {
+        assert_eq!(detect(None, "#!/usr/bin/env python3\nprint(1)").as_deref(), Some("python"));
+        assert_eq!(detect(None, "#!/bin/bash -e\necho hi").as_deref(), Some("shell"));
+        assert_eq!(detect(None, "#!/usr/bin/env -S node --no-warnings\n").as_deref(), Some("javascript"));
+    }
+
+    #[test]
+    fn content_is_guessed_last() {
+        assert_eq!(detect(None, "use std::io;\nfn main() {}").as_deref(), Some("rust"));
+        assert_eq!(detect(None, "<!DOCTYPE html><p>hi</p>").as_deref(), Some("html"));
+        assert_eq!(detect(None, "SELECT * FROM pastes;").as_deref(), Some("sql"));
+        assert_eq!(detect(None, "just some words"), None);
+    }
+
+    #[test]
+    fn names_stay_bare_and_languages_plain() {
+        assert_eq!(sanitize_filename(" report.txt ").unwrap().as_deref(), Some("report.txt"));
+        assert_eq!(sanitize_filename("").unwrap(), None);
+        assert!(sanitize_filename("../secret").is_err());
+        assert!(sanitize_filename("a\"b").is_err());
+        assert!(sanitize_filename("..").is_err());
+
+        assert_eq!(sanitize_language("C++").unwrap().as_deref(), Some("c++"));
+        assert!(sanitize_language("<script>").is_err());
+    }
+
+    #[test]
+    fn raw_content_types_never_execute() {
+        assert_eq!(content_type(Some("json")), "application/json; charset=utf-8");
+        assert_eq!(content_type(Some("html")), "text/plain; charset=utf-8");
+        assert_eq!(content_type(None), "text/plain; charset=utf-8");
+    }
+}
@@ -5,7 +5,10 @@ use tower_http::services::fs::{ServeDir, ServeFile};
 use tracing::info;
 
 use crate::rate_limit::rate_limit;
-use crate::pow::pow_challenge;
+use crate::pow::{pow_challenge, pow_info};
+
+use crate::storage::keyring::Keyring;
+use crate::storage::sqlite::SqliteStorage;
 
 use state::AppState;
 use state::{spawn_restore_strikes_task, spawn_cleanup_task};
@@ -18,11 +21,26 @@ mod state;
 mod pow;
 mod rate_limit;
 mod abuse;
+mod owner;
+mod ranking;
+mod receipt;
+mod export;
+mod template;
+mod language;
+mod envelope;
+mod crawler;
+#[cfg(test)]
+mod testing;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
 
+    // Offline maintenance, run with the server stopped: `polly-backend reencrypt`
+    if std::env::args().nth(1).as_deref() == Some("reencrypt") {
+        return reencrypt();
+    }
+
     // ---- Storage configuration ----
     let storage_backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "memory".into());
 
@@ -67,6 +85,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+// Moves every stored row to the first configured encryption key, or back to plaintext without keys
+fn reencrypt() -> Result<(), Box<dyn std::error::Error>> {
+    let sqlite_path = std::env::var("SQLITE_PATH").unwrap_or_else(|_| "polly.sqlite".into());
+    let keyring = Keyring::from_env()?;
+    let key = keyring.current_id().unwrap_or("none (plaintext)").to_string();
+
+    let storage = SqliteStorage::new(&sqlite_path, keyring)?;
+    let rewritten = storage.reencrypt()?;
+
+    info!("Re-encrypted {} rows in {} under key {}", rewritten, sqlite_path, key);
+    Ok(())
+}
+
 fn app(state: AppState) -> Router {
     let static_path = if Path::new("../static").exists() {
         "../static"
@@ -84,27 +115,63 @@ fn app(state: AppState) -> Router {
     const MAX_BODY_SIZE: usize = 256 * 1024; // 256 KB
 
 Router::new()
+    // --- Proof of work ---
+    .route("/api/pow", axum::routing::get(pow_info))
+
     // --- Bin routes ---
     .route("/bin", axum::routing::get(bin::bin_html)) 
     .route("/bin/:id", axum::routing::get(bin::serve_bin_html))
+    .route("/bin/:id/raw", axum::routing::get(bin::get_raw_paste))
+    .route("/bin/:id/raw/:file", axum::routing::get(bin::get_raw_file))
     .route("/api/bin", axum::routing::post(bin::create_paste)
+            .layer(DefaultBodyLimit::max(bin::MAX_PASTE_BODY_SIZE))
+            .layer(middleware::from_fn_with_state(state.clone(), pow_challenge)))
+    .route("/api/bin/raw", axum::routing::post(bin::create_raw_paste)
+            .layer(middleware::from_fn_with_state(state.clone(), pow_challenge)))
+    .route("/api/bin/upload", axum::routing::post(bin::upload_attachment)
+            .put(bin::upload_attachment) // `curl -T`
+            .layer(DefaultBodyLimit::disable()) // streamed to disk, limited by MAX_ATTACHMENT_SIZE
+            .layer(middleware::from_fn_with_state(state.clone(), pow_challenge)))
+    .route("/api/bin/:id", axum::routing::get(bin::get_paste)
+            .patch(bin::update_paste)
+            .delete(bin::delete_paste)
+            .layer(DefaultBodyLimit::max(bin::MAX_PASTE_BODY_SIZE)))
+    .route("/api/bin/:id/reveal", axum::routing::post(bin::reveal_paste))
+    .route("/api/bin/:id/diff", axum::routing::get(bin::diff_paste))
+    .route("/api/bin/:id/download", axum::routing::get(bin::download_attachment))
+    .route("/api/bin/:id/fork", axum::routing::post(bin::fork_paste)
+            .layer(DefaultBodyLimit::max(bin::MAX_PASTE_BODY_SIZE))
             .layer(middleware::from_fn_with_state(state.clone(), pow_challenge)))
-    .route("/api/bin/:id", axum::routing::get(bin::get_paste))   
 
     // --- Cal routes ---
     .route("/cal", axum::routing::get(cal::cal_html))
     .route("/cal/:id", axum::routing::get(cal::serve_cal_html))
     .route("/api/cal", axum::routing::post(cal::create_event)
             .layer(middleware::from_fn_with_state(state.clone(), pow_challenge)))
-    .route("/api/cal/:id", axum::routing::get(cal::get_event))
+    .route("/api/cal/:id", axum::routing::get(cal::get_event)
+            .patch(cal::update_event)
+            .delete(cal::delete_event))
+    .route("/api/cal/:id/reveal", axum::routing::post(cal::reveal_event))
     
     // --- Ask routes ---
     .route("/ask", axum::routing::get(ask::ask_html))
     .route("/ask/:id", axum::routing::get(ask::serve_ask_html))
     .route("/api/ask", axum::routing::post(ask::create_survey)
             .layer(middleware::from_fn_with_state(state.clone(), pow_challenge)))
-    .route("/api/ask/:id", axum::routing::get(ask::get_survey)) 
+    .route("/api/ask/:id", axum::routing::get(ask::get_survey)
+            .patch(ask::update_survey)
+            .delete(ask::delete_survey))
+    .route("/api/ask/:id/results", axum::routing::get(ask::get_survey_results))
+    .route("/api/ask/:id/export", axum::routing::get(ask::export_survey))
+    .route("/api/ask/:id/stream", axum::routing::get(ask::stream_survey))
+    .route("/api/ask/:id/clone", axum::routing::post(ask::clone_survey)
+            .layer(middleware::from_fn_with_state(state.clone(), pow_challenge)))
+    .route("/api/ask/:id/close", axum::routing::post(ask::close_survey))
+    .route("/api/ask/:id/reopen", axum::routing::post(ask::reopen_survey))
+    .route("/api/ask/:id/finalize", axum::routing::post(ask::finalize_survey))
     .route("/api/ask/:id/vote", axum::routing::post(ask::vote_survey)
+            .put(ask::change_vote)
+            .delete(ask::withdraw_vote)
             .layer(middleware::from_fn_with_state(state.clone(), pow_challenge)))  
 
     // --- Static files ---
@@ -0,0 +1,85 @@
+use axum::http::HeaderMap;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/*
+ * --- Configuration ---
+ */
+
+pub const MANAGE_TOKEN_HEADER: &str = "X-Manage-Token";
+const MANAGE_TOKEN_BYTES: usize = 32;
+
+/*
+ * --- Management tokens ---
+ */
+
+// Tokens are random and high entropy, so a plain SHA-256 is enough to store them
+pub fn generate_token() -> (String, String) {
+    let mut bytes = [0u8; MANAGE_TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    let token = hex::encode(bytes);
+    let hash = hash_token(&token);
+
+    (token, hash)
+}
+
+pub fn hash_token(token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+pub fn verify_token(stored_hash: &Option<String>, headers: &HeaderMap) -> bool {
+    let Some(stored_hash) = stored_hash else {
+        return false; // records created before tokens existed cannot be managed
+    };
+
+    let Some(provided) = headers
+        .get(MANAGE_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+    else {
+        return false;
+    };
+
+    constant_time_eq(hash_token(provided).as_bytes(), stored_hash.as_bytes())
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(MANAGE_TOKEN_HEADER, token.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn tokens_verify_against_their_hash() {
+        let (token, hash) = generate_token();
+
+        assert_eq!(token.len(), 2 * MANAGE_TOKEN_BYTES);
+        assert_eq!(hash, hash_token(&token));
+        assert!(verify_token(&Some(hash.clone()), &headers(&token)));
+        assert!(verify_token(&Some(hash.clone()), &headers(&format!(" {} ", token))));
+        assert!(!verify_token(&Some(hash.clone()), &headers(&generate_token().0)));
+        assert!(!verify_token(&Some(hash), &HeaderMap::new()));
+    }
+
+    #[test]
+    fn records_without_a_token_cannot_be_managed() {
+        assert!(!verify_token(&None, &headers("anything")));
+        assert!(!verify_token(&Some(hash_token("")), &headers(" ")));
+    }
+}
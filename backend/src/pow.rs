@@ -3,7 +3,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use axum::{
     body::Body,
     extract::State,
-    http::{Request, StatusCode, HeaderMap, HeaderValue},
+    http::{header, Request, StatusCode, HeaderMap, HeaderValue},
     middleware::Next,
     response::IntoResponse,
 };
@@ -29,6 +29,18 @@ fn make_challenge(secret: &[u8], now: u64) -> String {
     hex::encode(hasher.finalize())
 }
 
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// Leading zero hex digits of a solved hash, so shell scripts can compare strings instead of bits
+fn hex_prefix(difficulty: u32) -> String {
+    "0".repeat(difficulty.div_ceil(4) as usize)
+}
+
 fn hash_ok(hash: &[u8], difficulty: u32) -> bool {
     let mut zero_bits = 0;
     for b in hash {
@@ -43,24 +55,42 @@ fn hash_ok(hash: &[u8], difficulty: u32) -> bool {
     zero_bits >= difficulty as usize
 }
 
+/*
+ * --- Handlers ---
+ */
+
+// Hands out a challenge up front, e.g. for shell scripts:
+//   read challenge difficulty prefix < <(curl -s $HOST/api/pow)
+//   n=0; until printf '%s%s' "$challenge" "$n" | sha256sum | grep -q "^$prefix"; do n=$((n+1)); done
+//   curl -H "X-POW-Challenge: $challenge" -H "X-POW-Nonce: $n" ...
+pub async fn pow_info(State(state): State<AppState>) -> impl IntoResponse {
+    let challenge = make_challenge(&state.pow_secret, now_secs());
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        format!("{} {} {}\n", challenge, POW_DIFFICULTY, hex_prefix(POW_DIFFICULTY)),
+    )
+}
+
+/*
+ * --- Middleware ---
+ */
+
 pub async fn pow_challenge(
     State(state): State<AppState>,
     req: Request<Body>,
     next: Next,
 ) -> impl IntoResponse {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+    let now = now_secs();
 
     let headers = req.headers();
 
+    // The hash is recomputed below, so clients may leave X-POW-Hash out
     let challenge = headers.get("X-POW-Challenge");
     let nonce = headers.get("X-POW-Nonce");
-    let hash = headers.get("X-POW-Hash");
 
     // No PoW provided: demand one
-    if challenge.is_none() || nonce.is_none() || hash.is_none() {
+    if challenge.is_none() || nonce.is_none() {
         let challenge = make_challenge(&state.pow_secret, now);
         let mut headers = HeaderMap::new();
 
@@ -74,7 +104,14 @@ pub async fn pow_challenge(
             HeaderValue::from_str(&POW_DIFFICULTY.to_string()).unwrap(),
         );
 
-        return (StatusCode::PRECONDITION_REQUIRED, headers).into_response();
+        let body = format!(
+            "Proof of work required: send X-POW-Challenge and an X-POW-Nonce for which \
+             sha256(challenge + nonce) starts with {} zero bits (hex prefix {}).\n",
+            POW_DIFFICULTY,
+            hex_prefix(POW_DIFFICULTY),
+        );
+
+        return (StatusCode::PRECONDITION_REQUIRED, headers, body).into_response();
     }
 
     let challenge_str = match challenge.unwrap().to_str() {
@@ -116,3 +153,27 @@ pub async fn pow_challenge(
     next.run(req).await
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_prefix_covers_the_difficulty() {
+        assert_eq!(hex_prefix(12), "000");
+        assert_eq!(hex_prefix(13), "0000");
+    }
+
+    #[test]
+    fn nonces_found_by_hex_prefix_pass() {
+        let challenge = make_challenge(b"secret", 0);
+        let prefix = hex_prefix(POW_DIFFICULTY);
+
+        let hash = (0u64..)
+            .map(|nonce| Sha256::digest(format!("{}{}", challenge, nonce)))
+            .find(|hash| hex::encode(hash).starts_with(&prefix))
+            .expect("nonce");
+
+        assert!(hash_ok(&hash, POW_DIFFICULTY));
+    }
+}
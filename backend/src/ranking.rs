@@ -0,0 +1,413 @@
+use serde::{Deserialize, Serialize};
+
+/*
+ * --- Counting methods ---
+ */
+
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RankMethod {
+    #[default]
+    Borda,
+    Irv,
+    Schulze,
+}
+
+// A ballot lists item indices from most to least preferred; unlisted items are unranked
+pub type Ballot = Vec<usize>;
+
+#[derive(Serialize)]
+#[serde(tag = "method", rename_all = "lowercase")]
+pub enum RankOutcome {
+    Borda {
+        scores: Vec<usize>,
+        winners: Vec<usize>,
+    },
+    Irv {
+        rounds: Vec<IrvRound>,
+        winners: Vec<usize>,
+    },
+    Schulze {
+        pairwise: Vec<Vec<usize>>,
+        condorcet_winner: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        strongest_paths: Option<Vec<Vec<usize>>>,
+        winners: Vec<usize>,
+    },
+}
+
+#[derive(Serialize)]
+pub struct IrvRound {
+    pub counts: Vec<IrvCount>,
+    pub exhausted: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eliminated: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct IrvCount {
+    pub index: usize,
+    pub votes: usize,
+}
+
+pub fn count(method: RankMethod, candidates: usize, ballots: &[Ballot]) -> RankOutcome {
+    match method {
+        RankMethod::Borda => borda(candidates, ballots),
+        RankMethod::Irv => instant_runoff(candidates, ballots),
+        RankMethod::Schulze => condorcet_schulze(candidates, ballots),
+    }
+}
+
+impl RankOutcome {
+    // Every item, best first as the method sees it; ties keep item order
+    pub fn ranking(&self, candidates: usize) -> Vec<usize> {
+        let mut ranking: Vec<usize> = (0..candidates).collect();
+
+        match self {
+            RankOutcome::Borda { scores, .. } => {
+                ranking.sort_by(|&a, &b| scores[b].cmp(&scores[a]).then(a.cmp(&b)));
+            }
+            RankOutcome::Irv { rounds, .. } => {
+                // Final round by votes, then the eliminated items from last to first out
+                let Some(last) = rounds.last() else {
+                    return ranking;
+                };
+
+                let mut finalists: Vec<&IrvCount> = last.counts.iter().collect();
+                finalists.sort_by(|a, b| b.votes.cmp(&a.votes).then(a.index.cmp(&b.index)));
+
+                ranking = finalists.iter().map(|c| c.index).collect();
+                ranking.extend(rounds.iter().rev().filter_map(|round| round.eliminated));
+            }
+            RankOutcome::Schulze { pairwise, strongest_paths, .. } => {
+                // Items beaten by fewer others through their strongest paths rank higher
+                let paths = strongest_paths.clone().unwrap_or_else(|| widest_paths(candidates, pairwise));
+                let wins: Vec<usize> = (0..candidates)
+                    .map(|i| (0..candidates).filter(|&j| i != j && paths[i][j] > paths[j][i]).count())
+                    .collect();
+
+                ranking.sort_by(|&a, &b| wins[b].cmp(&wins[a]).then(a.cmp(&b)));
+            }
+        }
+
+        ranking
+    }
+}
+
+/*
+ * --- Borda count ---
+ */
+
+// First place earns n - 1 points, second n - 2 and so on; unranked items earn nothing
+pub fn borda_scores(candidates: usize, ballots: &[Ballot]) -> Vec<usize> {
+    let mut scores = vec![0usize; candidates];
+
+    for ballot in ballots {
+        for (position, &index) in ballot.iter().enumerate() {
+            if index < candidates && position < candidates {
+                scores[index] += candidates - 1 - position;
+            }
+        }
+    }
+
+    scores
+}
+
+fn borda(candidates: usize, ballots: &[Ballot]) -> RankOutcome {
+    let scores = borda_scores(candidates, ballots);
+    let winners = if ballots.is_empty() {
+        Vec::new()
+    } else {
+        top_indices(&scores)
+    };
+
+    RankOutcome::Borda { scores, winners }
+}
+
+/*
+ * --- Instant-runoff ---
+ */
+
+// Ties for elimination go to the item with fewer votes in earlier rounds, then to the later item
+fn instant_runoff(candidates: usize, ballots: &[Ballot]) -> RankOutcome {
+    let mut continuing: Vec<bool> = vec![true; candidates];
+    let mut history: Vec<Vec<usize>> = Vec::new();
+    let mut rounds = Vec::new();
+
+    if ballots.is_empty() {
+        return RankOutcome::Irv { rounds, winners: Vec::new() };
+    }
+
+    loop {
+        // Count each ballot for its highest ranked continuing item
+        let mut votes = vec![0usize; candidates];
+        let mut exhausted = 0;
+
+        for ballot in ballots {
+            match ballot.iter().find(|&&i| i < candidates && continuing[i]) {
+                Some(&index) => votes[index] += 1,
+                None => exhausted += 1,
+            }
+        }
+
+        let counts: Vec<IrvCount> = (0..candidates)
+            .filter(|&i| continuing[i])
+            .map(|index| IrvCount { index, votes: votes[index] })
+            .collect();
+
+        let active = ballots.len() - exhausted;
+        let remaining: Vec<usize> = counts.iter().map(|c| c.index).collect();
+
+        // Majority of the ballots still in play
+        if let Some(leader) = counts.iter().find(|c| c.votes * 2 > active) {
+            let winner = leader.index;
+            rounds.push(IrvRound { counts, exhausted, eliminated: None });
+            return RankOutcome::Irv { rounds, winners: vec![winner] };
+        }
+
+        // Remaining items are all tied (or nobody is left to eliminate)
+        let lowest = counts.iter().map(|c| c.votes).min().unwrap_or(0);
+        if remaining.len() <= 1 || counts.iter().all(|c| c.votes == lowest) {
+            rounds.push(IrvRound { counts, exhausted, eliminated: None });
+            return RankOutcome::Irv { rounds, winners: remaining };
+        }
+
+        let eliminated = remaining
+            .iter()
+            .copied()
+            .filter(|&i| votes[i] == lowest)
+            .min_by(|&a, &b| {
+                history
+                    .iter()
+                    .rev()
+                    .map(|round| round[a].cmp(&round[b]))
+                    .find(|o| o.is_ne())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(b.cmp(&a))
+            })
+            .expect("at least one item has the lowest count");
+
+        continuing[eliminated] = false;
+        history.push(votes);
+        rounds.push(IrvRound { counts, exhausted, eliminated: Some(eliminated) });
+    }
+}
+
+/*
+ * --- Condorcet / Schulze ---
+ */
+
+// pairwise[i][j] is the number of ballots preferring i over j; ranked items beat unranked ones
+pub fn pairwise_matrix(candidates: usize, ballots: &[Ballot]) -> Vec<Vec<usize>> {
+    let mut pairwise = vec![vec![0usize; candidates]; candidates];
+
+    for ballot in ballots {
+        let mut position = vec![usize::MAX; candidates];
+        for (rank, &index) in ballot.iter().enumerate() {
+            if index < candidates {
+                position[index] = rank;
+            }
+        }
+
+        for i in 0..candidates {
+            for j in 0..candidates {
+                if i != j && position[i] < position[j] {
+                    pairwise[i][j] += 1;
+                }
+            }
+        }
+    }
+
+    pairwise
+}
+
+fn condorcet_schulze(candidates: usize, ballots: &[Ballot]) -> RankOutcome {
+    let pairwise = pairwise_matrix(candidates, ballots);
+
+    let condorcet_winner = (0..candidates).find(|&i| {
+        (0..candidates).all(|j| i == j || pairwise[i][j] > pairwise[j][i])
+    });
+
+    if let Some(winner) = condorcet_winner {
+        return RankOutcome::Schulze {
+            pairwise,
+            condorcet_winner,
+            strongest_paths: None,
+            winners: vec![winner],
+        };
+    }
+
+    if ballots.is_empty() {
+        return RankOutcome::Schulze {
+            pairwise,
+            condorcet_winner: None,
+            strongest_paths: None,
+            winners: Vec::new(),
+        };
+    }
+
+    let paths = widest_paths(candidates, &pairwise);
+    let winners = (0..candidates)
+        .filter(|&i| (0..candidates).all(|j| i == j || paths[i][j] >= paths[j][i]))
+        .collect();
+
+    RankOutcome::Schulze {
+        pairwise,
+        condorcet_winner: None,
+        strongest_paths: Some(paths),
+        winners,
+    }
+}
+
+/*
+ * --- Helper functions ---
+ */
+
+// Widest paths through the defeat graph (Floyd-Warshall variant)
+fn widest_paths(candidates: usize, pairwise: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut paths = vec![vec![0usize; candidates]; candidates];
+    for i in 0..candidates {
+        for j in 0..candidates {
+            if i != j && pairwise[i][j] > pairwise[j][i] {
+                paths[i][j] = pairwise[i][j];
+            }
+        }
+    }
+
+    for k in 0..candidates {
+        for i in 0..candidates {
+            if i == k {
+                continue;
+            }
+            for j in 0..candidates {
+                if j == i || j == k {
+                    continue;
+                }
+                paths[i][j] = paths[i][j].max(paths[i][k].min(paths[k][j]));
+            }
+        }
+    }
+
+    paths
+}
+
+fn top_indices(scores: &[usize]) -> Vec<usize> {
+    let best = scores.iter().copied().max().unwrap_or(0);
+
+    scores
+        .iter()
+        .enumerate()
+        .filter(|(_, &score)| score == best)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ballots(groups: &[(usize, &[usize])]) -> Vec<Ballot> {
+        groups
+            .iter()
+            .flat_map(|&(count, ballot)| std::iter::repeat_n(ballot.to_vec(), count))
+            .collect()
+    }
+
+    fn winners(outcome: &RankOutcome) -> &[usize] {
+        match outcome {
+            RankOutcome::Borda { winners, .. } | RankOutcome::Irv { winners, .. } | RankOutcome::Schulze { winners, .. } => winners,
+        }
+    }
+
+    #[test]
+    fn borda_scores_positions() {
+        let ballots = ballots(&[(1, &[0, 1, 2]), (1, &[1, 0, 2]), (1, &[0, 2, 1])]);
+        let outcome = count(RankMethod::Borda, 3, &ballots);
+
+        assert_eq!(borda_scores(3, &ballots), vec![5, 3, 1]);
+        assert_eq!(winners(&outcome), &[0]);
+        assert_eq!(outcome.ranking(3), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn borda_ignores_unranked_items() {
+        let ballots = ballots(&[(1, &[2])]);
+
+        assert_eq!(borda_scores(3, &ballots), vec![0, 0, 2]);
+    }
+
+    #[test]
+    fn no_ballots_no_winners() {
+        for method in [RankMethod::Borda, RankMethod::Irv, RankMethod::Schulze] {
+            let outcome = count(method, 3, &[]);
+            assert!(winners(&outcome).is_empty());
+            assert_eq!(outcome.ranking(3), vec![0, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn irv_transfers_eliminated_votes() {
+        let ballots = ballots(&[(2, &[0]), (2, &[1]), (1, &[2, 1])]);
+        let outcome = count(RankMethod::Irv, 3, &ballots);
+
+        let RankOutcome::Irv { rounds, .. } = &outcome else { panic!("expected an IRV outcome") };
+        assert_eq!(rounds.len(), 2);
+        assert_eq!(rounds[0].eliminated, Some(2));
+        assert_eq!(winners(&outcome), &[1]);
+        assert_eq!(outcome.ranking(3), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn irv_tie_shares_the_win() {
+        let ballots = ballots(&[(1, &[0]), (1, &[1])]);
+        let outcome = count(RankMethod::Irv, 2, &ballots);
+
+        let RankOutcome::Irv { rounds, .. } = &outcome else { panic!("expected an IRV outcome") };
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds[0].eliminated, None);
+        assert_eq!(winners(&outcome), &[0, 1]);
+    }
+
+    #[test]
+    fn irv_elimination_tie_goes_to_the_later_item() {
+        let ballots = ballots(&[(2, &[0]), (1, &[1]), (1, &[2]), (1, &[3, 0])]);
+        let outcome = count(RankMethod::Irv, 4, &ballots);
+
+        let RankOutcome::Irv { rounds, .. } = &outcome else { panic!("expected an IRV outcome") };
+        assert_eq!(rounds[0].eliminated, Some(3));
+        assert_eq!(winners(&outcome), &[0]);
+    }
+
+    #[test]
+    fn schulze_resolves_a_cycle() {
+        // 0 beats 1 (5:2), 1 beats 2 (5:2), 2 beats 0 (4:3)
+        let ballots = ballots(&[(3, &[0, 1, 2]), (2, &[1, 2, 0]), (2, &[2, 0, 1])]);
+        let outcome = count(RankMethod::Schulze, 3, &ballots);
+
+        let RankOutcome::Schulze { condorcet_winner, strongest_paths, .. } = &outcome else {
+            panic!("expected a Schulze outcome")
+        };
+        assert_eq!(*condorcet_winner, None);
+        assert_eq!(strongest_paths.as_deref(), Some(&[vec![0, 5, 5], vec![4, 0, 5], vec![4, 4, 0]][..]));
+        assert_eq!(winners(&outcome), &[0]);
+        assert_eq!(outcome.ranking(3), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn schulze_condorcet_winner_ranks_first() {
+        let ballots = ballots(&[(1, &[1, 0, 2]), (1, &[1, 2, 0])]);
+        let outcome = count(RankMethod::Schulze, 3, &ballots);
+
+        let RankOutcome::Schulze { condorcet_winner, .. } = &outcome else { panic!("expected a Schulze outcome") };
+        assert_eq!(*condorcet_winner, Some(1));
+        assert_eq!(outcome.ranking(3), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn schulze_tie_shares_the_win() {
+        let ballots = ballots(&[(1, &[0, 1]), (1, &[1, 0])]);
+        let outcome = count(RankMethod::Schulze, 2, &ballots);
+
+        assert_eq!(winners(&outcome), &[0, 1]);
+    }
+}
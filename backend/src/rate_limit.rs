@@ -26,6 +26,11 @@ pub const GLOBAL_WINDOW: Duration = Duration::from_secs(60);
 pub const IP_MAX_REQUESTS: usize = 100; 
 pub const IP_WINDOW: Duration = Duration::from_secs(60);
 
+// Long-lived responses (live result streams) are capped per IP and end after a while,
+// so reconnecting clients pass through the request limits above again
+pub const IP_MAX_STREAMS: usize = 4;
+pub const STREAM_MAX_DURATION: Duration = Duration::from_secs(60 * 15);
+
 /*
  * --- Rate limiter states ---
  */
@@ -54,14 +59,70 @@ impl IPRateLimiter {
     }
 }
 
+pub struct StreamSlots {
+    pub open: HashMap<String, usize>,
+}
+
+impl StreamSlots {
+    pub fn new() -> Self {
+        Self {
+            open: HashMap::new(),
+        }
+    }
+}
+
 pub type SharedGlobalRateLimiter = Arc<Mutex<GlobalRateLimiter>>;
 pub type SharedIPRateLimiter = Arc<Mutex<IPRateLimiter>>;
+// Released from Drop, hence not the async mutex
+pub type SharedStreamSlots = Arc<std::sync::Mutex<StreamSlots>>;
+
+/*
+ * --- Stream slots ---
+ */
+
+// Held for the lifetime of a stream; the slot is given back when the stream is dropped
+pub struct StreamSlot {
+    slots: SharedStreamSlots,
+    ip: String,
+}
+
+impl StreamSlot {
+    pub fn acquire(slots: &SharedStreamSlots, ip: &str) -> Option<Self> {
+        let mut guard = slots.lock().unwrap_or_else(|e| e.into_inner());
+        let open = guard.open.entry(ip.to_string()).or_default();
+
+        if *open >= IP_MAX_STREAMS {
+            return None;
+        }
+
+        *open += 1;
+
+        Some(Self {
+            slots: slots.clone(),
+            ip: ip.to_string(),
+        })
+    }
+}
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        let mut guard = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(open) = guard.open.get_mut(&self.ip) {
+            *open = open.saturating_sub(1);
+
+            if *open == 0 {
+                guard.open.remove(&self.ip);
+            }
+        }
+    }
+}
 
 /*
  * --- Helper functions ---
  */
 
-fn extract_real_ip(headers: &HeaderMap, addr: SocketAddr) -> String {
+pub fn extract_real_ip(headers: &HeaderMap, addr: SocketAddr) -> String {
     let peer = addr.ip().to_string();
 
     // If request comes from Docker bridge (172.x.x.x), trust proxy headers instead
@@ -85,7 +146,7 @@ fn extract_real_ip(headers: &HeaderMap, addr: SocketAddr) -> String {
 }
 
 
-fn normalize_ip(ip: &str) -> String {
+pub fn normalize_ip(ip: &str) -> String {
     if let Ok(addr) = ip.parse::<IpAddr>() {
         match addr {
             IpAddr::V4(v4) => v4.to_string(),
@@ -0,0 +1,119 @@
+use axum::http::{header, HeaderMap};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::owner::constant_time_eq;
+
+/*
+ * --- Configuration ---
+ */
+
+pub const RECEIPT_HEADER: &str = "X-Vote-Receipt";
+pub const VOTER_COOKIE: &str = "polly_voter";
+const VOTER_TOKEN_BYTES: usize = 32;
+const VOTER_COOKIE_MAX_AGE: u64 = 365 * 24 * 60 * 60; // one year
+const HMAC_BLOCK_LEN: usize = 64; // SHA-256 block size
+
+/*
+ * --- Vote receipts ---
+ */
+
+// A receipt names the ballot and proves the server handed it out: "<ballot id>.<signature>"
+pub fn issue(secret: &[u8], survey_id: &str, ballot_id: &str) -> String {
+    format!("{}.{}", ballot_id, sign(secret, "receipt", survey_id, ballot_id))
+}
+
+// Returns the ballot id of a valid receipt for this survey
+pub fn verify(secret: &[u8], survey_id: &str, headers: &HeaderMap) -> Option<String> {
+    let provided = headers
+        .get(RECEIPT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)?;
+
+    let (ballot_id, signature) = provided.split_once('.')?;
+    let expected = sign(secret, "receipt", survey_id, ballot_id);
+
+    constant_time_eq(signature.as_bytes(), expected.as_bytes()).then(|| ballot_id.to_string())
+}
+
+/*
+ * --- Voter tokens ---
+ */
+
+// Random per browser, so voters sharing an address still get a ballot each.
+// This makes one vote per participant best-effort: clearing cookies or switching browsers gets a new ballot.
+pub fn generate_voter_token() -> String {
+    let mut bytes = [0u8; VOTER_TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn voter_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == VOTER_COOKIE)
+        .map(|(_, token)| token.to_string())
+        .filter(|token| token.len() == VOTER_TOKEN_BYTES * 2 && token.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+pub fn voter_cookie(token: &str) -> String {
+    format!(
+        "{}={}; Path=/api/ask; Max-Age={}; HttpOnly; SameSite=Strict",
+        VOTER_COOKIE, token, VOTER_COOKIE_MAX_AGE
+    )
+}
+
+// Salted per survey, so the same voter cannot be recognized across surveys
+pub fn participant(secret: &[u8], survey_id: &str, voter_token: &str) -> String {
+    sign(secret, "participant", survey_id, voter_token)
+}
+
+// HMAC-SHA256 (RFC 2104) over purpose, survey id and value
+fn sign(secret: &[u8], purpose: &str, survey_id: &str, value: &str) -> String {
+    let mut key = [0u8; HMAC_BLOCK_LEN];
+    if secret.len() > HMAC_BLOCK_LEN {
+        key[..32].copy_from_slice(&Sha256::digest(secret));
+    } else {
+        key[..secret.len()].copy_from_slice(secret);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(key.map(|b| b ^ 0x36));
+    inner.update(purpose.as_bytes());
+    inner.update([0]);
+    inner.update(survey_id.as_bytes());
+    inner.update([0]);
+    inner.update(value.as_bytes());
+
+    let mut outer = Sha256::new();
+    outer.update(key.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    hex::encode(outer.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_matches_hmac_sha256() {
+        // python3: hmac.new(b"k" * 32, b"receipt\0s\0b", hashlib.sha256).hexdigest()
+        assert_eq!(
+            sign(&[b'k'; 32], "receipt", "s", "b"),
+            "faae32de622f8f185d1c1338a46da91ccf775865cc09b449f6382c609af19a7a"
+        );
+    }
+
+    #[test]
+    fn sign_hashes_long_keys() {
+        // python3: hmac.new(b"k" * 100, b"receipt\0s\0b", hashlib.sha256).hexdigest()
+        assert_eq!(
+            sign(&[b'k'; 100], "receipt", "s", "b"),
+            "271631e77ff924fce7436d5f2f5c7d3d02dff64b4701830be1d4221b0beb0318"
+        );
+    }
+}
@@ -1,19 +1,24 @@
 use std::sync::Arc;
 use tokio::{
-    sync::Mutex,
+    sync::{broadcast, Mutex},
     time::{interval, Duration},
 };
 use rand::RngCore;
+use sha2::{Digest, Sha256};
 use tracing::{info, warn};
 
-use crate::rate_limit::{GlobalRateLimiter, SharedGlobalRateLimiter, IPRateLimiter, SharedIPRateLimiter};
+use crate::rate_limit::{GlobalRateLimiter, SharedGlobalRateLimiter, IPRateLimiter, SharedIPRateLimiter, StreamSlots, SharedStreamSlots};
 use crate::abuse::{AbuseTracker, SharedAbuseTracker, BAN_LEVELS, PERMANENT_BAN_AFTER};
 use crate::storage::{PasteStore, EventStore, SurveyStore};
 use crate::storage::memory::{MemoryPasteStore, MemoryEventStore, MemorySurveyStore};
 use crate::storage::sqlite::SqliteStorage;
 use crate::storage::ban::BanStore;
+use crate::storage::attachments::AttachmentDir;
+use crate::storage::keyring::Keyring;
+use crate::bin;
 
 pub const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 10); // every 10 minutes
+pub const SURVEY_UPDATES_CAPACITY: usize = 256;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -21,10 +26,15 @@ pub struct AppState {
     pub cal: Arc<dyn EventStore>,
     pub ask: Arc<dyn SurveyStore>,
     pub pow_secret: [u8; 32],
+    pub receipt_secret: [u8; 32],
     pub global_rate_limiter: SharedGlobalRateLimiter,
     pub ip_rate_limiter: SharedIPRateLimiter,
     pub abuse_tracker: SharedAbuseTracker,
     pub ban_store: Option<Arc<dyn BanStore>>,
+    // Ids of surveys whose ballots or settings changed, for live result streams
+    pub survey_updates: broadcast::Sender<String>,
+    pub stream_slots: SharedStreamSlots,
+    pub attachments: AttachmentDir,
 }
 
 impl Default for AppState {
@@ -37,6 +47,7 @@ impl Default for AppState {
             cal: Arc::new(MemoryEventStore::new()),
             ask: Arc::new(MemorySurveyStore::new()),
             pow_secret: secret,
+            receipt_secret: configured_receipt_secret().unwrap_or_else(random_secret),
             global_rate_limiter: Arc::new(Mutex::new(
                 GlobalRateLimiter::new()
             )),
@@ -47,6 +58,11 @@ impl Default for AppState {
                 AbuseTracker::new()
             )),
             ban_store: None,
+            survey_updates: broadcast::channel(SURVEY_UPDATES_CAPACITY).0,
+            stream_slots: Arc::new(std::sync::Mutex::new(
+                StreamSlots::new()
+            )),
+            attachments: AttachmentDir::from_env(),
         }
     }
 }
@@ -63,11 +79,28 @@ impl AppState {
         let cal: Arc<dyn EventStore>;
         let ask: Arc<dyn SurveyStore>;
         let ban_store: Option<Arc<dyn BanStore>>;
+        let receipt_secret: [u8; 32];
 
         if use_sqlite {
             let path = sqlite_path.unwrap_or("polly.sqlite");
 
-            let storage = Arc::new(SqliteStorage::new(path)?);
+            let keyring = Keyring::from_env()?;
+            if let Some(key_id) = keyring.current_id() {
+                info!("Encrypting stored content with key {}", key_id);
+            }
+
+            let storage = Arc::new(SqliteStorage::new(path, keyring)?);
+
+            receipt_secret = match configured_receipt_secret() {
+                Some(secret) => secret,
+                None => storage.secret("receipt")?,
+            };
+
+            // Rows sealed with a key that is gone would only show up as missing content
+            let missing = storage.missing_keys()?;
+            if !missing.is_empty() {
+                return Err(format!("Stored content uses encryption keys that are not configured: {}", missing.join(", ")).into());
+            }
 
             bin = storage.clone();
             cal = storage.clone();
@@ -78,6 +111,7 @@ impl AppState {
             cal = Arc::new(MemoryEventStore::new());
             ask = Arc::new(MemorySurveyStore::new());
             ban_store = None;
+            receipt_secret = configured_receipt_secret().unwrap_or_else(random_secret);
         }
 
         Ok(Self {
@@ -85,10 +119,14 @@ impl AppState {
             cal,
             ask,
             pow_secret: secret,
+            receipt_secret,
             global_rate_limiter: Arc::new(Mutex::new(GlobalRateLimiter::new())),
             ip_rate_limiter: Arc::new(Mutex::new(IPRateLimiter::new())),
             abuse_tracker: Arc::new(Mutex::new(AbuseTracker::new())),
             ban_store,
+            survey_updates: broadcast::channel(SURVEY_UPDATES_CAPACITY).0,
+            stream_slots: Arc::new(std::sync::Mutex::new(StreamSlots::new())),
+            attachments: AttachmentDir::from_env(),
         })
     }
 
@@ -134,6 +172,21 @@ impl AppState {
     }
 }
 
+// RECEIPT_SECRET keeps vote receipts valid across restarts; without it SQLite mode stores a random one,
+// and in-memory receipts only last as long as the votes they belong to
+fn configured_receipt_secret() -> Option<[u8; 32]> {
+    match std::env::var("RECEIPT_SECRET") {
+        Ok(configured) if !configured.is_empty() => Some(Sha256::digest(configured.as_bytes()).into()),
+        _ => None,
+    }
+}
+
+fn random_secret() -> [u8; 32] {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
 pub fn spawn_restore_strikes_task(state: AppState) {
     tokio::spawn(async move {
         if let Some(ban_store) = &state.ban_store {
@@ -166,6 +219,10 @@ pub fn spawn_cleanup_task(state: AppState) {
                 warn!("Paste cleanup failed: {}", e);
             }
 
+            if let Err(e) = bin::cleanup_attachments(&state).await {
+                warn!("Attachment cleanup failed: {:?}", e);
+            }
+
             if let Err(e) = state.cal.cleanup_expired().await {
                 warn!("Event cleanup failed: {}", e);
             }
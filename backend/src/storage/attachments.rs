@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use axum::body::Bytes;
+use futures::{Stream, StreamExt};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::bin::BinError;
+
+/*
+ * --- Limits ---
+ */
+
+pub const DEFAULT_MAX_ATTACHMENT_SIZE: u64 = 25 * 1024 * 1024; // 25 MB
+const PARTIAL_SUFFIX: &str = ".part";
+const STALE_PARTIAL_AGE: Duration = Duration::from_secs(60 * 60);
+
+/*
+ * --- Attachment directory ---
+ */
+
+// One file per paste id; uploads land in "<id>.part" until the paste is stored
+#[derive(Clone)]
+pub struct AttachmentDir {
+    root: PathBuf,
+    pub max_size: u64,
+}
+
+impl AttachmentDir {
+    pub fn new(root: impl Into<PathBuf>, max_size: u64) -> Self {
+        Self {
+            root: root.into(),
+            max_size,
+        }
+    }
+
+    // DATA_DIR (default "data") and MAX_ATTACHMENT_SIZE in bytes
+    pub fn from_env() -> Self {
+        let data_dir = std::env::var("DATA_DIR").unwrap_or_else(|_| "data".into());
+        let max_size = std::env::var("MAX_ATTACHMENT_SIZE")
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(DEFAULT_MAX_ATTACHMENT_SIZE);
+
+        Self::new(PathBuf::from(data_dir).join("attachments"), max_size)
+    }
+
+    // Ids are generated server-side uuids, never user input
+    fn path(&self, id: &str) -> PathBuf {
+        self.root.join(id)
+    }
+
+    fn partial_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}{}", id, PARTIAL_SUFFIX))
+    }
+
+    // Streams the upload to disk, stopping as soon as it grows past the limit
+    pub async fn receive<S, E>(&self, id: &str, stream: S) -> Result<u64, BinError>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+        E: std::fmt::Display,
+    {
+        fs::create_dir_all(&self.root).await?;
+
+        let result = self.write_partial(id, stream).await;
+        if result.is_err() {
+            self.discard(id).await;
+        }
+
+        result
+    }
+
+    async fn write_partial<S, E>(&self, id: &str, stream: S) -> Result<u64, BinError>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+        E: std::fmt::Display,
+    {
+        let mut stream = std::pin::pin!(stream);
+        let mut file = fs::File::create(self.partial_path(id)).await?;
+        let mut size: u64 = 0;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| BinError::InvalidInput(format!("Upload failed: {}", e)))?;
+
+            size += chunk.len() as u64;
+            if size > self.max_size {
+                return Err(BinError::TooLarge(format!("Attachment exceeds maximum size of {} bytes", self.max_size)));
+            }
+
+            file.write_all(&chunk).await?;
+        }
+
+        if size == 0 {
+            return Err(BinError::InvalidInput("Attachment cannot be empty".into()));
+        }
+
+        file.sync_all().await?;
+        Ok(size)
+    }
+
+    // Makes a received upload downloadable, once its paste exists
+    pub async fn commit(&self, id: &str) -> Result<(), BinError> {
+        fs::rename(self.partial_path(id), self.path(id)).await?;
+        Ok(())
+    }
+
+    pub async fn discard(&self, id: &str) {
+        fs::remove_file(self.partial_path(id)).await.ok();
+    }
+
+    pub async fn open(&self, id: &str) -> Result<fs::File, BinError> {
+        fs::File::open(self.path(id)).await.map_err(|_| BinError::NotFound)
+    }
+
+    // Open handles keep reading after removal, so a download in flight finishes
+    pub async fn remove(&self, id: &str) -> Result<(), BinError> {
+        match fs::remove_file(self.path(id)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Ids of committed attachments; abandoned partial uploads are removed on the way
+    pub async fn list(&self) -> Result<Vec<String>, BinError> {
+        let mut entries = match fs::read_dir(&self.root).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut ids = Vec::new();
+
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if name.ends_with(PARTIAL_SUFFIX) {
+                let stale = entry
+                    .metadata()
+                    .await
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+                    .and_then(|modified| modified.elapsed().ok())
+                    .is_some_and(|age| age > STALE_PARTIAL_AGE);
+
+                if stale {
+                    fs::remove_file(entry.path()).await.ok();
+                }
+            } else {
+                ids.push(name);
+            }
+        }
+
+        Ok(ids)
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::{Read, Write};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+/*
+ * --- Codecs ---
+ */
+
+// Stored next to compressed columns; rows without one are from before compression
+pub const DEFLATE: &str = "deflate";
+
+/*
+ * --- Compression ---
+ */
+
+// Paste content is mostly logs and code, which shrink several times over
+pub fn compress(text: &str) -> Result<Vec<u8>, String> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())
+}
+
+pub fn decompress(codec: Option<&str>, bytes: Vec<u8>) -> Result<String, String> {
+    let bytes = match codec {
+        None => bytes,
+        Some(DEFLATE) => {
+            let mut text = Vec::new();
+            DeflateDecoder::new(bytes.as_slice())
+                .read_to_end(&mut text)
+                .map_err(|e| format!("Cannot decompress content: {}", e))?;
+            text
+        }
+        Some(codec) => return Err(format!("Unknown codec: {}", codec)),
+    };
+
+    String::from_utf8(bytes).map_err(|_| "Stored content is not valid UTF-8".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deflate_round_trip() {
+        let text = "fn main() {\n    println!(\"héllo\");\n}\n".repeat(200);
+        let compressed = compress(&text).unwrap();
+
+        assert!(compressed.len() < text.len() / 10);
+        assert_eq!(decompress(Some(DEFLATE), compressed).unwrap(), text);
+        assert_eq!(decompress(Some(DEFLATE), compress("").unwrap()).unwrap(), "");
+    }
+
+    #[test]
+    fn legacy_rows_are_stored_as_is() {
+        assert_eq!(decompress(None, b"plain old paste".to_vec()).unwrap(), "plain old paste");
+        assert_eq!(decompress(None, vec![0xff, 0xfe]).unwrap_err(), "Stored content is not valid UTF-8");
+    }
+
+    #[test]
+    fn unknown_and_corrupt_content_is_an_error() {
+        let compressed = compress("hello").unwrap();
+
+        assert_eq!(decompress(Some("zstd"), compressed).unwrap_err(), "Unknown codec: zstd");
+        assert!(decompress(Some(DEFLATE), b"not deflate at all".to_vec()).is_err());
+    }
+}
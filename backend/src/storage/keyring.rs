@@ -0,0 +1,231 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand_core::{OsRng, RngCore};
+
+/*
+ * --- Limits ---
+ */
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const MAX_KEY_ID_LEN: usize = 32;
+
+/*
+ * --- Keyring ---
+ */
+
+// Keys for content columns at rest; the first one encrypts, all of them decrypt
+#[derive(Clone, Default)]
+pub struct Keyring {
+    keys: Vec<(String, XChaCha20Poly1305)>,
+}
+
+impl Keyring {
+    // ENCRYPTION_KEYS_FILE names a file with one "<key id>:<base64 key>" per line,
+    // ENCRYPTION_KEYS holds the same entries separated by commas. Without either nothing is encrypted.
+    pub fn from_env() -> Result<Self, String> {
+        if let Ok(path) = std::env::var("ENCRYPTION_KEYS_FILE") {
+            let entries = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Cannot read {}: {}", path, e))?;
+            return Self::parse(&entries);
+        }
+
+        match std::env::var("ENCRYPTION_KEYS") {
+            Ok(entries) => Self::parse(&entries.replace(',', "\n")),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub(crate) fn parse(entries: &str) -> Result<Self, String> {
+        let mut keys: Vec<(String, XChaCha20Poly1305)> = Vec::new();
+
+        for entry in entries.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (id, key) = entry
+                .split_once(':')
+                .ok_or_else(|| "Encryption keys look like <key id>:<base64 key>".to_string())?;
+            let id = id.trim();
+
+            if id.is_empty()
+                || id.len() > MAX_KEY_ID_LEN
+                || !id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            {
+                return Err(format!("Invalid encryption key id: {}", id));
+            }
+
+            if keys.iter().any(|(existing, _)| existing == id) {
+                return Err(format!("Duplicate encryption key id: {}", id));
+            }
+
+            let key = STANDARD
+                .decode(key.trim())
+                .map_err(|_| format!("Encryption key {} is not valid base64", id))?;
+
+            if key.len() != KEY_LEN {
+                return Err(format!("Encryption key {} must be {} bytes", id, KEY_LEN));
+            }
+
+            keys.push((id.to_string(), XChaCha20Poly1305::new(Key::from_slice(&key))));
+        }
+
+        Ok(Self { keys })
+    }
+
+    // Key id new rows are written with, None while encryption is off
+    pub fn current_id(&self) -> Option<&str> {
+        self.keys.first().map(|(id, _)| id.as_str())
+    }
+
+    pub fn has_key(&self, key_id: &str) -> bool {
+        self.keys.iter().any(|(id, _)| id == key_id)
+    }
+
+    // base64(nonce || ciphertext), bound to the column and row it is stored in,
+    // so a sealed value copied into another row does not open there
+    pub fn seal(&self, column: &str, row: &str, plaintext: &str) -> Result<String, String> {
+        if self.keys.is_empty() {
+            return Ok(plaintext.to_string());
+        }
+
+        self.seal_bytes(column, row, plaintext.as_bytes())
+    }
+
+    // Like seal, for values that are not text, e.g. compressed ones; needs a key
+    pub fn seal_bytes(&self, column: &str, row: &str, plaintext: &[u8]) -> Result<String, String> {
+        let (_, cipher) = self.keys
+            .first()
+            .ok_or_else(|| format!("No encryption key to seal {} with", column))?;
+
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad: associated_data(column, row).as_bytes() })
+            .map_err(|_| format!("Cannot encrypt {}", column))?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+
+        Ok(STANDARD.encode(sealed))
+    }
+
+    // Rows without a key id are plaintext, e.g. from before encryption was switched on
+    pub fn open(&self, column: &str, row: &str, value: String, key_id: Option<&str>) -> Result<String, String> {
+        let Some(key_id) = key_id else {
+            return Ok(value);
+        };
+
+        String::from_utf8(self.open_bytes(column, row, &value, key_id)?)
+            .map_err(|_| format!("Decrypted {} is not valid UTF-8", column))
+    }
+
+    pub fn open_bytes(&self, column: &str, row: &str, value: &str, key_id: &str) -> Result<Vec<u8>, String> {
+        let cipher = self.keys
+            .iter()
+            .find(|(id, _)| id == key_id)
+            .map(|(_, cipher)| cipher)
+            .ok_or_else(|| format!("Encryption key {} is not configured", key_id))?;
+
+        let sealed = STANDARD
+            .decode(value)
+            .map_err(|_| format!("Encrypted {} is not valid base64", column))?;
+
+        if sealed.len() < NONCE_LEN {
+            return Err(format!("Encrypted {} is truncated", column));
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        cipher
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: associated_data(column, row).as_bytes() })
+            .map_err(|_| format!("Cannot decrypt {} with key {}", column, key_id))
+    }
+}
+
+fn associated_data(column: &str, row: &str) -> String {
+    format!("{}:{}", column, row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> String {
+        STANDARD.encode([byte; KEY_LEN])
+    }
+
+    fn keyring(entries: &[(&str, u8)]) -> Keyring {
+        let entries: Vec<String> = entries.iter().map(|&(id, byte)| format!("{}:{}", id, key(byte))).collect();
+        Keyring::parse(&entries.join("\n")).expect("valid keys")
+    }
+
+    #[test]
+    fn seal_and_open_round_trip() {
+        let keyring = keyring(&[("k1", 1)]);
+        let sealed = keyring.seal("content", "paste-1", "secret notes").unwrap();
+
+        assert_ne!(sealed, "secret notes");
+        assert_eq!(keyring.open("content", "paste-1", sealed, Some("k1")).unwrap(), "secret notes");
+    }
+
+    #[test]
+    fn sealed_values_only_open_in_their_own_row_and_column() {
+        let keyring = keyring(&[("k1", 1)]);
+        let sealed = keyring.seal("content", "paste-1", "secret notes").unwrap();
+
+        assert_eq!(
+            keyring.open("content", "paste-2", sealed.clone(), Some("k1")).unwrap_err(),
+            "Cannot decrypt content with key k1"
+        );
+        assert!(keyring.open("title", "paste-1", sealed, Some("k1")).is_err());
+    }
+
+    #[test]
+    fn opening_with_a_missing_key_id_fails() {
+        let sealed = keyring(&[("old", 1)]).seal("content", "paste-1", "secret notes").unwrap();
+        let rotated = keyring(&[("new", 2)]);
+
+        assert!(!rotated.has_key("old"));
+        assert_eq!(
+            rotated.open("content", "paste-1", sealed, Some("old")).unwrap_err(),
+            "Encryption key old is not configured"
+        );
+    }
+
+    #[test]
+    fn first_key_seals_and_older_keys_still_open() {
+        let sealed = keyring(&[("old", 1)]).seal("content", "paste-1", "secret notes").unwrap();
+        let rotated = keyring(&[("new", 2), ("old", 1)]);
+
+        assert_eq!(rotated.current_id(), Some("new"));
+        assert_eq!(rotated.open("content", "paste-1", sealed, Some("old")).unwrap(), "secret notes");
+    }
+
+    #[test]
+    fn rows_without_a_key_id_are_plaintext() {
+        let keyring = keyring(&[("k1", 1)]);
+        assert_eq!(keyring.open("content", "paste-1", "legacy".into(), None).unwrap(), "legacy");
+
+        let disabled = Keyring::default();
+        assert_eq!(disabled.current_id(), None);
+        assert_eq!(disabled.seal("content", "paste-1", "plain").unwrap(), "plain");
+        assert!(disabled.seal_bytes("content", "paste-1", b"plain").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_bad_entries() {
+        assert!(Keyring::parse("no separator").is_err());
+        assert!(Keyring::parse(&format!("bad id!:{}", key(1))).is_err());
+        assert!(Keyring::parse("k1:not base64").is_err());
+        assert_eq!(
+            Keyring::parse(&format!("k1:{}", STANDARD.encode([1u8; 16]))).err().unwrap(),
+            "Encryption key k1 must be 32 bytes"
+        );
+        assert_eq!(
+            Keyring::parse(&format!("k1:{}\nk1:{}", key(1), key(2))).err().unwrap(),
+            "Duplicate encryption key id: k1"
+        );
+        assert!(Keyring::parse(&format!("# rotated out\n\n k1 : {} ", key(1))).unwrap().has_key("k1"));
+    }
+}
@@ -1,15 +1,16 @@
 use std::collections::HashMap;
 use tokio::sync::RwLock;
 use async_trait::async_trait;
-use crate::bin::{Paste, BinError};
+use crate::bin::{Paste, PasteFile, PasteRevision, RevisionInfo, BinError, is_expired};
 use crate::cal::{Event, CalError};
-use crate::ask::{Survey, StoredVote, AskError};
+use crate::ask::{Survey, StoredVote, WriteIn, AskError, place_write_ins};
 use crate::storage::PasteStore;
 use crate::storage::EventStore;
 use crate::storage::SurveyStore;
 
 pub struct MemoryPasteStore {
     pastes: RwLock<HashMap<String, Paste>>,
+    revisions: RwLock<HashMap<String, Vec<PasteRevision>>>, // always locked after pastes
 }
 
 pub struct MemoryEventStore {
@@ -24,6 +25,7 @@ impl MemoryPasteStore {
     pub fn new() -> Self {
         Self {
             pastes: RwLock::new(HashMap::new()),
+            revisions: RwLock::new(HashMap::new()),
         }
     }
 }
@@ -48,6 +50,14 @@ impl MemorySurveyStore {
 impl PasteStore for MemoryPasteStore {
     async fn create_paste(&self, paste: Paste) -> Result<(), BinError> {
         let mut pastes = self.pastes.write().await;
+        let mut revisions = self.revisions.write().await;
+
+        revisions.insert(paste.id.clone(), vec![PasteRevision {
+            number: paste.revision,
+            content: paste.content.clone(),
+            files: paste.files.clone(),
+            created_at: paste.created_at,
+        }]);
         pastes.insert(paste.id.clone(), paste);
         Ok(())
     }
@@ -57,24 +67,100 @@ impl PasteStore for MemoryPasteStore {
         pastes.get(id).cloned().ok_or(BinError::NotFound)
     }
 
-    async fn consume_paste(&self, id: &str) -> Result<Paste, BinError> {
+    async fn consume_paste(&self, id: &str, uses: u32) -> Result<Paste, BinError> {
         let mut pastes = self.pastes.write().await;
 
-        let mut paste = pastes.remove(id).ok_or(BinError::NotFound)?;
-
-        if paste.uses > 0 {
-            paste.uses -= 1;
+        if pastes.get(id).ok_or(BinError::NotFound)?.uses < uses {
+            return Err(BinError::InvalidInput("Not enough uses left".into()));
         }
 
+        let mut paste = pastes.remove(id).ok_or(BinError::NotFound)?;
+        paste.uses -= uses;
+
         let exhausted = paste.uses == 0;
 
         if !exhausted {
             pastes.insert(id.to_string(), paste.clone());
+        } else {
+            self.revisions.write().await.remove(id);
         }
 
         Ok(paste)
     } 
 
+    async fn update_paste(&self, paste: Paste, uses: Option<u32>, revision: Option<(String, Vec<PasteFile>)>) -> Result<(), BinError> {
+        let mut pastes = self.pastes.write().await;
+        let mut revisions = self.revisions.write().await;
+        let existing = pastes.get_mut(&paste.id).ok_or(BinError::NotFound)?;
+
+        existing.password_hash = paste.password_hash;
+        existing.expiry = paste.expiry;
+        existing.uses = uses.unwrap_or(existing.uses);
+        existing.language = paste.language;
+        existing.filename = paste.filename;
+        existing.forks_consume = paste.forks_consume;
+
+        if let Some((content, files)) = revision {
+            existing.revision += 1;
+            existing.content = content.clone();
+            existing.files = files.clone();
+
+            revisions.entry(paste.id).or_default().push(PasteRevision {
+                number: existing.revision,
+                content,
+                files,
+                created_at: std::time::SystemTime::now(),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn get_revision(&self, id: &str, number: u32) -> Result<PasteRevision, BinError> {
+        let revisions = self.revisions.read().await;
+
+        revisions
+            .get(id)
+            .ok_or(BinError::NotFound)?
+            .iter()
+            .find(|revision| revision.number == number)
+            .cloned()
+            .ok_or(BinError::RevisionNotFound)
+    }
+
+    async fn list_revisions(&self, id: &str) -> Result<Vec<RevisionInfo>, BinError> {
+        let revisions = self.revisions.read().await;
+
+        Ok(revisions
+            .get(id)
+            .ok_or(BinError::NotFound)?
+            .iter()
+            .map(|revision| RevisionInfo {
+                number: revision.number,
+                created_at: revision.created_at.into(),
+            })
+            .collect())
+    }
+
+    async fn list_forks(&self, id: &str) -> Result<Vec<String>, BinError> {
+        let pastes = self.pastes.read().await;
+
+        let mut forks: Vec<&Paste> = pastes
+            .values()
+            .filter(|paste| paste.parent_id.as_deref() == Some(id) && !is_expired(paste))
+            .collect();
+        forks.sort_by_key(|paste| paste.created_at);
+
+        Ok(forks.into_iter().map(|paste| paste.id.clone()).collect())
+    }
+
+    async fn delete_paste(&self, id: &str) -> Result<(), BinError> {
+        let mut pastes = self.pastes.write().await;
+        pastes.remove(id).ok_or(BinError::NotFound)?;
+        self.revisions.write().await.remove(id);
+        Ok(())
+    }
+
     async fn cleanup_expired(&self) -> Result<(), Box<dyn std::error::Error + Send>> {
         Ok(())
     }
@@ -111,6 +197,21 @@ impl EventStore for MemoryEventStore {
         Ok(event)
     }
 
+    async fn update_event(&self, event: Event, uses: Option<u32>) -> Result<(), CalError> {
+        let mut events = self.events.write().await;
+        let existing = events.get_mut(&event.id).ok_or(CalError::NotFound)?;
+
+        let uses = uses.unwrap_or(existing.uses);
+        *existing = Event { uses, ..event };
+        Ok(())
+    }
+
+    async fn delete_event(&self, id: &str) -> Result<(), CalError> {
+        let mut events = self.events.write().await;
+        events.remove(id).ok_or(CalError::NotFound)?;
+        Ok(())
+    }
+
     async fn cleanup_expired(&self) -> Result<(), Box<dyn std::error::Error + Send>> {
         Ok(())
     }
@@ -129,11 +230,23 @@ impl SurveyStore for MemorySurveyStore {
         surveys.get(id).cloned().ok_or(AskError::NotFound)
     }
 
-    async fn add_vote(&self, id: &str, vote: StoredVote) -> Result<Survey, AskError> {
+    async fn add_vote(&self, id: &str, mut vote: StoredVote, write_ins: Vec<WriteIn>) -> Result<Survey, AskError> {
         let mut surveys = self.surveys.write().await;
     
         let updated = {
             let survey = surveys.get_mut(id).ok_or(AskError::NotFound)?;
+
+            if vote.participant.is_some() && survey.votes.iter().any(|v| v.participant == vote.participant) {
+                return Err(AskError::AlreadyVoted);
+            }
+
+            // Placed on a copy, a rejected write-in leaves the survey untouched
+            if !write_ins.is_empty() {
+                let mut content = survey.content.clone();
+                place_write_ins(&mut content, &mut vote, &write_ins)?;
+                survey.content = content;
+                survey.version += 1;
+            }
             survey.votes.push(vote);
             survey.clone()
         };
@@ -141,6 +254,64 @@ impl SurveyStore for MemorySurveyStore {
         Ok(updated)
     }
 
+    async fn update_vote(&self, id: &str, mut vote: StoredVote, write_ins: Vec<WriteIn>) -> Result<Survey, AskError> {
+        let mut surveys = self.surveys.write().await;
+        let survey = surveys.get_mut(id).ok_or(AskError::NotFound)?;
+
+        let position = survey
+            .votes
+            .iter()
+            .position(|v| v.ballot_id.is_some() && v.ballot_id == vote.ballot_id)
+            .ok_or(AskError::NotFound)?;
+
+        if !write_ins.is_empty() {
+            let mut content = survey.content.clone();
+            place_write_ins(&mut content, &mut vote, &write_ins)?;
+            survey.content = content;
+            survey.version += 1;
+        }
+        survey.votes[position] = vote;
+
+        Ok(survey.clone())
+    }
+
+    async fn delete_vote(&self, id: &str, ballot_id: &str) -> Result<Survey, AskError> {
+        let mut surveys = self.surveys.write().await;
+        let survey = surveys.get_mut(id).ok_or(AskError::NotFound)?;
+
+        let position = survey
+            .votes
+            .iter()
+            .position(|v| v.ballot_id.as_deref() == Some(ballot_id))
+            .ok_or(AskError::NotFound)?;
+        survey.votes.remove(position);
+
+        Ok(survey.clone())
+    }
+
+    async fn update_survey(&self, survey: Survey) -> Result<(), AskError> {
+        let mut surveys = self.surveys.write().await;
+        let existing = surveys.get_mut(&survey.id).ok_or(AskError::NotFound)?;
+
+        // Written meanwhile, e.g. by a write-in
+        if existing.version != survey.version {
+            return Err(AskError::Conflict);
+        }
+
+        // Votes are managed through add_vote only
+        existing.content = survey.content;
+        existing.password_hash = survey.password_hash;
+        existing.expiry = survey.expiry;
+        existing.version += 1;
+        Ok(())
+    }
+
+    async fn delete_survey(&self, id: &str) -> Result<(), AskError> {
+        let mut surveys = self.surveys.write().await;
+        surveys.remove(id).ok_or(AskError::NotFound)?;
+        Ok(())
+    }
+
     async fn cleanup_expired(&self) -> Result<(), Box<dyn std::error::Error + Send>> {
         Ok(())
     }
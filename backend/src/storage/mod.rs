@@ -1,17 +1,25 @@
 use async_trait::async_trait;
-use crate::bin::{Paste, BinError};
+use crate::bin::{Paste, PasteFile, PasteRevision, RevisionInfo, BinError};
 use crate::cal::{Event, CalError};
-use crate::ask::{Survey, StoredVote, AskError};
+use crate::ask::{Survey, StoredVote, WriteIn, AskError};
 
 pub mod memory;
 pub mod sqlite;
 pub mod ban;
+pub mod attachments;
+pub mod keyring;
+pub mod codec;
 
 #[async_trait]
 pub trait PasteStore: Send + Sync {
     async fn create_paste(&self, paste: Paste) -> Result<(), BinError>;
     async fn get_paste(&self, id: &str) -> Result<Paste, BinError>;
-    async fn consume_paste(&self, id: &str) -> Result<Paste, BinError>;
+    async fn consume_paste(&self, id: &str, uses: u32) -> Result<Paste, BinError>; // takes `uses` of the uses left, none if fewer are left
+    async fn update_paste(&self, paste: Paste, uses: Option<u32>, revision: Option<(String, Vec<PasteFile>)>) -> Result<(), BinError>; // settings, plus new content as the next revision
+    async fn get_revision(&self, id: &str, number: u32) -> Result<PasteRevision, BinError>;
+    async fn list_revisions(&self, id: &str) -> Result<Vec<RevisionInfo>, BinError>;
+    async fn list_forks(&self, id: &str) -> Result<Vec<String>, BinError>; // unexpired pastes forked from this one
+    async fn delete_paste(&self, id: &str) -> Result<(), BinError>;
     async fn cleanup_expired(&self) -> Result<(), Box<dyn std::error::Error + Send>>;
 }
 
@@ -20,6 +28,8 @@ pub trait EventStore: Send + Sync {
     async fn create_event(&self, event: Event) -> Result<(), CalError>;
     async fn get_event(&self, id: &str) -> Result<Event, CalError>;
     async fn consume_event(&self, id: &str) -> Result<Event, CalError>;
+    async fn update_event(&self, event: Event, uses: Option<u32>) -> Result<(), CalError>; // uses stay as views left them unless set
+    async fn delete_event(&self, id: &str) -> Result<(), CalError>;
     async fn cleanup_expired(&self) -> Result<(), Box<dyn std::error::Error + Send>>;
 }
 
@@ -27,7 +37,11 @@ pub trait EventStore: Send + Sync {
 pub trait SurveyStore: Send + Sync {
     async fn create_survey(&self, survey: Survey) -> Result<(), AskError>;
     async fn get_survey(&self, id: &str) -> Result<Survey, AskError>;
-    async fn add_vote(&self, id: &str, vote: StoredVote) -> Result<Survey, AskError>;
+    async fn add_vote(&self, id: &str, vote: StoredVote, write_ins: Vec<WriteIn>) -> Result<Survey, AskError>; // write-ins are placed in the same transaction
+    async fn update_vote(&self, id: &str, vote: StoredVote, write_ins: Vec<WriteIn>) -> Result<Survey, AskError>;
+    async fn delete_vote(&self, id: &str, ballot_id: &str) -> Result<Survey, AskError>;
+    async fn update_survey(&self, survey: Survey) -> Result<(), AskError>;
+    async fn delete_survey(&self, id: &str) -> Result<(), AskError>;
     async fn cleanup_expired(&self) -> Result<(), Box<dyn std::error::Error + Send>>;
     async fn cleanup_loose_votes(&self) -> Result<(), Box<dyn std::error::Error + Send>>;
 }
@@ -1,22 +1,38 @@
 use std::sync::{Arc, Mutex};
 use std::time::UNIX_EPOCH;
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
+use rusqlite::types::Value;
 use async_trait::async_trait;
+use rand::RngCore;
+use uuid::Uuid;
 
-use crate::bin::{Paste, BinError};
+use crate::bin::{Attachment, Paste, PasteFile, PasteRevision, RevisionInfo, BinError};
 use crate::cal::{Event, EventContent, CalError};
-use crate::ask::{Survey, SurveyContent, StoredVote, AskError};
+use crate::ask::{Survey, SurveyContent, StoredVote, WriteIn, AskError, place_write_ins};
 use crate::storage::PasteStore;
 use crate::storage::EventStore;
 use crate::storage::SurveyStore;
 use crate::storage::ban::BanStore;
+use crate::storage::keyring::Keyring;
+use crate::storage::codec;
 
 pub struct SqliteStorage {
     conn: Arc<Mutex<rusqlite::Connection>>,
+    keyring: Arc<Keyring>,
 }
 
+// Columns holding user content, encrypted at rest when a key is configured; (table, row id, columns, compressed).
+// Sealed values are bound to the row id, so it has to stay stable for the life of the row.
+const SEALED_COLUMNS: &[(&str, &str, &[&str], bool)] = &[
+    ("pastes", "id", &["content", "files"], true),
+    ("paste_revisions", "paste_id || '/' || number", &["content", "files"], true),
+    ("events", "id", &["content"], false),
+    ("surveys", "id", &["content"], false),
+    ("survey_votes", "survey_id || '/' || ballot_id", &["vote"], false),
+];
+
 impl SqliteStorage {
-    pub fn new(path: &str) -> Result<Self, rusqlite::Error> {
+    pub fn new(path: &str, keyring: Keyring) -> Result<Self, rusqlite::Error> {
         let conn = rusqlite::Connection::open(path)?;
 
         conn.execute_batch(
@@ -27,7 +43,16 @@ impl SqliteStorage {
                 password_hash TEXT,
                 expiry INTEGER NOT NULL,
                 uses INTEGER NOT NULL,
-                created_at INTEGER NOT NULL
+                created_at INTEGER NOT NULL,
+                manage_token_hash TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS paste_revisions (
+                paste_id TEXT NOT NULL,
+                number INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (paste_id, number)
             );
 
             CREATE TABLE IF NOT EXISTS events (
@@ -37,20 +62,24 @@ impl SqliteStorage {
                 expiry INTEGER NOT NULL,
                 uses INTEGER NOT NULL,
                 created_at INTEGER NOT NULL,
-                created_utc INTEGER NOT NULL
+                created_utc INTEGER NOT NULL,
+                manage_token_hash TEXT
             );
 
             CREATE TABLE IF NOT EXISTS surveys (
                 id TEXT PRIMARY KEY,
-                content TEXT NOT NULL,
+                content TEXT NOT NULL, -- JSON, one flat question or an ordered questions list
                 password_hash TEXT,
                 expiry INTEGER NOT NULL,
-                created_at INTEGER NOT NULL
+                created_at INTEGER NOT NULL,
+                manage_token_hash TEXT
             );
 
             CREATE TABLE IF NOT EXISTS survey_votes (
                 survey_id TEXT NOT NULL,
-                vote TEXT NOT NULL
+                vote TEXT NOT NULL, -- JSON, first answer flat, further answers keyed by question
+                ballot_id TEXT,
+                participant TEXT -- per-survey voter fingerprint, only in one-vote-per-participant mode
             );
 
             CREATE TABLE IF NOT EXISTS bans (
@@ -62,13 +91,461 @@ impl SqliteStorage {
             );
 
             CREATE INDEX IF NOT EXISTS idx_bans_banned_until ON bans(banned_until);
+
+            CREATE TABLE IF NOT EXISTS secrets (
+                name TEXT PRIMARY KEY,
+                value BLOB NOT NULL
+            );
             "#
         )?;
 
+        // Columns added after the initial schema
+        add_column_if_missing(&conn, "pastes", "manage_token_hash", "TEXT")?;
+        add_column_if_missing(&conn, "events", "manage_token_hash", "TEXT")?;
+        add_column_if_missing(&conn, "surveys", "manage_token_hash", "TEXT")?;
+        add_column_if_missing(&conn, "survey_votes", "ballot_id", "TEXT")?;
+        add_column_if_missing(&conn, "survey_votes", "participant", "TEXT")?;
+        add_column_if_missing(&conn, "pastes", "revision", "INTEGER NOT NULL DEFAULT 1")?;
+        add_column_if_missing(&conn, "pastes", "language", "TEXT")?;
+        add_column_if_missing(&conn, "pastes", "filename", "TEXT")?;
+        add_column_if_missing(&conn, "pastes", "files", "TEXT")?; // JSON, multi-file pastes only
+        add_column_if_missing(&conn, "paste_revisions", "files", "TEXT")?;
+        add_column_if_missing(&conn, "pastes", "attachment", "TEXT")?; // JSON, file name, type and size
+        add_column_if_missing(&conn, "pastes", "encrypted", "INTEGER NOT NULL DEFAULT 0")?; // 0 or 1
+        add_column_if_missing(&conn, "pastes", "parent_id", "TEXT")?;
+        add_column_if_missing(&conn, "pastes", "forks_consume", "INTEGER NOT NULL DEFAULT 0")?; // 0 or 1
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_pastes_parent_id ON pastes(parent_id)", [])?;
+
+        // Key of the sealed columns in the same row, NULL = plaintext
+        for (table, _, _, _) in SEALED_COLUMNS {
+            add_column_if_missing(&conn, table, "key_id", "TEXT")?;
+        }
+
+        // One ballot per participant; later duplicates keep their votes but lose the link to the voter
+        conn.execute(
+            r#"
+            UPDATE survey_votes SET participant = NULL
+            WHERE participant IS NOT NULL AND rowid NOT IN (
+                SELECT MIN(rowid) FROM survey_votes WHERE participant IS NOT NULL GROUP BY survey_id, participant
+            )
+            "#,
+            [],
+        )?;
+        conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_survey_votes_participant ON survey_votes(survey_id, participant)",
+            [],
+        )?;
+
+        // Codec of content and files in the same row, NULL = uncompressed TEXT
+        add_column_if_missing(&conn, "pastes", "codec", "TEXT")?;
+        add_column_if_missing(&conn, "paste_revisions", "codec", "TEXT")?;
+
+        // Compare-and-swap counter for survey content, see update_survey
+        add_column_if_missing(&conn, "surveys", "version", "INTEGER NOT NULL DEFAULT 0")?;
+
+        // Votes from before ballot ids get one, sealed votes are bound to it
+        let unnumbered = conn
+            .prepare("SELECT rowid FROM survey_votes WHERE ballot_id IS NULL")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for rowid in unnumbered {
+            conn.execute(
+                "UPDATE survey_votes SET ballot_id = ?1 WHERE rowid = ?2",
+                params![Uuid::new_v4().to_string(), rowid],
+            )?;
+        }
+
+        // Pastes from before revisions start their history with the current content
+        conn.execute(
+            r#"
+            INSERT INTO paste_revisions (paste_id, number, content, created_at, key_id, codec)
+            SELECT id, revision, content, created_at, key_id, codec FROM pastes
+            WHERE id NOT IN (SELECT paste_id FROM paste_revisions)
+            "#,
+            [],
+        )?;
+
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            keyring: Arc::new(keyring),
         })
     }
+
+    // Random secret generated on first use and kept across restarts, for signatures that have to outlive the process
+    pub fn secret(&self, name: &str) -> Result<[u8; 32], String> {
+        let conn = self.conn
+            .lock()
+            .map_err(|_| "database mutex poisoned".to_string())?;
+
+        let mut fresh = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut fresh);
+
+        conn.execute(
+            "INSERT OR IGNORE INTO secrets (name, value) VALUES (?1, ?2)",
+            params![name, fresh.to_vec()],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let value: Vec<u8> = conn
+            .query_row("SELECT value FROM secrets WHERE name = ?1", params![name], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+
+        value.try_into().map_err(|_| format!("stored secret {} is not 32 bytes", name))
+    }
+
+    // Key ids referenced by stored rows that the keyring cannot open
+    pub fn missing_keys(&self) -> Result<Vec<String>, String> {
+        let conn = self.conn
+            .lock()
+            .map_err(|_| "database mutex poisoned".to_string())?;
+        let mut missing = Vec::new();
+
+        for (table, _, _, _) in SEALED_COLUMNS {
+            let mut stmt = conn
+                .prepare(&format!("SELECT DISTINCT key_id FROM {} WHERE key_id IS NOT NULL", table))
+                .map_err(|e| e.to_string())?;
+
+            let key_ids = stmt
+                .query_map([], |row| row.get::<_, String>(0))
+                .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+                .map_err(|e| e.to_string())?;
+
+            for key_id in key_ids {
+                if !self.keyring.has_key(&key_id) && !missing.contains(&key_id) {
+                    missing.push(key_id);
+                }
+            }
+        }
+
+        Ok(missing)
+    }
+
+    // Moves every sealed row to the current key, or back to plaintext without one.
+    // Meant for `polly-backend reencrypt` while the server is stopped; returns the rows rewritten.
+    pub fn reencrypt(&self) -> Result<usize, String> {
+        let mut conn = self.conn
+            .lock()
+            .map_err(|_| "database mutex poisoned".to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let current = self.keyring.current_id();
+        let mut rewritten = 0;
+
+        for (table, row_id, columns, compressed) in SEALED_COLUMNS {
+            let rows = {
+                let mut stmt = tx
+                    .prepare(&format!(
+                        "SELECT rowid, {}, key_id, {} FROM {} WHERE key_id IS NOT ?1",
+                        row_id, columns.join(", "), table
+                    ))
+                    .map_err(|e| e.to_string())?;
+
+                let rows = stmt.query_map([current], |row| {
+                    let values = (0..columns.len())
+                        .map(|i| row.get::<_, Value>(i + 3))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?, values))
+                }).map_err(|e| e.to_string())?;
+
+                rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?
+            };
+
+            for (rowid, row_id, key_id, values) in rows {
+                let mut sealed = Vec::with_capacity(values.len());
+
+                for (column, value) in columns.iter().zip(values) {
+                    let column = format!("{}.{}", table, column);
+                    let plaintext = match (value, key_id.as_deref()) {
+                        (Value::Null, _) => {
+                            sealed.push(Value::Null);
+                            continue;
+                        }
+                        (Value::Text(value), Some(key_id)) => self.keyring.open_bytes(&column, &row_id, &value, key_id)?,
+                        (Value::Text(value), None) => value.into_bytes(),
+                        (Value::Blob(value), _) => value,
+                        _ => return Err(format!("Unexpected value in {}", column)),
+                    };
+
+                    sealed.push(seal_value(&self.keyring, &column, &row_id, plaintext, *compressed)?);
+                }
+
+                let assignments: Vec<String> = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| format!("{} = ?{}", column, i + 3))
+                    .collect();
+
+                let mut params: Vec<Value> = vec![current.map(str::to_string).into(), rowid.into()];
+                params.extend(sealed);
+
+                tx.execute(
+                    &format!("UPDATE {} SET key_id = ?1, {} WHERE rowid = ?2", table, assignments.join(", ")),
+                    rusqlite::params_from_iter(params),
+                ).map_err(|e| e.to_string())?;
+
+                rewritten += 1;
+            }
+        }
+
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(rewritten)
+    }
+}
+
+/*
+ * --- Helper functions ---
+ */
+
+fn add_column_if_missing(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+
+    Ok(())
+}
+
+const PASTE_COLUMNS: &str = "id, content, password_hash, expiry, uses, created_at, manage_token_hash, revision, language, filename, files, attachment, encrypted, key_id, parent_id, forks_consume, codec";
+const REVISION_COLUMNS: &str = "number, content, created_at, files, key_id, codec";
+const EVENT_COLUMNS: &str = "id, content, password_hash, expiry, uses, created_at, created_utc, manage_token_hash, key_id";
+const SURVEY_COLUMNS: &str = "id, content, password_hash, expiry, created_at, manage_token_hash, key_id, version";
+const VOTE_COLUMNS: &str = "vote, ballot_id, participant, key_id";
+
+// Row ids sealed values are bound to, matching the expressions in SEALED_COLUMNS
+fn revision_row(paste_id: &str, number: u32) -> String {
+    format!("{}/{}", paste_id, number)
+}
+
+fn vote_row(survey_id: &str, ballot_id: &str) -> String {
+    format!("{}/{}", survey_id, ballot_id)
+}
+
+// Decrypts a sealed column with the key named in the row
+fn open_column(
+    row: &rusqlite::Row,
+    keyring: &Keyring,
+    column: &str,
+    row_id: &str,
+    index: usize,
+    key_index: usize,
+) -> Result<Option<String>, rusqlite::Error> {
+    let value: Option<String> = row.get(index)?;
+    let key_id: Option<String> = row.get(key_index)?;
+
+    value
+        .map(|value| keyring.open(column, row_id, value, key_id.as_deref()))
+        .transpose()
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into()))
+}
+
+// Decrypts, then decompresses with the codec named in the row
+fn open_compressed_column(
+    row: &rusqlite::Row,
+    keyring: &Keyring,
+    column: &str,
+    row_id: &str,
+    index: usize,
+    key_index: usize,
+    codec_index: usize,
+) -> Result<Option<String>, rusqlite::Error> {
+    let key_id: Option<String> = row.get(key_index)?;
+    let codec: Option<String> = row.get(codec_index)?;
+
+    let bytes = match (row.get::<_, Value>(index)?, key_id) {
+        (Value::Null, _) => return Ok(None),
+        (Value::Text(value), Some(key_id)) => keyring.open_bytes(column, row_id, &value, &key_id),
+        (Value::Text(value), None) => Ok(value.into_bytes()),
+        (Value::Blob(value), _) => Ok(value),
+        _ => Err(format!("Unexpected value in {}", column)),
+    };
+
+    bytes
+        .and_then(|bytes| codec::decompress(codec.as_deref(), bytes))
+        .map(Some)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Blob, e.into()))
+}
+
+// Sealed as base64 TEXT with a key; without one compressed columns are BLOBs, the rest stays TEXT
+fn seal_value(keyring: &Keyring, column: &str, row_id: &str, plaintext: Vec<u8>, compressed: bool) -> Result<Value, String> {
+    if keyring.current_id().is_some() {
+        return keyring.seal_bytes(column, row_id, &plaintext).map(Value::Text);
+    }
+
+    if compressed {
+        return Ok(Value::Blob(plaintext));
+    }
+
+    String::from_utf8(plaintext)
+        .map(Value::Text)
+        .map_err(|_| format!("{} is not valid UTF-8", column))
+}
+
+// Compressed first, encryption would leave nothing to compress
+fn compress_column(keyring: &Keyring, column: &str, row_id: &str, text: &str) -> Result<Value, BinError> {
+    let compressed = codec::compress(text).map_err(BinError::Internal)?;
+    seal_value(keyring, column, row_id, compressed, true).map_err(BinError::Internal)
+}
+
+fn paste_from_row(row: &rusqlite::Row, keyring: &Keyring) -> Result<Paste, rusqlite::Error> {
+    let id: String = row.get(0)?;
+
+    Ok(Paste {
+        content: open_compressed_column(row, keyring, "pastes.content", &id, 1, 13, 16)?.unwrap_or_default(),
+        password_hash: row.get(2)?,
+        expiry: row.get::<_, i64>(3)? as u32,
+        uses: row.get::<_, i64>(4)? as u32,
+        created_at: UNIX_EPOCH + std::time::Duration::from_secs(row.get::<_, i64>(5)? as u64),
+        manage_token_hash: row.get(6)?,
+        revision: row.get::<_, i64>(7)? as u32,
+        language: row.get(8)?,
+        filename: row.get(9)?,
+        files: files_from_column(open_compressed_column(row, keyring, "pastes.files", &id, 10, 13, 16)?)?,
+        attachment: attachment_from_column(row.get(11)?)?,
+        encrypted: row.get::<_, i64>(12)? != 0,
+        parent_id: row.get(14)?,
+        forks_consume: row.get::<_, i64>(15)? != 0,
+        id,
+    })
+}
+
+fn revision_from_row(row: &rusqlite::Row, keyring: &Keyring, paste_id: &str) -> Result<PasteRevision, rusqlite::Error> {
+    let number = row.get::<_, i64>(0)? as u32;
+    let row_id = revision_row(paste_id, number);
+
+    Ok(PasteRevision {
+        number,
+        content: open_compressed_column(row, keyring, "paste_revisions.content", &row_id, 1, 4, 5)?.unwrap_or_default(),
+        files: files_from_column(open_compressed_column(row, keyring, "paste_revisions.files", &row_id, 3, 4, 5)?)?,
+        created_at: UNIX_EPOCH + std::time::Duration::from_secs(row.get::<_, i64>(2)? as u64),
+    })
+}
+
+// Single-file pastes keep the plain content column and leave files NULL
+fn files_to_column(keyring: &Keyring, column: &str, row_id: &str, files: &[PasteFile]) -> Result<Value, BinError> {
+    if files.is_empty() {
+        return Ok(Value::Null);
+    }
+
+    let files = serde_json::to_string(files).map_err(|e| BinError::Internal(e.to_string()))?;
+    compress_column(keyring, column, row_id, &files)
+}
+
+fn attachment_to_column(attachment: &Option<Attachment>) -> Result<Option<String>, BinError> {
+    attachment
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| BinError::Internal(e.to_string()))
+}
+
+fn attachment_from_column(attachment: Option<String>) -> Result<Option<Attachment>, rusqlite::Error> {
+    attachment
+        .map(|attachment| serde_json::from_str(&attachment))
+        .transpose()
+        .map_err(|_| rusqlite::Error::InvalidQuery)
+}
+
+fn files_from_column(files: Option<String>) -> Result<Vec<PasteFile>, rusqlite::Error> {
+    match files {
+        Some(files) => serde_json::from_str(&files).map_err(|_| rusqlite::Error::InvalidQuery),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn event_from_row(row: &rusqlite::Row, keyring: &Keyring) -> Result<Event, rusqlite::Error> {
+    let id: String = row.get(0)?;
+    let content = open_column(row, keyring, "events.content", &id, 1, 8)?.unwrap_or_default();
+    let content: EventContent = serde_json::from_str(&content)
+        .map_err(|_| rusqlite::Error::InvalidQuery)?;
+    let created_utc_ts: i64 = row.get(6)?;
+    let created_utc = chrono::DateTime::<chrono::Utc>::from_timestamp(created_utc_ts, 0)
+        .ok_or(rusqlite::Error::InvalidQuery)?;
+
+    Ok(Event {
+        id,
+        content,
+        password_hash: row.get(2)?,
+        expiry: row.get::<_, i64>(3)? as u32,
+        uses: row.get::<_, i64>(4)? as u32,
+        created_at: UNIX_EPOCH + std::time::Duration::from_secs(row.get::<_, i64>(5)? as u64),
+        created_utc,
+        manage_token_hash: row.get(7)?,
+    })
+}
+
+fn survey_from_row(row: &rusqlite::Row, keyring: &Keyring) -> Result<Survey, rusqlite::Error> {
+    let id: String = row.get(0)?;
+    let content = open_column(row, keyring, "surveys.content", &id, 1, 6)?.unwrap_or_default();
+    let content: SurveyContent = serde_json::from_str(&content)
+        .map_err(|_| rusqlite::Error::InvalidQuery)?;
+
+    Ok(Survey {
+        id,
+        content,
+        password_hash: row.get(2)?,
+        expiry: row.get::<_, i64>(3)? as u32,
+        created_at: UNIX_EPOCH + std::time::Duration::from_secs(row.get::<_, i64>(4)? as u64),
+        votes: Vec::new(),
+        manage_token_hash: row.get(5)?,
+        version: row.get::<_, i64>(7)? as u64,
+    })
+}
+
+// Ballot ids and fingerprints live in their own columns and never end up in the vote JSON
+fn vote_from_row(row: &rusqlite::Row, keyring: &Keyring, survey_id: &str) -> Result<StoredVote, rusqlite::Error> {
+    let ballot_id: String = row.get(1)?;
+    let vote = open_column(row, keyring, "survey_votes.vote", &vote_row(survey_id, &ballot_id), 0, 3)?.unwrap_or_default();
+    let mut vote: StoredVote = serde_json::from_str(&vote)
+        .map_err(|_| rusqlite::Error::InvalidQuery)?;
+
+    vote.ballot_id = Some(ballot_id);
+    vote.participant = row.get(2)?;
+
+    Ok(vote)
+}
+
+// Items live inside the content JSON, so write-ins rewrite it within the caller's transaction
+fn place_write_ins_in(
+    conn: &rusqlite::Connection,
+    keyring: &Keyring,
+    id: &str,
+    vote: &mut StoredVote,
+    write_ins: &[WriteIn],
+) -> Result<(), AskError> {
+    if write_ins.is_empty() {
+        return Ok(());
+    }
+
+    let content = conn.query_row(
+        "SELECT content, key_id FROM surveys WHERE id = ?1",
+        [id],
+        |row| open_column(row, keyring, "surveys.content", id, 0, 1),
+    ).optional()?.ok_or(AskError::NotFound)?.unwrap_or_default();
+
+    let mut content: SurveyContent = serde_json::from_str(&content)
+        .map_err(|e| AskError::Internal(e.to_string()))?;
+    place_write_ins(&mut content, vote, write_ins)?;
+
+    let content = serde_json::to_string(&content)
+        .map_err(|e| AskError::InvalidInput(e.to_string()))?;
+    let content = keyring.seal("surveys.content", id, &content).map_err(AskError::Internal)?;
+    conn.execute(
+        "UPDATE surveys SET content = ?1, key_id = ?2, version = version + 1 WHERE id = ?3",
+        params![content, keyring.current_id(), id],
+    )?;
+
+    Ok(())
 }
 
 #[async_trait]
@@ -76,6 +553,7 @@ impl PasteStore for SqliteStorage {
     async fn create_paste(&self, paste: Paste) -> Result<(), BinError> {
         let paste = paste.clone();
         let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
 
         tokio::task::spawn_blocking(move || {
             let created_at = paste.created_at
@@ -83,25 +561,51 @@ impl PasteStore for SqliteStorage {
                 .map_err(|_| BinError::InvalidInput("Invalid timestamp".into()))?
                 .as_secs() as i64;
 
-            let conn = conn
+            let key_id = keyring.current_id();
+            let revision_id = revision_row(&paste.id, paste.revision);
+            let content = compress_column(&keyring, "pastes.content", &paste.id, &paste.content)?;
+            let files = files_to_column(&keyring, "pastes.files", &paste.id, &paste.files)?;
+            let revision_content = compress_column(&keyring, "paste_revisions.content", &revision_id, &paste.content)?;
+            let revision_files = files_to_column(&keyring, "paste_revisions.files", &revision_id, &paste.files)?;
+            let attachment = attachment_to_column(&paste.attachment)?;
+
+            let mut conn = conn
                 .lock()
                 .map_err(|_| BinError::Internal("database mutex poisoned".into()))?;
+            let tx = conn.transaction()?;
 
-            conn.execute(
+            tx.execute(
                 r#"
-                INSERT INTO pastes (id, content, password_hash, expiry, uses, created_at)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                INSERT INTO pastes (id, content, password_hash, expiry, uses, created_at, manage_token_hash, revision, language, filename, files, attachment, encrypted, key_id, parent_id, forks_consume, codec)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
                 "#,
-                (
-                    paste.id,
-                    paste.content,
+                params![
+                    &paste.id,
+                    content,
                     paste.password_hash,
                     paste.expiry as i64,
                     paste.uses as i64,
                     created_at,
-                ),
+                    paste.manage_token_hash,
+                    paste.revision as i64,
+                    paste.language,
+                    paste.filename,
+                    files,
+                    attachment,
+                    paste.encrypted as i64,
+                    key_id,
+                    paste.parent_id,
+                    paste.forks_consume as i64,
+                    codec::DEFLATE,
+                ],
+            )?;
+
+            tx.execute(
+                "INSERT INTO paste_revisions (paste_id, number, content, created_at, files, key_id, codec) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![&paste.id, paste.revision as i64, revision_content, created_at, revision_files, key_id, codec::DEFLATE],
             )?;
 
+            tx.commit()?;
             Ok::<(), BinError>(())
         })
         .await
@@ -111,6 +615,7 @@ impl PasteStore for SqliteStorage {
     async fn get_paste(&self, id: &str) -> Result<Paste, BinError> {
         let id = id.to_owned();
         let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
 
         tokio::task::spawn_blocking(move || {
             let conn = conn
@@ -118,23 +623,13 @@ impl PasteStore for SqliteStorage {
                 .map_err(|_| BinError::Internal("database mutex poisoned".into()))?;
 
             let mut stmt = conn.prepare(
-                r#"
-                SELECT id, content, password_hash, expiry, uses, created_at
-                FROM pastes WHERE id = ?1
-                "#
+                &format!("SELECT {} FROM pastes WHERE id = ?1", PASTE_COLUMNS)
             )?;
 
-            let paste = stmt.query_row([&id], |row| {
-                Ok(Paste {
-                    id: row.get(0)?,
-                    content: row.get(1)?,
-                    password_hash: row.get(2)?,
-                    expiry: row.get::<_, i64>(3)? as u32,
-                    uses: row.get::<_, i64>(4)? as u32,
-                    created_at: std::time::UNIX_EPOCH
-                        + std::time::Duration::from_secs(row.get::<_, i64>(5)? as u64),
-                })
-            }).map_err(|_| BinError::NotFound)?;
+            // Only a missing row is a 404, rows that fail to open are the operator's problem
+            let paste = stmt.query_row([&id], |row| paste_from_row(row, &keyring))
+                .optional()?
+                .ok_or(BinError::NotFound)?;
 
             Ok::<Paste, BinError>(paste)
         })
@@ -142,36 +637,29 @@ impl PasteStore for SqliteStorage {
         .map_err(|e| BinError::InvalidInput(e.to_string()))?
     }
 
-    async fn consume_paste(&self, id: &str) -> Result<Paste, BinError> {
+    async fn consume_paste(&self, id: &str, uses: u32) -> Result<Paste, BinError> {
         let id = id.to_owned();
         let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
 
         tokio::task::spawn_blocking(move || {
             let mut conn = conn.lock().unwrap();
             let tx = conn.transaction()?;
 
             let mut paste: Paste = tx.query_row(
-                "SELECT id, content, password_hash, expiry, uses, created_at FROM pastes WHERE id = ?1",
+                &format!("SELECT {} FROM pastes WHERE id = ?1", PASTE_COLUMNS),
                 [&id],
-                |row| {
-                    Ok(Paste {
-                        id: row.get(0)?,
-                        content: row.get(1)?,
-                        password_hash: row.get(2)?,
-                        expiry: row.get::<_, i64>(3)? as u32,
-                        uses: row.get::<_, i64>(4)? as u32,
-                        created_at: std::time::UNIX_EPOCH
-                            + std::time::Duration::from_secs(row.get::<_, i64>(5)? as u64),
-                    })
-                },
-            ).map_err(|_| BinError::NotFound)?;
-
-            if paste.uses > 0 {
-                paste.uses -= 1;
+                |row| paste_from_row(row, &keyring),
+            ).optional()?.ok_or(BinError::NotFound)?;
+
+            if paste.uses < uses {
+                return Err(BinError::InvalidInput("Not enough uses left".into()));
             }
+            paste.uses -= uses;
 
             if paste.uses == 0 {
                 tx.execute("DELETE FROM pastes WHERE id = ?1", [&id])?;
+                tx.execute("DELETE FROM paste_revisions WHERE paste_id = ?1", [&id])?;
             } else {
                 tx.execute(
                     "UPDATE pastes SET uses = ?1 WHERE id = ?2",
@@ -186,6 +674,173 @@ impl PasteStore for SqliteStorage {
         .map_err(|e| BinError::InvalidInput(e.to_string()))?
     }
 
+    async fn update_paste(&self, paste: Paste, uses: Option<u32>, revision: Option<(String, Vec<PasteFile>)>) -> Result<(), BinError> {
+        let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| BinError::Internal("database mutex poisoned".into()))?;
+            let tx = conn.transaction()?;
+
+            // Remaining uses are left to consume_paste unless the owner sets them
+            let updated = tx.execute(
+                r#"
+                UPDATE pastes SET password_hash = ?1, expiry = ?2, uses = COALESCE(?3, uses), language = ?4, filename = ?5, forks_consume = ?6
+                WHERE id = ?7
+                "#,
+                params![
+                    paste.password_hash,
+                    paste.expiry as i64,
+                    uses.map(|uses| uses as i64),
+                    paste.language,
+                    paste.filename,
+                    paste.forks_consume as i64,
+                    paste.id
+                ],
+            )?;
+
+            if updated == 0 {
+                return Err(BinError::NotFound);
+            }
+
+            // New content becomes the next revision in the same transaction
+            if let Some((content, files)) = revision {
+                let id = &paste.id;
+                let revision: i64 = tx.query_row(
+                    "SELECT revision FROM pastes WHERE id = ?1",
+                    [id],
+                    |row| row.get(0),
+                )?;
+                let revision = revision + 1;
+
+                let key_id = keyring.current_id();
+                let revision_id = revision_row(id, revision as u32);
+                let paste_content = compress_column(&keyring, "pastes.content", id, &content)?;
+                let paste_files = files_to_column(&keyring, "pastes.files", id, &files)?;
+                let revision_content = compress_column(&keyring, "paste_revisions.content", &revision_id, &content)?;
+                let revision_files = files_to_column(&keyring, "paste_revisions.files", &revision_id, &files)?;
+
+                let now = std::time::SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_err(|_| BinError::InvalidInput("Invalid timestamp".into()))?
+                    .as_secs() as i64;
+
+                tx.execute(
+                    "UPDATE pastes SET content = ?1, files = ?2, revision = ?3, key_id = ?4, codec = ?5 WHERE id = ?6",
+                    params![paste_content, paste_files, revision, key_id, codec::DEFLATE, id],
+                )?;
+                tx.execute(
+                    "INSERT INTO paste_revisions (paste_id, number, content, created_at, files, key_id, codec) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![id, revision, revision_content, now, revision_files, key_id, codec::DEFLATE],
+                )?;
+            }
+
+            tx.commit()?;
+            Ok::<(), BinError>(())
+        })
+        .await
+        .map_err(|e| BinError::InvalidInput(e.to_string()))?
+    }
+
+    async fn get_revision(&self, id: &str, number: u32) -> Result<PasteRevision, BinError> {
+        let id = id.to_owned();
+        let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| BinError::Internal("database mutex poisoned".into()))?;
+
+            let revision = conn.query_row(
+                &format!("SELECT {} FROM paste_revisions WHERE paste_id = ?1 AND number = ?2", REVISION_COLUMNS),
+                params![&id, number as i64],
+                |row| revision_from_row(row, &keyring, &id),
+            ).optional()?.ok_or(BinError::RevisionNotFound)?;
+
+            Ok::<PasteRevision, BinError>(revision)
+        })
+        .await
+        .map_err(|e| BinError::InvalidInput(e.to_string()))?
+    }
+
+    async fn list_revisions(&self, id: &str) -> Result<Vec<RevisionInfo>, BinError> {
+        let id = id.to_owned();
+        let conn = self.conn.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| BinError::Internal("database mutex poisoned".into()))?;
+
+            let mut stmt = conn.prepare(
+                "SELECT number, created_at FROM paste_revisions WHERE paste_id = ?1 ORDER BY number"
+            )?;
+            let revisions = stmt.query_map([&id], |row| {
+                let created_at = UNIX_EPOCH + std::time::Duration::from_secs(row.get::<_, i64>(1)? as u64);
+
+                Ok(RevisionInfo {
+                    number: row.get::<_, i64>(0)? as u32,
+                    created_at: created_at.into(),
+                })
+            })?;
+
+            Ok::<Vec<RevisionInfo>, BinError>(revisions.collect::<Result<Vec<_>, _>>()?)
+        })
+        .await
+        .map_err(|e| BinError::InvalidInput(e.to_string()))?
+    }
+
+    async fn list_forks(&self, id: &str) -> Result<Vec<String>, BinError> {
+        let id = id.to_owned();
+        let conn = self.conn.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| BinError::Internal("database mutex poisoned".into()))?;
+
+            let now = std::time::SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| BinError::InvalidInput("Invalid timestamp".into()))?
+                .as_secs() as i64;
+
+            let mut stmt = conn.prepare(
+                "SELECT id FROM pastes WHERE parent_id = ?1 AND (created_at + expiry * 3600) >= ?2 ORDER BY created_at"
+            )?;
+            let forks = stmt.query_map(params![&id, now], |row| row.get::<_, String>(0))?;
+
+            Ok::<Vec<String>, BinError>(forks.collect::<Result<Vec<_>, _>>()?)
+        })
+        .await
+        .map_err(|e| BinError::InvalidInput(e.to_string()))?
+    }
+
+    async fn delete_paste(&self, id: &str) -> Result<(), BinError> {
+        let id = id.to_owned();
+        let conn = self.conn.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| BinError::Internal("database mutex poisoned".into()))?;
+            let tx = conn.transaction()?;
+
+            if tx.execute("DELETE FROM pastes WHERE id = ?1", [&id])? == 0 {
+                return Err(BinError::NotFound);
+            }
+
+            tx.execute("DELETE FROM paste_revisions WHERE paste_id = ?1", [&id])?;
+            tx.commit()?;
+
+            Ok::<(), BinError>(())
+        })
+        .await
+        .map_err(|e| BinError::InvalidInput(e.to_string()))?
+    }
+
     async fn cleanup_expired(&self) -> Result<(), Box<dyn std::error::Error + Send>> {
         let conn_clone = self.conn.clone();
 
@@ -198,6 +853,9 @@ impl PasteStore for SqliteStorage {
         
             conn.execute("DELETE FROM pastes WHERE (created_at + expiry * 3600) < ?1", params![now])
                 .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+            conn.execute("DELETE FROM paste_revisions WHERE paste_id NOT IN (SELECT id FROM pastes)", [])
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
         
             Ok::<(), Box<dyn std::error::Error + Send>>(())
         })
@@ -212,10 +870,12 @@ impl PasteStore for SqliteStorage {
 impl EventStore for SqliteStorage {
     async fn create_event(&self, event: Event) -> Result<(), CalError> {
         let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
 
         tokio::task::spawn_blocking(move || {
             let content = serde_json::to_string(&event.content)
                 .map_err(|e| CalError::InvalidInput(e.to_string()))?;
+            let content = keyring.seal("events.content", &event.id, &content).map_err(CalError::Internal)?;
 
             let created_at = event.created_at
                 .duration_since(UNIX_EPOCH)
@@ -231,8 +891,8 @@ impl EventStore for SqliteStorage {
             conn.execute(
                 r#"
                 INSERT INTO events
-                (id, content, password_hash, expiry, uses, created_at, created_utc)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                (id, content, password_hash, expiry, uses, created_at, created_utc, manage_token_hash, key_id)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
                 "#,
                 params![
                     event.id,
@@ -241,7 +901,9 @@ impl EventStore for SqliteStorage {
                     event.expiry as i64,
                     event.uses as i64,
                     created_at,
-                    created_utc
+                    created_utc,
+                    event.manage_token_hash,
+                    keyring.current_id()
                 ],
             )?;
 
@@ -254,6 +916,7 @@ impl EventStore for SqliteStorage {
     async fn get_event(&self, id: &str) -> Result<Event, CalError> {
         let id = id.to_owned();
         let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
 
         tokio::task::spawn_blocking(move || {
             let conn = conn
@@ -261,31 +924,12 @@ impl EventStore for SqliteStorage {
                 .map_err(|_| CalError::Internal("database mutex poisoned".into()))?;
 
             conn.query_row(
-                r#"
-                SELECT id, content, password_hash, expiry, uses, created_at, created_utc
-                FROM events WHERE id = ?1
-                "#,
+                &format!("SELECT {} FROM events WHERE id = ?1", EVENT_COLUMNS),
                 [&id],
-                |row| {
-                    let content: String = row.get(1)?;
-                    let content: EventContent = serde_json::from_str(&content)
-                        .map_err(|_| rusqlite::Error::InvalidQuery)?;
-                    let created_utc_ts: i64 = row.get(6)?;
-                    let created_utc = chrono::DateTime::<chrono::Utc>::from_timestamp(created_utc_ts, 0)
-                        .ok_or(rusqlite::Error::InvalidQuery)?;
-
-                    Ok(Event {
-                        id: row.get(0)?,
-                        content,
-                        password_hash: row.get(2)?,
-                        expiry: row.get::<_, i64>(3)? as u32,
-                        uses: row.get::<_, i64>(4)? as u32,
-                        created_at: UNIX_EPOCH + std::time::Duration::from_secs(row.get::<_, i64>(5)? as u64),
-                        created_utc,
-                    })
-                },
+                |row| event_from_row(row, &keyring),
             )
-            .map_err(|_| CalError::NotFound)
+            .optional()?
+            .ok_or(CalError::NotFound)
         })
         .await
         .map_err(|e| CalError::InvalidInput(e.to_string()))?
@@ -294,33 +938,17 @@ impl EventStore for SqliteStorage {
     async fn consume_event(&self, id: &str) -> Result<Event, CalError> {
         let id = id.to_owned();
         let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
 
         tokio::task::spawn_blocking(move || {
             let mut conn = conn.lock().unwrap();
             let tx = conn.transaction()?;
 
             let mut event: Event = tx.query_row(
-                "SELECT id, content, password_hash, expiry, uses, created_at, created_utc FROM events WHERE id = ?1",
+                &format!("SELECT {} FROM events WHERE id = ?1", EVENT_COLUMNS),
                 [&id],
-                |row| {
-                    let content: String = row.get(1)?;
-                    let content: EventContent = serde_json::from_str(&content)
-                        .map_err(|_| rusqlite::Error::InvalidQuery)?;
-                    let created_utc_ts: i64 = row.get(6)?;
-                    let created_utc = chrono::DateTime::<chrono::Utc>::from_timestamp(created_utc_ts, 0)
-                        .ok_or(rusqlite::Error::InvalidQuery)?;
-
-                    Ok(Event {
-                        id: row.get(0)?,
-                        content,
-                        password_hash: row.get(2)?,
-                        expiry: row.get::<_, i64>(3)? as u32,
-                        uses: row.get::<_, i64>(4)? as u32,
-                        created_at: UNIX_EPOCH + std::time::Duration::from_secs(row.get::<_, i64>(5)? as u64),
-                        created_utc,
-                    })
-                },
-            ).map_err(|_| CalError::NotFound)?;
+                |row| event_from_row(row, &keyring),
+            ).optional()?.ok_or(CalError::NotFound)?;
 
             if event.uses > 0 {
                 event.uses -= 1;
@@ -339,6 +967,64 @@ impl EventStore for SqliteStorage {
         .map_err(|e| CalError::InvalidInput(e.to_string()))?
     }
 
+    async fn update_event(&self, event: Event, uses: Option<u32>) -> Result<(), CalError> {
+        let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let content = serde_json::to_string(&event.content)
+                .map_err(|e| CalError::InvalidInput(e.to_string()))?;
+            let content = keyring.seal("events.content", &event.id, &content).map_err(CalError::Internal)?;
+
+            let conn = conn
+                .lock()
+                .map_err(|_| CalError::Internal("database mutex poisoned".into()))?;
+
+            // Remaining uses are left to consume_event unless the owner sets them
+            let updated = conn.execute(
+                r#"
+                UPDATE events SET content = ?1, password_hash = ?2, expiry = ?3, uses = COALESCE(?4, uses), key_id = ?5
+                WHERE id = ?6
+                "#,
+                params![
+                    content,
+                    event.password_hash,
+                    event.expiry as i64,
+                    uses.map(|uses| uses as i64),
+                    keyring.current_id(),
+                    event.id
+                ],
+            )?;
+
+            if updated == 0 {
+                return Err(CalError::NotFound);
+            }
+
+            Ok::<(), CalError>(())
+        })
+        .await
+        .map_err(|e| CalError::InvalidInput(e.to_string()))?
+    }
+
+    async fn delete_event(&self, id: &str) -> Result<(), CalError> {
+        let id = id.to_owned();
+        let conn = self.conn.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = conn
+                .lock()
+                .map_err(|_| CalError::Internal("database mutex poisoned".into()))?;
+
+            if conn.execute("DELETE FROM events WHERE id = ?1", [&id])? == 0 {
+                return Err(CalError::NotFound);
+            }
+
+            Ok::<(), CalError>(())
+        })
+        .await
+        .map_err(|e| CalError::InvalidInput(e.to_string()))?
+    }
+
     async fn cleanup_expired(&self) -> Result<(), Box<dyn std::error::Error + Send>> {
         let conn_clone = self.conn.clone();
 
@@ -365,10 +1051,12 @@ impl EventStore for SqliteStorage {
 impl SurveyStore for SqliteStorage {
     async fn create_survey(&self, survey: Survey) -> Result<(), AskError> {
         let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
 
         tokio::task::spawn_blocking(move || {
             let content = serde_json::to_string(&survey.content)
                 .map_err(|e| AskError::InvalidInput(e.to_string()))?;
+            let content = keyring.seal("surveys.content", &survey.id, &content).map_err(AskError::Internal)?;
 
             let created_at = survey.created_at
                 .duration_since(UNIX_EPOCH)
@@ -381,15 +1069,17 @@ impl SurveyStore for SqliteStorage {
             
             conn.execute(
                 r#"
-                INSERT INTO surveys (id, content, password_hash, expiry, created_at)
-                VALUES (?1, ?2, ?3, ?4, ?5)
+                INSERT INTO surveys (id, content, password_hash, expiry, created_at, manage_token_hash, key_id)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
                 "#,
                 params![
                     survey.id,
                     content,
                     survey.password_hash,
                     survey.expiry as i64,
-                    created_at
+                    created_at,
+                    survey.manage_token_hash,
+                    keyring.current_id()
                 ],
             )?;
 
@@ -402,6 +1092,7 @@ impl SurveyStore for SqliteStorage {
     async fn get_survey(&self, id: &str) -> Result<Survey, AskError> {
         let id = id.to_owned();
         let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
 
         tokio::task::spawn_blocking(move || {
             let conn = conn
@@ -409,30 +1100,15 @@ impl SurveyStore for SqliteStorage {
                 .map_err(|_| AskError::Internal("database mutex poisoned".into()))?;
 
             let survey = conn.query_row(
-                "SELECT id, content, password_hash, expiry, created_at FROM surveys WHERE id = ?1",
+                &format!("SELECT {} FROM surveys WHERE id = ?1", SURVEY_COLUMNS),
                 [&id],
-                |row| {
-                    let content: String = row.get(1)?;
-                    let content: SurveyContent = serde_json::from_str(&content)
-                        .map_err(|_| rusqlite::Error::InvalidQuery)?;
-
-                    Ok(Survey {
-                        id: row.get(0)?,
-                        content,
-                        password_hash: row.get(2)?,
-                        expiry: row.get::<_, i64>(3)? as u32,
-                        created_at: UNIX_EPOCH + std::time::Duration::from_secs(row.get::<_, i64>(4)? as u64),
-                        votes: Vec::new(),
-                    })
-                },
-            ).map_err(|_| AskError::NotFound)?;
-
-            let mut stmt = conn.prepare("SELECT vote FROM survey_votes WHERE survey_id = ?1")?;
-            let votes = stmt.query_map([&id], |row| {
-                let vote: String = row.get(0)?;
-                serde_json::from_str::<StoredVote>(&vote)
-                    .map_err(|_| rusqlite::Error::InvalidQuery)
-            })?;
+                |row| survey_from_row(row, &keyring),
+            ).optional()?.ok_or(AskError::NotFound)?;
+
+            let mut stmt = conn.prepare(
+                &format!("SELECT {} FROM survey_votes WHERE survey_id = ?1 ORDER BY rowid", VOTE_COLUMNS)
+            )?;
+            let votes = stmt.query_map([&id], |row| vote_from_row(row, &keyring, &id))?;
 
             let mut survey = survey;
             survey.votes = votes.collect::<Result<Vec<_>, _>>()?;
@@ -443,32 +1119,178 @@ impl SurveyStore for SqliteStorage {
         .map_err(|e| AskError::InvalidInput(e.to_string()))?
     }
 
-    async fn add_vote(&self, id: &str, vote: StoredVote) -> Result<Survey, AskError> {
+    async fn add_vote(&self, id: &str, mut vote: StoredVote, write_ins: Vec<WriteIn>) -> Result<Survey, AskError> {
         let id = id.to_owned();
         let id_for_closure = id.clone();
         let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
 
-        let _ = tokio::task::spawn_blocking(move || {
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| AskError::Internal("database mutex poisoned".into()))?;
+            let tx = conn.transaction()?;
+
+            place_write_ins_in(&tx, &keyring, &id, &mut vote, &write_ins)?;
+
+            // Every stored vote has a ballot id, its sealed value is bound to it
+            let ballot_id = vote.ballot_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
             let vote_json = serde_json::to_string(&vote)
                 .map_err(|e| AskError::InvalidInput(e.to_string()))?;
+            let vote_json = keyring.seal("survey_votes.vote", &vote_row(&id, &ballot_id), &vote_json).map_err(AskError::Internal)?;
+
+            tx.execute(
+                "INSERT INTO survey_votes (survey_id, vote, ballot_id, participant, key_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![&id, vote_json, ballot_id, vote.participant, keyring.current_id()],
+            ).map_err(|e| match e {
+                rusqlite::Error::SqliteFailure(failure, _) if failure.code == rusqlite::ErrorCode::ConstraintViolation => {
+                    AskError::AlreadyVoted
+                }
+                e => e.into(),
+            })?;
+            tx.commit()?;
 
+            Ok::<(), AskError>(())
+        })
+        .await
+        .map_err(|e| AskError::InvalidInput(e.to_string()))??;
+
+        self.get_survey(&id_for_closure).await
+    }
+
+    async fn update_vote(&self, id: &str, mut vote: StoredVote, write_ins: Vec<WriteIn>) -> Result<Survey, AskError> {
+        let id = id.to_owned();
+        let id_for_closure = id.clone();
+        let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| AskError::Internal("database mutex poisoned".into()))?;
+            let tx = conn.transaction()?;
+
+            place_write_ins_in(&tx, &keyring, &id, &mut vote, &write_ins)?;
+
+            let ballot_id = vote.ballot_id.clone().ok_or(AskError::NotFound)?;
+            let vote_json = serde_json::to_string(&vote)
+                .map_err(|e| AskError::InvalidInput(e.to_string()))?;
+            let vote_json = keyring.seal("survey_votes.vote", &vote_row(&id, &ballot_id), &vote_json).map_err(AskError::Internal)?;
+
+            let updated = tx.execute(
+                "UPDATE survey_votes SET vote = ?1, key_id = ?2 WHERE survey_id = ?3 AND ballot_id = ?4",
+                params![vote_json, keyring.current_id(), &id, ballot_id],
+            )?;
+
+            if updated == 0 {
+                return Err(AskError::NotFound);
+            }
+            tx.commit()?;
+
+            Ok::<(), AskError>(())
+        })
+        .await
+        .map_err(|e| AskError::InvalidInput(e.to_string()))??;
+
+        self.get_survey(&id_for_closure).await
+    }
+
+    async fn delete_vote(&self, id: &str, ballot_id: &str) -> Result<Survey, AskError> {
+        let id = id.to_owned();
+        let id_for_closure = id.clone();
+        let ballot_id = ballot_id.to_owned();
+        let conn = self.conn.clone();
+
+        tokio::task::spawn_blocking(move || {
             let conn = conn
                 .lock()
                 .map_err(|_| AskError::Internal("database mutex poisoned".into()))?;
 
-            conn.execute(
-                "INSERT INTO survey_votes (survey_id, vote) VALUES (?1, ?2)",
-                params![&id, vote_json],
+            let deleted = conn.execute(
+                "DELETE FROM survey_votes WHERE survey_id = ?1 AND ballot_id = ?2",
+                params![&id, &ballot_id],
             )?;
 
+            if deleted == 0 {
+                return Err(AskError::NotFound);
+            }
+
             Ok::<(), AskError>(())
         })
         .await
-        .map_err(|e| AskError::InvalidInput(e.to_string()))?;
+        .map_err(|e| AskError::InvalidInput(e.to_string()))??;
 
         self.get_survey(&id_for_closure).await
     }
 
+    async fn update_survey(&self, survey: Survey) -> Result<(), AskError> {
+        let conn = self.conn.clone();
+        let keyring = self.keyring.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let content = serde_json::to_string(&survey.content)
+                .map_err(|e| AskError::InvalidInput(e.to_string()))?;
+            let content = keyring.seal("surveys.content", &survey.id, &content).map_err(AskError::Internal)?;
+
+            let conn = conn
+                .lock()
+                .map_err(|_| AskError::Internal("database mutex poisoned".into()))?;
+
+            // Content is written as a whole, so it only applies to the version it was read at
+            let updated = conn.execute(
+                r#"
+                UPDATE surveys SET content = ?1, password_hash = ?2, expiry = ?3, key_id = ?4, version = version + 1
+                WHERE id = ?5 AND version = ?6
+                "#,
+                params![
+                    content,
+                    survey.password_hash,
+                    survey.expiry as i64,
+                    keyring.current_id(),
+                    survey.id,
+                    survey.version as i64
+                ],
+            )?;
+
+            if updated == 0 {
+                let exists: bool = conn.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM surveys WHERE id = ?1)",
+                    [&survey.id],
+                    |row| row.get(0),
+                )?;
+
+                return Err(if exists { AskError::Conflict } else { AskError::NotFound });
+            }
+
+            Ok::<(), AskError>(())
+        })
+        .await
+        .map_err(|e| AskError::InvalidInput(e.to_string()))?
+    }
+
+    async fn delete_survey(&self, id: &str) -> Result<(), AskError> {
+        let id = id.to_owned();
+        let conn = self.conn.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| AskError::Internal("database mutex poisoned".into()))?;
+            let tx = conn.transaction()?;
+
+            if tx.execute("DELETE FROM surveys WHERE id = ?1", [&id])? == 0 {
+                return Err(AskError::NotFound);
+            }
+
+            tx.execute("DELETE FROM survey_votes WHERE survey_id = ?1", [&id])?;
+            tx.commit()?;
+
+            Ok::<(), AskError>(())
+        })
+        .await
+        .map_err(|e| AskError::InvalidInput(e.to_string()))?
+    }
+
     async fn cleanup_expired(&self) -> Result<(), Box<dyn std::error::Error + Send>> {
         let conn_clone = self.conn.clone();
     
@@ -679,3 +1501,185 @@ impl BanStore for SqliteStorage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use std::time::SystemTime;
+
+    fn keyring(entries: &[(&str, u8)]) -> Keyring {
+        let entries: Vec<String> = entries.iter().map(|&(id, byte)| format!("{}:{}", id, STANDARD.encode([byte; 32]))).collect();
+        Keyring::parse(&entries.join("\n")).expect("valid keys")
+    }
+
+    // Same database, read and written with another keyring
+    fn rotated(storage: &SqliteStorage, keyring: Keyring) -> SqliteStorage {
+        SqliteStorage { conn: storage.conn.clone(), keyring: Arc::new(keyring) }
+    }
+
+    fn paste(id: &str, content: &str) -> Paste {
+        Paste {
+            id: id.into(),
+            content: content.into(),
+            password_hash: None,
+            expiry: u32::MAX,
+            uses: 0,
+            created_at: SystemTime::now(),
+            manage_token_hash: None,
+            revision: 1,
+            language: None,
+            filename: None,
+            files: Vec::new(),
+            attachment: None,
+            encrypted: false,
+            parent_id: None,
+            forks_consume: false,
+        }
+    }
+
+    fn stored(storage: &SqliteStorage, sql: &str) -> Vec<(Option<String>, String)> {
+        let conn = storage.conn.lock().unwrap();
+        let mut stmt = conn.prepare(sql).unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.collect::<Result<_, _>>().unwrap()
+    }
+
+    #[tokio::test]
+    async fn content_is_sealed_at_rest_and_opens_on_read() {
+        let storage = SqliteStorage::new(":memory:", keyring(&[("k1", 1)])).unwrap();
+        storage.create_paste(paste("a", "secret notes")).await.unwrap();
+        storage.update_paste(paste("a", ""), None, Some(("more secrets".into(), Vec::new()))).await.unwrap();
+
+        for (key_id, content) in stored(&storage, "SELECT key_id, content FROM pastes")
+            .into_iter()
+            .chain(stored(&storage, "SELECT key_id, content FROM paste_revisions"))
+        {
+            assert_eq!(key_id.as_deref(), Some("k1"));
+            assert!(!content.contains("secret"));
+        }
+
+        assert_eq!(storage.get_paste("a").await.unwrap().content, "more secrets");
+        assert_eq!(storage.get_revision("a", 1).await.unwrap().content, "secret notes");
+    }
+
+    #[tokio::test]
+    async fn sealed_values_moved_to_another_row_do_not_open() {
+        let storage = SqliteStorage::new(":memory:", keyring(&[("k1", 1)])).unwrap();
+        storage.create_paste(paste("a", "secret notes")).await.unwrap();
+        storage.create_paste(paste("b", "other notes")).await.unwrap();
+
+        storage.conn.lock().unwrap().execute(
+            "UPDATE pastes SET content = (SELECT content FROM pastes WHERE id = 'a') WHERE id = 'b'",
+            [],
+        ).unwrap();
+
+        assert!(storage.get_paste("a").await.is_ok());
+        assert!(storage.get_paste("b").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rotated_keys_keep_old_rows_readable_until_reencrypted() {
+        let storage = SqliteStorage::new(":memory:", keyring(&[("old", 1)])).unwrap();
+        storage.create_paste(paste("a", "secret notes")).await.unwrap();
+
+        let rotated_in = rotated(&storage, keyring(&[("new", 2), ("old", 1)]));
+        storage.create_paste(paste("b", "newer notes")).await.unwrap();
+        assert_eq!(rotated_in.get_paste("a").await.unwrap().content, "secret notes");
+
+        // Paste and first revision of "a" move over, "b" already has the current key
+        let rotated_in = rotated(&storage, keyring(&[("new", 2), ("old", 1)]));
+        assert_eq!(rotated_in.reencrypt().unwrap(), 4);
+        assert_eq!(rotated_in.reencrypt().unwrap(), 0);
+
+        let old_dropped = rotated(&storage, keyring(&[("new", 2)]));
+        assert!(old_dropped.missing_keys().unwrap().is_empty());
+        assert_eq!(old_dropped.get_paste("a").await.unwrap().content, "secret notes");
+    }
+
+    #[tokio::test]
+    async fn reencrypting_seals_rows_from_before_encryption() {
+        let storage = SqliteStorage::new(":memory:", Keyring::default()).unwrap();
+        storage.create_paste(paste("a", "old notes")).await.unwrap();
+        assert_eq!(stored(&storage, "SELECT key_id, typeof(content) FROM pastes"), vec![(None, "blob".to_string())]);
+
+        let sealing = rotated(&storage, keyring(&[("k1", 1)]));
+        assert_eq!(sealing.reencrypt().unwrap(), 2);
+        assert_eq!(stored(&storage, "SELECT key_id, content FROM pastes")[0].0.as_deref(), Some("k1"));
+        assert_eq!(sealing.get_revision("a", 1).await.unwrap().content, "old notes");
+    }
+
+    #[tokio::test]
+    async fn missing_keys_lists_key_ids_the_keyring_cannot_open() {
+        let storage = SqliteStorage::new(":memory:", keyring(&[("k1", 1)])).unwrap();
+        storage.create_paste(paste("a", "secret notes")).await.unwrap();
+
+        assert!(storage.missing_keys().unwrap().is_empty());
+        assert_eq!(rotated(&storage, keyring(&[("k2", 2)])).missing_keys().unwrap(), vec!["k1".to_string()]);
+        assert_eq!(rotated(&storage, Keyring::default()).missing_keys().unwrap(), vec!["k1".to_string()]);
+    }
+
+    #[test]
+    fn legacy_votes_get_distinct_ballot_ids() {
+        let path = std::env::temp_dir().join(format!("polly-{}.sqlite", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+
+        rusqlite::Connection::open(path).unwrap().execute_batch(
+            r#"
+            CREATE TABLE survey_votes (survey_id TEXT NOT NULL, vote TEXT NOT NULL);
+            INSERT INTO survey_votes (survey_id, vote) VALUES ('s', '{}'), ('s', '{}');
+            "#,
+        ).unwrap();
+
+        let storage = SqliteStorage::new(path, Keyring::default()).unwrap();
+        let rows = stored(&storage, "SELECT ballot_id, survey_id FROM survey_votes");
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|(ballot_id, _)| ballot_id.is_some()));
+        assert_ne!(rows[0].0, rows[1].0);
+    }
+
+    #[test]
+    fn secrets_survive_a_restart() {
+        let path = std::env::temp_dir().join(format!("polly-{}.sqlite", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+
+        let first = SqliteStorage::new(path, Keyring::default()).unwrap();
+        let secret = first.secret("receipt").unwrap();
+        assert_eq!(first.secret("receipt").unwrap(), secret);
+        assert_ne!(first.secret("other").unwrap(), secret);
+        drop(first);
+
+        let reopened = SqliteStorage::new(path, Keyring::default()).unwrap().secret("receipt").unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(reopened, secret);
+    }
+
+    #[tokio::test]
+    async fn revisions_are_compressed_before_they_are_sealed() {
+        let storage = SqliteStorage::new(":memory:", keyring(&[("k1", 1)])).unwrap();
+        let text = "let x = 1;\n".repeat(500);
+        storage.create_paste(paste("a", &text)).await.unwrap();
+
+        let (codec, content) = stored(&storage, "SELECT codec, content FROM paste_revisions").remove(0);
+        assert_eq!(codec.as_deref(), Some(codec::DEFLATE));
+        assert!(content.len() < text.len() / 10);
+        assert_eq!(storage.get_revision("a", 1).await.unwrap().content, text);
+    }
+
+    #[tokio::test]
+    async fn uncompressed_revisions_from_before_codecs_still_open() {
+        let storage = SqliteStorage::new(":memory:", keyring(&[("k1", 1)])).unwrap();
+        storage.create_paste(paste("a", "v1")).await.unwrap();
+
+        let sealed = storage.keyring.seal("paste_revisions.content", &revision_row("a", 1), "legacy text").unwrap();
+        storage.conn.lock().unwrap().execute(
+            "UPDATE paste_revisions SET content = ?1, codec = NULL WHERE paste_id = 'a'",
+            [sealed],
+        ).unwrap();
+
+        assert_eq!(storage.get_revision("a", 1).await.unwrap().content, "legacy text");
+    }
+}
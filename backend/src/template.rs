@@ -0,0 +1,62 @@
+use serde::Deserialize;
+
+use crate::ask::{NewQuestion, RatingRange, SurveyType};
+
+/*
+ * --- Built-in survey templates ---
+ */
+
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SurveyTemplate {
+    YesNoMaybe,
+    Rating,
+    Weekdays,
+}
+
+const WEEKDAYS: [&str; 5] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday"];
+
+impl SurveyTemplate {
+    // Used when the survey is created without a title of its own
+    pub fn title(self) -> &'static str {
+        match self {
+            SurveyTemplate::YesNoMaybe => "Yes, no or maybe?",
+            SurveyTemplate::Rating => "How would you rate it?",
+            SurveyTemplate::Weekdays => "Which weekdays work for you?",
+        }
+    }
+
+    pub fn question(self) -> NewQuestion {
+        match self {
+            SurveyTemplate::YesNoMaybe => {
+                choice_question(SurveyType::SingleChoice, &["Yes", "No", "Maybe"])
+            }
+            SurveyTemplate::Rating => NewQuestion {
+                rating_range: Some(RatingRange { min: 1, max: 5 }),
+                ..choice_question(SurveyType::RatingScale, &["Rating"])
+            },
+            // Matrix answers are yes, maybe or no per voter and day
+            SurveyTemplate::Weekdays => choice_question(SurveyType::MatrixChoice, &WEEKDAYS),
+        }
+    }
+}
+
+fn choice_question(survey_type: SurveyType, items: &[&str]) -> NewQuestion {
+    NewQuestion {
+        prompt: String::new(),
+        survey_type,
+        items: items.iter().map(|item| item.to_string()).collect(),
+        required: true,
+        slots: Vec::new(),
+        timezone: String::new(),
+        rank_method: None,
+        min_choices: None,
+        max_choices: None,
+        rating_range: None,
+        rating_ranges: None,
+        min_text_len: None,
+        max_text_len: None,
+        allow_write_ins: false,
+        max_write_ins: None,
+    }
+}
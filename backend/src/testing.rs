@@ -0,0 +1,155 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::ConnectInfo,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use axum_extra::headers::{Authorization, HeaderMapExt};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::state::AppState;
+use crate::storage::attachments::{AttachmentDir, DEFAULT_MAX_ATTACHMENT_SIZE};
+
+/*
+ * --- Test client ---
+ */
+
+// The whole app with in-memory stores, driven like a client would, proof of work included
+pub struct TestApp {
+    app: Router,
+    pow: Option<(String, String, String)>, // solved challenge, nonce and hash, valid for every request of a test
+    data_dir: PathBuf,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or_else(|_| panic!("not JSON: {}", self.text()))
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        let data_dir = std::env::temp_dir().join(format!("polly-test-{}", Uuid::new_v4()));
+        let state = AppState {
+            attachments: AttachmentDir::new(data_dir.join("attachments"), DEFAULT_MAX_ATTACHMENT_SIZE),
+            ..AppState::default()
+        };
+
+        let mut app = Self {
+            app: crate::app(state),
+            pow: None,
+            data_dir,
+        };
+
+        // A create without proof of work is answered with a fresh challenge
+        let demand = app.request(Method::POST, "/api/bin", &[], Body::empty()).await;
+        assert_eq!(demand.status, StatusCode::PRECONDITION_REQUIRED);
+        let challenge = demand.header("X-POW-Challenge").expect("challenge").to_string();
+        let difficulty: u32 = demand.header("X-POW-Difficulty").expect("difficulty").parse().expect("number");
+
+        let (nonce, hash) = (0u64..)
+            .map(|nonce| (nonce, Sha256::digest(format!("{}{}", challenge, nonce))))
+            .find(|(_, hash)| leading_zero_bits(hash) >= difficulty)
+            .expect("nonce");
+
+        app.pow = Some((challenge, nonce.to_string(), hex::encode(hash)));
+        app
+    }
+
+    pub async fn request(&self, method: Method, uri: &str, headers: &[(&str, &str)], body: Body) -> TestResponse {
+        let mut request = Request::builder().method(method).uri(uri);
+
+        if let Some((challenge, nonce, hash)) = &self.pow {
+            request = request
+                .header("X-POW-Challenge", challenge)
+                .header("X-POW-Nonce", nonce)
+                .header("X-POW-Hash", hash);
+        }
+
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        // Sent by every real client for a body of known size
+        if let Some(length) = axum::body::HttpBody::size_hint(&body).exact().filter(|&length| length > 0) {
+            request = request.header(header::CONTENT_LENGTH, length);
+        }
+
+        let mut request = request.body(body).expect("request");
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))));
+
+        let response = self.app.clone().oneshot(request).await.expect("response");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.expect("body");
+
+        TestResponse { status, headers, body }
+    }
+
+    pub async fn get(&self, uri: &str, headers: &[(&str, &str)]) -> TestResponse {
+        self.request(Method::GET, uri, headers, Body::empty()).await
+    }
+
+    pub async fn send_json(&self, method: Method, uri: &str, headers: &[(&str, &str)], body: Value) -> TestResponse {
+        let mut headers = headers.to_vec();
+        headers.push((header::CONTENT_TYPE.as_str(), "application/json"));
+        self.request(method, uri, &headers, Body::from(body.to_string())).await
+    }
+
+    pub async fn post_json(&self, uri: &str, body: Value) -> TestResponse {
+        self.send_json(Method::POST, uri, &[], body).await
+    }
+
+    // Creates something through its JSON endpoint and returns its id and management token
+    pub async fn create(&self, uri: &str, body: Value) -> (String, String) {
+        let response = self.post_json(uri, body).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+
+        let json = response.json();
+        (json["id"].as_str().expect("id").into(), json["manage_token"].as_str().expect("token").into())
+    }
+
+    pub fn attachment_files(&self) -> Vec<String> {
+        std::fs::read_dir(self.data_dir.join("attachments"))
+            .map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| entry.file_name().to_string_lossy().into_owned()).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.data_dir).ok();
+    }
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let zero_bytes = hash.iter().take_while(|b| **b == 0).count();
+    let partial = hash.get(zero_bytes).map_or(0, |b| b.leading_zeros());
+    zero_bytes as u32 * 8 + partial
+}
+
+// Value of an Authorization header for a password-protected record, which has no user name
+pub fn basic_auth(password: &str) -> String {
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(Authorization::basic("", password));
+    headers[header::AUTHORIZATION].to_str().expect("header").to_string()
+}
@@ -332,7 +332,7 @@ function aggregateVotes(items, votes, type) {
   return { absoluteCounts, relativeCounts, voteScores };
 }
 
-function openSurveyBox(surveyId, content, votes) {
+function openSurveyBox(surveyId, content, votes, resultsVisible = true) {
   function items_survey_sc_mc_rank(items, votes, type) {
     const { absoluteCounts, relativeCounts, voteScores } = aggregateVotes(items, votes, type);
     const currentHighestScore = voteScores.indexOf(Math.max(...voteScores));
@@ -517,6 +517,12 @@ function openSurveyBox(surveyId, content, votes) {
   openedIdBoxHtml.querySelector('.opened-survey-type').dataset.type = content.survey_type;
   openedIdBoxHtml.querySelector('.opened-survey-type').textContent = type;
 
+  // Hidden results come without votes, so there is nothing to count
+  if (!resultsVisible) {
+    openedIdBoxHtml.querySelector('.opened-survey-type').textContent += " (results hidden)";
+    openedIdBoxHtml.querySelectorAll('.opened-survey-choice-stats').forEach(el => el.remove());
+  }
+
   if(Array.isArray(items)) {
     items.forEach(item => openedIdBoxHtml.querySelector('.opened-survey-choices').append(item));
   } else {
@@ -627,7 +633,7 @@ async function submitOpenIdForm(rawInput = '', password = '') {
     }
 
     if (result?.status === 'success') {
-      openSurveyBox(result.data.id, result.data.content, result.data.votes);
+      openSurveyBox(result.data.id, result.data.content, result.data.votes ?? [], result.data.results_visible);
     } else if (result?.status === 'protected') {
       const openSurveyPw = document.getElementById('open-survey-pw');
       openSurveyPw.dataset.id = id;
@@ -715,7 +721,7 @@ async function submitVoteIdForm(id, voteData, password = '') {
     }
 
     if (result?.status === 'success') {
-      openSurveyBox(result.data.id, result.data.content, result.data.votes);
+      openSurveyBox(result.data.id, result.data.content, result.data.votes ?? [], result.data.results_visible);
     } else if (result?.status === 'protected') {
       const voteSurveyPw = document.getElementById('vote-survey-pw');
       voteSurveyPw.dataset.id = id;
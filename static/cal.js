@@ -659,8 +659,9 @@ async function submitOpenIdForm(rawInput = '', password = '') {
   try {
     const headers = password ? { 'Authorization': 'Basic ' + btoa(':' + password) } : {};
 
-    const response = await fetch(`/api/cal/${encodeURIComponent(id)}`, {
-      method: 'GET',
+    // Revealing counts as a use, a plain GET only describes it
+    const response = await fetch(`/api/cal/${encodeURIComponent(id)}/reveal`, {
+      method: 'POST',
       headers,
     });
 